@@ -0,0 +1,88 @@
+// cargo bench --bench spatial_index
+// Compares the quadtree against checking every pair of rectangles.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_book::geometry::index::QuadTree;
+use rust_book::geometry::Rectangle;
+
+const WORLD: u32 = 10_000;
+
+fn rectangles(count: usize) -> Vec<Rectangle> {
+    let mut rng = StdRng::seed_from_u64(42);
+    (0..count)
+        .map(|_| {
+            Rectangle::new(
+                rng.gen_range(0..WORLD as i32),
+                rng.gen_range(0..WORLD as i32),
+                rng.gen_range(1..50),
+                rng.gen_range(1..50),
+            )
+        })
+        .collect()
+}
+
+fn naive_collisions(rects: &[Rectangle]) -> usize {
+    let mut count = 0;
+    for (i, a) in rects.iter().enumerate() {
+        for b in &rects[i + 1..] {
+            if a.overlaps(b) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+fn naive_query(rects: &[Rectangle], region: &Rectangle) -> usize {
+    rects.iter().filter(|r| r.overlaps(region)).count()
+}
+
+fn build(rects: &[Rectangle]) -> QuadTree {
+    let mut tree = QuadTree::new(Rectangle::with_size(WORLD, WORLD));
+    for rect in rects {
+        tree.insert(*rect);
+    }
+    tree
+}
+
+fn collisions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collisions");
+    for count in [1_000, 10_000] {
+        let rects = rectangles(count);
+        let tree = build(&rects);
+        group.bench_with_input(BenchmarkId::new("naive", count), &rects, |b, rects| {
+            b.iter(|| naive_collisions(black_box(rects)))
+        });
+        group.bench_with_input(BenchmarkId::new("quadtree", count), &tree, |b, tree| {
+            b.iter(|| black_box(tree).collisions().len())
+        });
+    }
+    group.finish();
+}
+
+fn region_query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("region_query");
+    let region = Rectangle::new(4_000, 4_000, 500, 500);
+    for count in [1_000, 10_000, 50_000] {
+        let rects = rectangles(count);
+        let tree = build(&rects);
+        group.bench_with_input(BenchmarkId::new("naive", count), &rects, |b, rects| {
+            b.iter(|| naive_query(black_box(rects), &region))
+        });
+        group.bench_with_input(BenchmarkId::new("quadtree", count), &tree, |b, tree| {
+            b.iter(|| black_box(tree).query(&region).len())
+        });
+    }
+    group.finish();
+}
+
+fn insert(c: &mut Criterion) {
+    let rects = rectangles(10_000);
+    c.bench_function("quadtree insert 10000", |b| {
+        b.iter(|| build(black_box(&rects)))
+    });
+}
+
+criterion_group!(benches, collisions, region_query, insert);
+criterion_main!(benches);
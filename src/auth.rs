@@ -0,0 +1,571 @@
+// Credentials and sign in for the User from the structs chapter.
+// Passwords are stored as Argon2id PHC strings (salted, slow and memory-hard), never in clear text.
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::user::{User, Username};
+
+const SESSION_TOKEN_BYTES: usize = 32;
+
+// Where sign in reads the current time from, so tests can run without waiting or a network clock.
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+// Clones share the same time, so a test can keep one copy and advance it.
+#[derive(Debug, Clone)]
+pub struct FakeClock {
+    now: Rc<Cell<SystemTime>>,
+}
+
+impl FakeClock {
+    pub fn new(start: SystemTime) -> FakeClock {
+        FakeClock {
+            now: Rc::new(Cell::new(start)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Default for FakeClock {
+    fn default() -> Self {
+        FakeClock::new(SystemTime::UNIX_EPOCH)
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> SystemTime {
+        self.now.get()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    // the user is unknown or the password is wrong, on purpose not saying which
+    InvalidCredentials,
+    AlreadyRegistered,
+    Inactive,
+    Locked { until: SystemTime },
+    InvalidSession,
+    Hash(String),
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::InvalidCredentials => write!(f, "wrong username or password"),
+            AuthError::AlreadyRegistered => write!(f, "user is already registered"),
+            AuthError::Inactive => write!(f, "account is not active"),
+            AuthError::Locked { until } => write!(f, "account is locked until {:?}", until),
+            AuthError::InvalidSession => write!(f, "session token is unknown or expired"),
+            AuthError::Hash(e) => write!(f, "password hashing failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl From<argon2::password_hash::Error> for AuthError {
+    fn from(e: argon2::password_hash::Error) -> Self {
+        AuthError::Hash(e.to_string())
+    }
+}
+
+// Argon2id cost parameters. The defaults follow the OWASP recommendation (19 MiB, 2 passes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashCost {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl HashCost {
+    // Only meant for tests, where hashing at full cost would make every case slow.
+    pub fn insecure_fast() -> HashCost {
+        HashCost {
+            memory_kib: Params::MIN_M_COST,
+            iterations: Params::MIN_T_COST,
+            parallelism: 1,
+        }
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>, AuthError> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|e| AuthError::Hash(e.to_string()))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+impl Default for HashCost {
+    fn default() -> Self {
+        HashCost {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+// A PHC string such as `$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>`. It embeds its own salt
+// and cost, so verifying keeps working after the default cost changes.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PasswordDigest(String);
+
+impl PasswordDigest {
+    pub fn hash(password: &str, cost: HashCost) -> Result<PasswordDigest, AuthError> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = cost.argon2()?.hash_password(password.as_bytes(), &salt)?;
+        Ok(PasswordDigest(hash.to_string()))
+    }
+
+    pub fn verify(&self, password: &str) -> Result<bool, AuthError> {
+        let parsed = PasswordHash::new(&self.0)?;
+        match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => Ok(true),
+            Err(argon2::password_hash::Error::Password) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+// never print the digest, not even in dbg! output
+impl fmt::Debug for PasswordDigest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "PasswordDigest(..)")
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct SessionToken(String);
+
+impl SessionToken {
+    fn generate() -> SessionToken {
+        let mut bytes = [0u8; SESSION_TOKEN_BYTES];
+        OsRng.fill_bytes(&mut bytes);
+        SessionToken(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SessionToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "SessionToken(..)")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub token: SessionToken,
+    pub username: Username,
+    pub expires_at: SystemTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignInPolicy {
+    pub max_failed_attempts: u32,
+    pub lockout: Duration,
+    pub session_ttl: Duration,
+    pub hash_cost: HashCost,
+}
+
+impl Default for SignInPolicy {
+    fn default() -> Self {
+        SignInPolicy {
+            max_failed_attempts: 5,
+            lockout: Duration::from_secs(15 * 60),
+            session_ttl: Duration::from_secs(60 * 60),
+            hash_cost: HashCost::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Account {
+    pub user: User,
+    password: PasswordDigest,
+    failed_attempts: u32,
+    locked_until: Option<SystemTime>,
+}
+
+impl Account {
+    pub fn failed_attempts(&self) -> u32 {
+        self.failed_attempts
+    }
+
+    pub fn locked_until(&self) -> Option<SystemTime> {
+        self.locked_until
+    }
+
+    // Refuses while locked, a lockout that has run out starts the count over.
+    fn check_lock(&mut self, now: SystemTime) -> Result<(), AuthError> {
+        if let Some(until) = self.locked_until {
+            if now < until {
+                return Err(AuthError::Locked { until });
+            }
+            self.locked_until = None;
+            self.failed_attempts = 0;
+        }
+        Ok(())
+    }
+
+    // Counts a wrong password and locks after `max_failed_attempts` in a row,
+    // the right one resets the count.
+    fn check_password(
+        &mut self,
+        password: &str,
+        now: SystemTime,
+        policy: &SignInPolicy,
+    ) -> Result<(), AuthError> {
+        if !self.password.verify(password)? {
+            self.failed_attempts += 1;
+            if self.failed_attempts >= policy.max_failed_attempts {
+                let until = later(now, policy.lockout);
+                self.locked_until = Some(until);
+                return Err(AuthError::Locked { until });
+            }
+            return Err(AuthError::InvalidCredentials);
+        }
+        self.failed_attempts = 0;
+        Ok(())
+    }
+}
+
+// SystemTime has no MAX, so a duration that does not fit is halved until it does.
+// A lockout, session or lease that long lasts as good as forever.
+pub(crate) fn later(now: SystemTime, mut by: Duration) -> SystemTime {
+    loop {
+        if let Some(time) = now.checked_add(by) {
+            return time;
+        }
+        by /= 2;
+    }
+}
+
+pub struct Authenticator<C: Clock> {
+    clock: C,
+    policy: SignInPolicy,
+    accounts: HashMap<Username, Account>,
+    sessions: HashMap<SessionToken, Session>,
+    // checked against when the user is unknown, so that takes as long as a wrong password
+    dummy: Option<PasswordDigest>,
+}
+
+impl<C: Clock> Authenticator<C> {
+    pub fn new(clock: C, policy: SignInPolicy) -> Authenticator<C> {
+        Authenticator {
+            clock,
+            policy,
+            accounts: HashMap::new(),
+            sessions: HashMap::new(),
+            dummy: None,
+        }
+    }
+
+    // Hashes a password nobody has, once, at the policy's cost.
+    fn verify_dummy(&mut self, password: &str) -> Result<(), AuthError> {
+        if self.dummy.is_none() {
+            let dummy = PasswordDigest::hash(&SessionToken::generate().0, self.policy.hash_cost)?;
+            self.dummy = Some(dummy);
+        }
+        if let Some(dummy) = &self.dummy {
+            dummy.verify(password)?;
+        }
+        Ok(())
+    }
+
+    pub fn register(&mut self, user: User, password: &str) -> Result<(), AuthError> {
+        if self.accounts.contains_key(&user.username) {
+            return Err(AuthError::AlreadyRegistered);
+        }
+        let password = PasswordDigest::hash(password, self.policy.hash_cost)?;
+        self.accounts.insert(
+            user.username.clone(),
+            Account {
+                user,
+                password,
+                failed_attempts: 0,
+                locked_until: None,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn account(&self, username: &Username) -> Option<&Account> {
+        self.accounts.get(username)
+    }
+
+    // Wrong old passwords count towards the same lockout as sign_in.
+    pub fn change_password(
+        &mut self,
+        username: &Username,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<(), AuthError> {
+        let now = self.clock.now();
+        let Some(account) = self.accounts.get_mut(username) else {
+            self.verify_dummy(old_password)?;
+            return Err(AuthError::InvalidCredentials);
+        };
+        account.check_lock(now)?;
+        account.check_password(old_password, now, &self.policy)?;
+        account.password = PasswordDigest::hash(new_password, self.policy.hash_cost)?;
+        Ok(())
+    }
+
+    // Verifies the password, counts the sign in on the User and opens a session.
+    // After `max_failed_attempts` wrong passwords in a row the account is locked for `lockout`.
+    // An unknown user and a wrong password look the same, and an inactive account is only
+    // reported to someone who knows its password.
+    pub fn sign_in(&mut self, username: &Username, password: &str) -> Result<Session, AuthError> {
+        let now = self.clock.now();
+        let Some(account) = self.accounts.get_mut(username) else {
+            self.verify_dummy(password)?;
+            return Err(AuthError::InvalidCredentials);
+        };
+
+        account.check_lock(now)?;
+        account.check_password(password, now, &self.policy)?;
+        if !account.user.active {
+            return Err(AuthError::Inactive);
+        }
+
+        account.user.sign_in_count += 1;
+        let session = Session {
+            token: SessionToken::generate(),
+            username: username.clone(),
+            expires_at: later(now, self.policy.session_ttl),
+        };
+        self.sessions.insert(session.token.clone(), session.clone());
+        Ok(session)
+    }
+
+    // Returns the signed in user for a token that exists and has not expired yet.
+    // Expired sessions are dropped on lookup.
+    pub fn authenticate(&mut self, token: &SessionToken) -> Result<&User, AuthError> {
+        let now = self.clock.now();
+        let username = match self.sessions.get(token) {
+            Some(session) if now < session.expires_at => session.username.clone(),
+            Some(_) => {
+                self.sessions.remove(token);
+                return Err(AuthError::InvalidSession);
+            }
+            None => return Err(AuthError::InvalidSession),
+        };
+        self.accounts
+            .get(&username)
+            .map(|account| &account.user)
+            .ok_or(AuthError::InvalidSession)
+    }
+
+    pub fn sign_out(&mut self, token: &SessionToken) -> bool {
+        self.sessions.remove(token).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> SignInPolicy {
+        SignInPolicy {
+            max_failed_attempts: 3,
+            lockout: Duration::from_secs(60),
+            session_ttl: Duration::from_secs(300),
+            hash_cost: HashCost::insecure_fast(),
+        }
+    }
+
+    fn authenticator() -> (Authenticator<FakeClock>, FakeClock, Username) {
+        let clock = FakeClock::default();
+        let mut auth = Authenticator::new(clock.clone(), policy());
+        let user = User::parse("name@example.com", "name").unwrap();
+        let username = user.username.clone();
+        auth.register(user, "correct horse battery staple").unwrap();
+        (auth, clock, username)
+    }
+
+    #[test]
+    fn digest_is_salted_and_verifies() {
+        let a = PasswordDigest::hash("secret", HashCost::insecure_fast()).unwrap();
+        let b = PasswordDigest::hash("secret", HashCost::insecure_fast()).unwrap();
+        assert_ne!(a, b);
+        assert!(a.as_str().starts_with("$argon2id$"));
+        assert!(a.verify("secret").unwrap());
+        assert!(!a.verify("Secret").unwrap());
+        assert_eq!(format!("{:?}", a), "PasswordDigest(..)");
+    }
+
+    #[test]
+    fn sign_in_counts_and_opens_session() {
+        let (mut auth, clock, username) = authenticator();
+        let session = auth
+            .sign_in(&username, "correct horse battery staple")
+            .unwrap();
+        assert_eq!(session.expires_at, clock.now() + Duration::from_secs(300));
+        assert_eq!(session.token.as_str().len(), SESSION_TOKEN_BYTES * 2);
+        assert_eq!(auth.authenticate(&session.token).unwrap().sign_in_count, 2);
+
+        assert!(auth.sign_out(&session.token));
+        assert_eq!(
+            auth.authenticate(&session.token),
+            Err(AuthError::InvalidSession)
+        );
+    }
+
+    #[test]
+    fn session_expires() {
+        let (mut auth, clock, username) = authenticator();
+        let session = auth
+            .sign_in(&username, "correct horse battery staple")
+            .unwrap();
+        clock.advance(Duration::from_secs(299));
+        assert!(auth.authenticate(&session.token).is_ok());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            auth.authenticate(&session.token),
+            Err(AuthError::InvalidSession)
+        );
+    }
+
+    #[test]
+    fn repeated_failures_lock_the_account() {
+        let (mut auth, clock, username) = authenticator();
+        assert_eq!(
+            auth.sign_in(&username, "wrong").unwrap_err(),
+            AuthError::InvalidCredentials
+        );
+        assert_eq!(auth.account(&username).unwrap().failed_attempts(), 1);
+        auth.sign_in(&username, "wrong").unwrap_err();
+        let until = clock.now() + Duration::from_secs(60);
+        assert_eq!(
+            auth.sign_in(&username, "wrong").unwrap_err(),
+            AuthError::Locked { until }
+        );
+        // even the right password is refused while locked
+        assert_eq!(
+            auth.sign_in(&username, "correct horse battery staple")
+                .unwrap_err(),
+            AuthError::Locked { until }
+        );
+        assert_eq!(auth.account(&username).unwrap().user.sign_in_count, 1);
+
+        clock.advance(Duration::from_secs(60));
+        assert!(auth
+            .sign_in(&username, "correct horse battery staple")
+            .is_ok());
+        assert_eq!(auth.account(&username).unwrap().failed_attempts(), 0);
+    }
+
+    #[test]
+    fn unknown_and_inactive_users_cannot_sign_in() {
+        let (mut auth, _clock, username) = authenticator();
+        let other = Username::parse("other").unwrap();
+        // an unknown user looks just like a wrong password
+        assert_eq!(
+            auth.sign_in(&other, "x").unwrap_err(),
+            AuthError::InvalidCredentials
+        );
+        assert_eq!(
+            auth.change_password(&other, "x", "y").unwrap_err(),
+            AuthError::InvalidCredentials
+        );
+        assert!(auth.dummy.is_some());
+
+        // and an inactive one too, until the password is right
+        auth.accounts.get_mut(&username).unwrap().user.active = false;
+        assert_eq!(
+            auth.sign_in(&username, "wrong").unwrap_err(),
+            AuthError::InvalidCredentials
+        );
+        assert_eq!(
+            auth.sign_in(&username, "correct horse battery staple")
+                .unwrap_err(),
+            AuthError::Inactive
+        );
+    }
+
+    #[test]
+    fn huge_durations_do_not_overflow() {
+        let clock = FakeClock::default();
+        let policy = SignInPolicy {
+            max_failed_attempts: 1,
+            lockout: Duration::MAX,
+            session_ttl: Duration::MAX,
+            ..policy()
+        };
+        let mut auth = Authenticator::new(clock.clone(), policy);
+        let user = User::parse("name@example.com", "name").unwrap();
+        let username = user.username.clone();
+        auth.register(user, "secret").unwrap();
+
+        let session = auth.sign_in(&username, "secret").unwrap();
+        assert!(session.expires_at > clock.now() + Duration::from_secs(1 << 40));
+        assert!(matches!(
+            auth.sign_in(&username, "wrong"),
+            Err(AuthError::Locked { until }) if until > clock.now() + Duration::from_secs(1 << 40)
+        ));
+    }
+
+    #[test]
+    fn change_password_requires_old_one() {
+        let (mut auth, _clock, username) = authenticator();
+        assert!(auth.change_password(&username, "wrong", "new").is_err());
+        auth.change_password(&username, "correct horse battery staple", "new")
+            .unwrap();
+        assert!(auth.sign_in(&username, "new").is_ok());
+    }
+
+    #[test]
+    fn wrong_old_passwords_lock_the_account() {
+        let (mut auth, clock, username) = authenticator();
+        assert_eq!(
+            auth.change_password(&username, "wrong", "new").unwrap_err(),
+            AuthError::InvalidCredentials
+        );
+        // sign_in and change_password share the count
+        auth.sign_in(&username, "wrong").unwrap_err();
+        let until = clock.now() + Duration::from_secs(60);
+        assert_eq!(
+            auth.change_password(&username, "wrong", "new").unwrap_err(),
+            AuthError::Locked { until }
+        );
+        assert_eq!(
+            auth.change_password(&username, "correct horse battery staple", "new")
+                .unwrap_err(),
+            AuthError::Locked { until }
+        );
+
+        clock.advance(Duration::from_secs(60));
+        auth.change_password(&username, "correct horse battery staple", "new")
+            .unwrap();
+        assert_eq!(auth.account(&username).unwrap().failed_attempts(), 0);
+        assert!(auth.sign_in(&username, "new").is_ok());
+    }
+}
@@ -1,4 +1,11 @@
 // https://doc.rust-lang.org/book/ch08-00-common-collections.html
+use rust_book::auth::SystemClock; // from lib.rs, the scoreboard records real times
+use rust_book::counter::{Counter, StableHasher}; // from lib.rs, the word count as a multiset
+use rust_book::scoreboard::Scoreboard; // from lib.rs, the scores map with history
+use rust_book::spreadsheet::{CellRef, Sheet, SpreadsheetCell}; // from lib.rs, the row as a real sheet
+use rust_book::strings; // from lib.rs, slicing by grapheme
+use rust_book::text::{Analysis, Tokenizer}; // from lib.rs, the word counter with Unicode words
+
 fn main() {
     vectors();
     string_utf8();
@@ -6,6 +13,7 @@ fn main() {
     hash_maps();
 }
 
+#[allow(clippy::useless_vec, clippy::vec_init_then_push)] // vec! and push are what these show
 fn vectors() {
     // creating
     let _v: Vec<i32> = Vec::new();
@@ -41,18 +49,25 @@ fn vectors() {
     }
 
     // use enum to store different types
-    #[derive(Debug)]
-    enum SpreadsheetCell {
-        Int(i32),
-        Float(f64),
-        Text(String),
-    }
     let row = vec![
         SpreadsheetCell::Int(3),
         SpreadsheetCell::Text(String::from("blue")),
         SpreadsheetCell::Float(10.12),
     ];
-    dbg!(row);
+    dbg!(&row);
+
+    // the same row in a sheet, with a formula that adds up the numbers
+    let mut sheet = Sheet::new(4, 2);
+    for (column, cell) in row.into_iter().enumerate() {
+        sheet.set_cell(CellRef::new(column as u32, 0), cell).unwrap();
+    }
+    let total = CellRef::new(3, 0);
+    sheet.set(total, "=SUM(A1:C1)").unwrap();
+    println!("{} {} = {}", total, sheet.formula(total).unwrap(), sheet.value(total));
+    sheet.set(CellRef::new(0, 0), "4").unwrap();
+    println!("after A1 = 4: {}", sheet.value(total));
+    sheet.set(CellRef::new(0, 1), "=A2+1").unwrap();
+    println!("A2 reads itself: {}", sheet.value(CellRef::new(0, 1)));
 }
 
 #[allow(unused_variables)]
@@ -105,6 +120,10 @@ fn indexing_into_strings() {
     for b in "नमस्ते".bytes() {
         println!("{}", b);
     }
+
+    // graphemes keep the marks with their letters, bad indices are errors
+    println!("{:?}", strings::slice_bytes(hello, 0..3));
+    println!("{:?} {}", strings::slice("नमस्ते", 2..3), strings::reverse("नमस्ते"));
 }
 
 fn hash_maps() {
@@ -120,10 +139,23 @@ fn hash_maps() {
     let teams = vec![String::from("Blue"), String::from("Yellow")];
     let initial_scores = vec![10, 50];
 
+    #[allow(clippy::useless_conversion)] // both sides turned into iterators the same way
     let scores: HashMap<_, _> =
         teams.into_iter().zip(initial_scores.into_iter()).collect();
     dbg!(scores);
 
+    // the same teams on a scoreboard that remembers why a score changed
+    let mut board = Scoreboard::new(SystemClock);
+    for team in ["Blue", "Yellow"] {
+        board.add_team(team).unwrap();
+    }
+    board.score("Blue", 10, "opening goal").unwrap();
+    board.score("Yellow", 50, "opening goal").unwrap();
+    board.set_score("Blue", 25, "overwritten").unwrap();
+    for standing in board.leaderboard() {
+        println!("{}. {} {}", standing.rank, standing.team, standing.score);
+    }
+
     let field_name = String::from("Favorite color");
     let field_value = String::from("Blue");
 
@@ -162,4 +194,12 @@ fn hash_maps() {
         *count += 1; // dereference to update value of the hashmap
     }
     println!("{:?}", map);
+
+    // the same count split by the Unicode rules, see also the word_frequency binary
+    let analysis = Analysis::from_text(text, &Tokenizer::new(), 2);
+    println!("{:?} {:?}", analysis.top(2), analysis.top_ngrams(1));
+
+    // or with a Counter, which does not depend on hash order for its answers
+    let counter: Counter<&str, StableHasher> = text.split_whitespace().collect();
+    println!("{:?}", counter.most_common(2));
 }
\ No newline at end of file
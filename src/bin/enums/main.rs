@@ -1,4 +1,10 @@
 // https://doc.rust-lang.org/book/ch06-00-enums.html
+use rust_book::coin::{make_change, Coin, Inventory, UsState}; // from lib.rs, all coins and states
+use rust_book::config::Loader; // from lib.rs, config_max with real layers
+use rust_book::game::{Board, Dice, Game, Rules}; // from lib.rs, the dice_roll match as a game
+use rust_book::message::{Decoder, Message, Screen}; // from lib.rs, with encodings and a Screen to run on
+use rust_book::network::{Cidr, IpAddr}; // from lib.rs, the variants hold validated addresses
+
 fn main() {
     generic_ip();
     specialized_ip();
@@ -36,39 +42,42 @@ fn generic_ip() {
 
 #[allow(dead_code)]
 fn specialized_ip() {
-    #[derive(Debug)]
-    enum IpAddr {
-        V4(String),
-        V6(String),
+    let home = IpAddr::V4([127, 0, 0, 1]);
+    let loopback: IpAddr = "0:0:0:0:0:0:0:1".parse().unwrap();
+    dbg!(home, home.class());
+    println!("{} is {:?}", loopback, loopback.class());
+    println!("{:?}", "::1::".parse::<IpAddr>());
+
+    let office: Cidr = "192.168.0.0/22".parse().unwrap();
+    for subnet in office.split(24).unwrap() {
+        println!("{} holds {:?} addresses", subnet, subnet.address_count());
     }
-    let home = IpAddr::V4(String::from("127.0.0.1"));
-    let loopback = IpAddr::V6(String::from("::1"));
-    dbg!(home);
-    dbg!(loopback);
+    println!("home in office: {}", office.contains(&home));
 }
 
 #[allow(dead_code)]
 fn variants() {
-    #[derive(Debug)]
-    enum Message {
-        Quit,
-        Move { x: i32, y: i32 },
-        Write(String),
-        ChangeColor(i32, i32, i32),
-    }
-    impl Message {
-        fn call(&self) {
-            dbg!(self);
-        }
-    }
     let q = Message::Quit;
     let mv = Message::Move { x: 1, y: 2 };
     let m = Message::Write(String::from("hello"));
-    let c = Message::ChangeColor(1,2,3);
-    q.call();
-    mv.call();
-    m.call();
-    c.call();
+    let c: Message = "COLOR 1 2 3".parse().unwrap();
+
+    // send them over the wire and read them back, one byte at a time
+    let mut wire = Vec::new();
+    for message in [&mv, &m, &c, &q] {
+        println!("{} -> {:?}", message, message.to_bytes().unwrap());
+        message.encode(&mut wire).unwrap();
+    }
+    let mut decoder = Decoder::new();
+    let mut received = Vec::new();
+    for byte in wire {
+        decoder.feed(&[byte]);
+        received.extend(decoder.by_ref().map(Result::unwrap));
+    }
+
+    let mut screen = Screen::new();
+    screen.run(&received).unwrap();
+    dbg!(screen.text(), screen.cursor(), screen.color());
 }
 
 fn option_enum() {
@@ -84,25 +93,22 @@ fn option_enum() {
 }
 
 fn match_control_flow_operator() {
-    #[derive(Debug)] // so we can inspect the state in a minute
-    enum UsState {
-        Alaska,
-    }
-
-    enum Coin {
-        Penny,
-        Quarter(UsState),
-    }
-    fn value_in_cents(coin: Coin) -> u8 {
+    // Coin and UsState come from lib.rs with every coin and all 50 states, so this
+    // match has to name them all
+    fn value_in_cents(coin: Coin) -> u32 {
         match coin {
             Coin::Penny => {
                 println!("Lucky penny!");
                 1
             }
+            Coin::Nickel => 5,
+            Coin::Dime => 10,
             Coin::Quarter(state) => {
                 println!("State quarter from {:?}!", state);
                 25
             }
+            Coin::HalfDollar => 50,
+            Coin::Dollar => 100,
         }
     }
     let coin = Coin::Penny;
@@ -110,9 +116,26 @@ fn match_control_flow_operator() {
 
     let coin = Coin::Quarter(UsState::Alaska);
     value_in_cents(coin);
+
+    // the fewest coins for 68 cents from what is in the pocket
+    let pocket = [
+        Coin::Quarter(UsState::Alaska),
+        Coin::Quarter(UsState::Hawaii),
+        Coin::Dime,
+        Coin::Nickel,
+        Coin::Nickel,
+        Coin::Penny,
+        Coin::Penny,
+        Coin::Penny,
+    ];
+    match make_change(68, &Inventory::from_coins(&pocket)) {
+        Ok(change) => println!("68 cents: {:?}", change.iter().collect::<Vec<_>>()),
+        Err(e) => println!("68 cents: {}", e),
+    }
 }
 
 fn matching_with_option_generic() {
+    #[allow(clippy::manual_map)] // the match that x.map(|i| i + 1) hides
     fn plus_one(x: Option<i32>) -> Option<i32> {
         match x {
             None => None,
@@ -127,18 +150,16 @@ fn matching_with_option_generic() {
 }
 
 fn catch_all_patterns() {
-    let dice_roll = 9;
-    match dice_roll {
-        3 => add_fancy_hat(),
-        7 => remove_fancy_hat(),
-        other => move_player(other),
-    }
-
-    fn add_fancy_hat() {}
-    fn remove_fancy_hat() {}
-    fn move_player(num_spaces: u8) {
-        println!("move player {} spaces", num_spaces);
+    // the match on the dice roll now lives in rust_book::game, where 3 and 7 are squares
+    // that add and remove a fancy hat and any other square just moves the player
+    let dice = Dice::seeded(1, 9, 9);
+    let mut game = Game::new(&["ferris", "corro"], Board::classic(20), Rules::default(), dice)
+        .unwrap();
+    let outcome = game.play();
+    for event in game.log() {
+        println!("{}", game.describe(event));
     }
+    println!("{:?}", outcome);
 
     // Here, we’re telling Rust explicitly that we aren’t going to use any other value that doesn’t match
     // a pattern in an earlier arm, and we don’t want to run any code in this case.
@@ -153,6 +174,7 @@ fn catch_all_patterns() {
 fn if_let_control_flow() {
     // with match
     let config_max = Some(3u8);
+    #[allow(clippy::single_match)] // the boilerplate if let removes
     match config_max {
         Some(max) => println!("The maximum is configured to be {}", max),
         _ => (),
@@ -165,4 +187,19 @@ fn if_let_control_flow() {
     } else { // optional else
         println!("not executed");
     }
+
+    // the same Option, loaded from the defaults, APP_* variables and --flags
+    let config = Loader::new()
+        .with_optional_file("app.conf")
+        .with_env("APP_", std::env::vars())
+        .with_args(std::env::args().skip(1))
+        .load();
+    match config {
+        Ok(config) => {
+            if let Some(max) = config.max {
+                println!("The maximum is configured to be {}", max);
+            }
+        }
+        Err(e) => println!("bad configuration: {}", e),
+    }
 }
\ No newline at end of file
@@ -1,16 +1,21 @@
 // https://doc.rust-lang.org/book/ch09-00-error-handling.html
 // https://doc.rust-lang.org/book/ch09-03-to-panic-or-not-to-panic.html
-use std::error::Error;
+use rust_book::error::{Context, Error, Result}; // from lib.rs, errors that say which file and what failed
+use rust_book::user::profile::{Locations, ProfileError}; // from lib.rs, a real file for the username
 
-fn main() -> Result<(), Box<dyn Error>> {
-    error_scenarios();
+#[allow(clippy::needless_return)] // the explicit return the comment below explains
+fn main() -> Result<()> {
+    error_scenarios()?;
     propagate_errors();
     propagate_errors_with_shortcut();
+    propagate_errors_with_context()?;
     custom_types_for_validation();
     return Ok(()); // just returning Result enum, () variant which is the unit type or an empty tuple.
 }
 
-fn error_scenarios() {
+// The book panics on every unexpected failure here, these return the error with what was
+// being done instead and main reports it.
+fn error_scenarios() -> Result<()> {
     use std::fs::File;
     use std::io::ErrorKind;
     let f = File::open("hello.txt");
@@ -20,33 +25,30 @@ fn error_scenarios() {
         Err(error) => match error.kind() {
             ErrorKind::NotFound => match File::create("hello.txt") {
                 Ok(fc) => fc,
-                Err(e) => panic!("Problem creating the file: {:?}", e),
+                Err(e) => return Err(Error::from(e).file_context("creating", "hello.txt")),
             },
-            other_error => {
-                panic!("Problem opening the file: {:?}", other_error)
-            }
+            _ => return Err(Error::from(error).file_context("opening", "hello.txt")),
         },
     };
 
-    let f = File::open("hello.txt").unwrap_or_else(|error| {
+    let f = File::open("hello.txt").or_else(|error| {
         if error.kind() == ErrorKind::NotFound {
-            File::create("hello.txt").unwrap_or_else(|error| {
-                panic!("Problem creating the file: {:?}", error);
-            })
+            File::create("hello.txt").file_context("creating", "hello.txt")
         } else {
-            panic!("Problem opening the file: {:?}", error);
+            Err(Error::from(error).file_context("opening", "hello.txt"))
         }
-    });
+    })?;
     dbg!(f);
 
-    let _f = File::open("hello.txt").unwrap(); // match shortcut to get Ok or panic
-    let f = File::open("hello.txt").expect("Failed to open hello.txt"); // like to unwrap but to specify panic message
+    // unwrap and expect would panic here, ? hands the error to the caller
+    let _f = File::open("hello.txt").file_context("opening", "hello.txt")?;
+    // with_context only builds the message when there is an error
+    let f = File::open("hello.txt").with_context(|| format!("opening {} again", "hello.txt"))?;
     dbg!(f);
 
-    std::fs::remove_file("hello.txt").unwrap_or_else(|error| {
-        panic!("Problem removing the file: {:?}", error);
-    });
+    std::fs::remove_file("hello.txt").file_context("removing", "hello.txt")?;
     println!("File cleaned");
+    Ok(())
 }
 
 fn propagate_errors() {
@@ -60,6 +62,8 @@ fn propagate_errors() {
     };
     println!("{}", u);
 
+    // spelled out with match, the next example replaces it with ?
+    #[allow(clippy::question_mark)]
     fn read_username_from_file() -> Result<String, io::Error> {
         let f = File::open("hello.txt");
 
@@ -131,6 +135,49 @@ fn propagate_errors_with_shortcut() {
     println!("{}", u);
 }
 
+// Only a missing file falls back to the default, anything else goes up with the file name
+// and what was being done, parse errors included.
+fn propagate_errors_with_context() -> Result<()> {
+    use std::fs;
+
+    fn read_username(path: &str) -> Result<String> {
+        let name = fs::read_to_string(path).file_context("reading the username from", path)?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Error::invalid("the username is empty").file_context("checking", path));
+        }
+        Ok(name.to_string())
+    }
+
+    let u = match read_username("hello.txt") {
+        Ok(u) => u,
+        Err(error) if error.is_not_found() => "Default username".to_string(),
+        Err(error) => return Err(error),
+    };
+    println!("{}", u);
+
+    // the username from a profile in the config directories instead of hello.txt
+    let locations = Locations::from_env(std::env::vars());
+    let u = match locations.load() {
+        Ok((_, profile)) => profile.username().to_string(),
+        Err(ProfileError::NotFound { .. }) => "Default username".to_string(),
+        Err(error) => return Err(error.into()),
+    };
+    println!("{}", u);
+
+    fn read_age(path: &str) -> Result<u8> {
+        let text = fs::read_to_string(path).file_context("reading", path)?;
+        text.trim().parse::<u8>().file_context("parsing the age in", path)
+    }
+
+    fs::write("age.txt", "two hundred").file_context("writing", "age.txt")?;
+    if let Err(error) = read_age("age.txt") {
+        println!("{:#}", error); // parsing the age in age.txt: not a whole number: invalid digit found in string
+    }
+    fs::remove_file("age.txt").file_context("removing", "age.txt")?;
+    Ok(())
+}
+
 fn custom_types_for_validation() {
     #[allow(dead_code)]
     pub struct Guess {
@@ -139,6 +186,7 @@ fn custom_types_for_validation() {
 
     #[allow(dead_code)]
     impl Guess {
+        #[allow(clippy::manual_range_contains)] // the two bounds the message names
         pub fn new(value: i32) -> Guess { // constructor/factory method
             if value < 1 || value > 100 {
                 panic!("Guess value must be between 1 and 100, got {}.", value);
@@ -1,12 +1,9 @@
 // https://doc.rust-lang.org/book/ch05-00-structs.html
-#[derive(Debug)]
-#[allow(dead_code)]
-struct User {
-    active: bool,
-    username: String,
-    email: String,
-    sign_in_count: u64,
-}
+use rust_book::color;
+use rust_book::geometry::space::Point3;
+use rust_book::geometry::Rectangle;
+use rust_book::user::patch::UserPatch;
+use rust_book::user::{Email, User, Username};
 
 fn main() {
     basic();
@@ -21,8 +18,8 @@ fn main() {
 
 fn basic() {
     let user1 = User {
-        email: String::from("someone@example.com"),
-        username: String::from("someusername123"),
+        email: Email::parse("someone@example.com").expect("valid email"),
+        username: Username::parse("someusername123").expect("valid username"),
         active: true,
         sign_in_count: 1,
     };
@@ -30,25 +27,29 @@ fn basic() {
     println!("user: {:?}", user1);
 
     let mut user1 = User {
-        email: String::from("someone@example.com"),
-        username: String::from("someusername123"),
+        email: Email::parse("someone@example.com").expect("valid email"),
+        username: Username::parse("someusername123").expect("valid username"),
         active: true,
         sign_in_count: 1,
     };
 
-    user1.email = String::from("anotheremail@example.com");
+    user1.email = Email::parse("anotheremail@example.com").expect("valid email");
     println!("user: {:?}", user1);
+
+    // invalid values never make it into a User
+    match Email::parse("not an email") {
+        Ok(email) => println!("email: {}", email),
+        Err(e) => println!("rejected: {}", e),
+    }
 }
 
 fn builder() {
-    let user = build_user(String::from("name@example.com"), String::from("name"));
-
-    fn build_user(email: String, username: String) -> User {
-        User {
-            email,
-            username,
-            active: true,
-            sign_in_count: 1,
+    let user = build_user("name@example.com", "name");
+
+    fn build_user(email: &str, username: &str) -> User {
+        match User::parse(email, username) {
+            Ok(user) => user,
+            Err(e) => panic!("Problem building the user: {}", e),
         }
     }
     println!("user: {:?}", user);
@@ -57,17 +58,25 @@ fn builder() {
 fn struct_update() {
     let user = User {
         active: true,
-        username: String::from("the user name"),
-        email: String::from("another@example.com"),
+        username: Username::parse("the_user_name").expect("valid username"),
+        email: Email::parse("another@example.com").expect("valid email"),
         sign_in_count: 2,
     };
     println!("user: {:?}", user);
 
     let user2 = User {
-        email: String::from("updated@email.com"),
+        email: Email::parse("updated@email.com").expect("valid email"),
         ..user  // from this point we cannot user user anymore as it moved the data to user2
     };
     println!("user: {:?}", user2);
+
+    // the same idea as a patch that also tells what changed
+    let patch = UserPatch::new().active(false);
+    let (user3, changes) = patch.apply(user2);
+    println!("user: {:?}", user3);
+    for change in changes {
+        println!("changed {}", change);
+    }
 }
 
 fn tuple_struct() {
@@ -80,6 +89,32 @@ fn tuple_struct() {
     let black = Color(0, 0, 0);
     let origin = Point(0, 0, 0);
     println!("Color: {:?}, Point: {:?}", black, origin);
+
+    // the fields are accessed by index, here to build a checked color from lib.rs
+    match color::Color::try_from((black.0, black.1, black.2)) {
+        Ok(checked) => println!(
+            "{} has contrast {:.1} on white",
+            checked,
+            checked.contrast_ratio(&color::Color::WHITE)
+        ),
+        Err(e) => println!("not a color: {}", e),
+    }
+    let too_bright = Color(300, 0, 0);
+    if let Err(e) = color::Color::try_from((too_bright.0, too_bright.1, too_bright.2)) {
+        println!("not a color: {}", e);
+    }
+
+    // and a generic 3D point with vector math
+    let origin = Point3::from((origin.0, origin.1, origin.2));
+    let corner = Point3::new(3, 4, 12);
+    let v = corner - origin; // point - point is a vector
+    println!(
+        "distance {} from {:?} to {:?}, moved twice: {:?}",
+        origin.distance(&corner),
+        origin,
+        corner,
+        origin + v * 2
+    );
 }
 
 fn unit_like_struct() {
@@ -114,14 +149,10 @@ fn calculate_area() {
         dimensions.0 * dimensions.1
     }
 
-    // using structs
-    #[derive(Debug)]
-    struct Rectangle {
-        width: u32,
-        height: u32,
-    }
-
+    // using structs (the Rectangle from lib.rs, which also has a position)
     let rect1 = Rectangle {
+        x: 0,
+        y: 0,
         width: 30,
         height: 50,
     };
@@ -139,25 +170,8 @@ fn calculate_area() {
 }
 
 fn struct_methods() {
-    #[derive(Debug)]
-    struct Rectangle {
-        width: u32,
-        height: u32,
-    }
-
-    impl Rectangle {
-        fn area(&self) -> u32 {
-            self.width * self.height
-        }
-        fn width(&self) -> u32 {
-            self.width
-        }
-    }
-
-    let rect1 = Rectangle {
-        width: 30,
-        height: 50,
-    };
+    // area() and width() are methods defined in the impl block of lib.rs
+    let rect1 = Rectangle::with_size(30, 50);
 
     println!(
         "The area of the rectangle of width {} is {} square pixels.",
@@ -166,42 +180,20 @@ fn struct_methods() {
 }
 
 fn methods_with_params() {
-    #[derive(Debug)]
-    struct Rectangle {
-        width: u32,
-        height: u32,
-    }
-    impl Rectangle {
-        fn can_hold(&self, other: &Rectangle) -> bool {
-            self.width > other.width && self.height > other.height
-        }
-    }
-    impl Rectangle { // you can use multiple impl blocks for the same struct
-        fn build_square(size: u32) -> Rectangle { // factory method
-            Rectangle {
-                width: size,
-                height: size,
-            }
-        }
-    }
-
-    let rect1 = Rectangle {
-        width: 30,
-        height: 50,
-    };
-    let rect2 = Rectangle {
-        width: 10,
-        height: 40,
-    };
-    let rect3 = Rectangle {
-        width: 60,
-        height: 45,
-    };
+    let rect1 = Rectangle::with_size(30, 50);
+    let rect2 = Rectangle::with_size(10, 40);
+    let rect3 = Rectangle::with_size(60, 45);
+    let rect4 = Rectangle::with_size(45, 25); // only fits when rotated
 
     println!("Can rect1 hold rect2? {}", rect1.can_hold(&rect2));
     println!("Can rect1 hold rect3? {}", rect1.can_hold(&rect3));
+    println!("Can rect1 hold rect4? {}", rect1.can_hold(&rect4));
 
-    let square = Rectangle::build_square(10);
+    let square = Rectangle::build_square(10); // factory method, no self
     dbg!(square);
-}
 
+    // with positions they can also be combined
+    let moved = square.translate(25, 25);
+    println!("Overlap of rect1 and moved square: {:?}", rect1.intersection(&moved));
+    println!("Both fit in: {:?}", rect1.union(&moved));
+}
@@ -0,0 +1,139 @@
+// https://doc.rust-lang.org/book/ch08-03-hash-maps.html
+// https://doc.rust-lang.org/book/ch12-01-accepting-command-line-arguments.html
+// The hash maps word counter as a command line tool. Reads the files given, or stdin when
+// there are none or for "-", and prints word counts, n-grams and readability for each, and
+// the words that set each file apart when there are several.
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::process;
+
+use rust_book::text::{Analysis, Corpus, Tokenizer, ENGLISH_STOP_WORDS};
+
+const USAGE: &str = "usage: word_frequency [--top N] [--ngrams N] [--stop-words english|FILE] [--keep-case] [FILE...]";
+
+struct Args {
+    top: usize,
+    ngrams: usize,
+    stop_words: Option<String>,
+    keep_case: bool,
+    files: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        top: 10,
+        ngrams: 2,
+        stop_words: None,
+        keep_case: false,
+        files: Vec::new(),
+    };
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{} needs a value", flag));
+        match arg.as_str() {
+            "--top" | "--ngrams" => {
+                let n = value(&arg)?;
+                let n = n
+                    .parse()
+                    .map_err(|_| format!("{} is not a number: {:?}", arg, n))?;
+                if arg == "--top" {
+                    parsed.top = n;
+                } else {
+                    parsed.ngrams = n;
+                }
+            }
+            "--stop-words" => parsed.stop_words = Some(value(&arg)?),
+            "--keep-case" => parsed.keep_case = true,
+            "-h" | "--help" => return Err(String::new()),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            _ => parsed.files.push(arg),
+        }
+    }
+    if parsed.files.is_empty() {
+        parsed.files.push(String::from("-"));
+    }
+    Ok(parsed)
+}
+
+fn tokenizer(args: &Args) -> io::Result<Tokenizer> {
+    let tokenizer = Tokenizer::new().fold_case(!args.keep_case);
+    match args.stop_words.as_deref() {
+        None => Ok(tokenizer),
+        Some("english") => Ok(tokenizer.stop_words(ENGLISH_STOP_WORDS)),
+        Some(path) => tokenizer.stop_words_from(BufReader::new(File::open(path)?)),
+    }
+}
+
+fn analyze(path: &str, tokenizer: &Tokenizer, ngrams: usize) -> io::Result<Analysis> {
+    if path == "-" {
+        Analysis::from_reader(io::stdin().lock(), tokenizer, ngrams)
+    } else {
+        Analysis::from_reader(BufReader::new(File::open(path)?), tokenizer, ngrams)
+    }
+}
+
+fn report(name: &str, analysis: &Analysis, args: &Args) {
+    let readability = analysis.readability();
+    println!("== {}", name);
+    println!(
+        "{} words, {} counted, {} distinct, {} sentences",
+        readability.words(),
+        analysis.total_words(),
+        analysis.distinct_words(),
+        readability.sentences()
+    );
+    if let (Some(ease), Some(grade)) = (readability.reading_ease(), readability.grade_level()) {
+        println!("reading ease {:.1}, grade level {:.1}", ease, grade);
+    }
+    for (word, count) in analysis.top(args.top) {
+        println!("{:>8} {}", count, word);
+    }
+    let ngrams = analysis.top_ngrams(args.top);
+    if !ngrams.is_empty() {
+        println!("-- {}-grams", args.ngrams);
+        for (ngram, count) in ngrams {
+            println!("{:>8} {}", count, ngram);
+        }
+    }
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("{}", message);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let tokenizer = tokenizer(&args).unwrap_or_else(|e| {
+        eprintln!("stop words: {}", e);
+        process::exit(1);
+    });
+
+    let mut corpus = Corpus::new();
+    for path in &args.files {
+        match analyze(path, &tokenizer, args.ngrams) {
+            Ok(analysis) => {
+                report(path, &analysis, &args);
+                corpus.add(path, analysis);
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if corpus.len() > 1 {
+        for (index, (name, _)) in corpus.documents().enumerate() {
+            let words: Vec<String> = corpus
+                .tf_idf(index, args.top)
+                .into_iter()
+                .map(|(word, score)| format!("{} ({:.3})", word, score))
+                .collect();
+            println!("== tf-idf {}: {}", name, words.join(", "));
+        }
+    }
+}
@@ -1,20 +1,6 @@
 // https://doc.rust-lang.org/book/ch11-00-testing.html
 fn main() {}
 
-#[derive(Debug)]
-#[allow(dead_code)]
-struct Rectangle {
-    width: u32,
-    height: u32,
-}
-
-#[allow(dead_code)]
-impl Rectangle {
-    fn can_hold(&self, other: &Rectangle) -> bool {
-        self.width > other.width && self.height > other.height
-    }
-}
-
 pub fn add_two(a: i32) -> i32 {
     a + 2
 }
@@ -35,16 +21,19 @@ mod tests {
     }
 
     use super::*;
+    use rust_book::geometry::Rectangle;
 
     #[test]
     fn larger_can_hold_smaller() {
         let larger = Rectangle {
             width: 8,
             height: 7,
+            ..Default::default()
         };
         let smaller = Rectangle {
             width: 5,
             height: 1,
+            ..Default::default()
         };
 
         assert!(larger.can_hold(&smaller));
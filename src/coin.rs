@@ -0,0 +1,572 @@
+// https://doc.rust-lang.org/book/ch06-02-match.html
+// The Coin and UsState enums from the match examples with every circulating US coin and
+// every state quarter, plus change-making for any coin system.
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+pub mod catalog;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Coin {
+    Penny,
+    Nickel,
+    Dime,
+    Quarter(UsState),
+    HalfDollar,
+    Dollar,
+}
+
+impl Coin {
+    // one coin of each value, the quarter stands for all of them
+    pub const DENOMINATIONS: [u32; 6] = [1, 5, 10, 25, 50, 100];
+
+    pub fn value_in_cents(&self) -> u32 {
+        match self {
+            Coin::Penny => 1,
+            Coin::Nickel => 5,
+            Coin::Dime => 10,
+            Coin::Quarter(_) => 25,
+            Coin::HalfDollar => 50,
+            Coin::Dollar => 100,
+        }
+    }
+}
+
+impl Display for Coin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Coin::Penny => write!(f, "penny"),
+            Coin::Nickel => write!(f, "nickel"),
+            Coin::Dime => write!(f, "dime"),
+            Coin::Quarter(state) => write!(f, "{} quarter", state),
+            Coin::HalfDollar => write!(f, "half dollar"),
+            Coin::Dollar => write!(f, "dollar coin"),
+        }
+    }
+}
+
+// In the order the 50 State Quarters were released, which is the order of statehood.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum UsState {
+    Delaware,
+    Pennsylvania,
+    NewJersey,
+    Georgia,
+    Connecticut,
+    Massachusetts,
+    Maryland,
+    SouthCarolina,
+    NewHampshire,
+    Virginia,
+    NewYork,
+    NorthCarolina,
+    RhodeIsland,
+    Vermont,
+    Kentucky,
+    Tennessee,
+    Ohio,
+    Louisiana,
+    Indiana,
+    Mississippi,
+    Illinois,
+    Alabama,
+    Maine,
+    Missouri,
+    Arkansas,
+    Michigan,
+    Florida,
+    Texas,
+    Iowa,
+    Wisconsin,
+    California,
+    Minnesota,
+    Oregon,
+    Kansas,
+    WestVirginia,
+    Nevada,
+    Nebraska,
+    Colorado,
+    NorthDakota,
+    SouthDakota,
+    Montana,
+    Washington,
+    Idaho,
+    Wyoming,
+    Utah,
+    Oklahoma,
+    NewMexico,
+    Arizona,
+    Alaska,
+    Hawaii,
+}
+
+impl UsState {
+    pub const ALL: [UsState; 50] = [
+        UsState::Delaware,
+        UsState::Pennsylvania,
+        UsState::NewJersey,
+        UsState::Georgia,
+        UsState::Connecticut,
+        UsState::Massachusetts,
+        UsState::Maryland,
+        UsState::SouthCarolina,
+        UsState::NewHampshire,
+        UsState::Virginia,
+        UsState::NewYork,
+        UsState::NorthCarolina,
+        UsState::RhodeIsland,
+        UsState::Vermont,
+        UsState::Kentucky,
+        UsState::Tennessee,
+        UsState::Ohio,
+        UsState::Louisiana,
+        UsState::Indiana,
+        UsState::Mississippi,
+        UsState::Illinois,
+        UsState::Alabama,
+        UsState::Maine,
+        UsState::Missouri,
+        UsState::Arkansas,
+        UsState::Michigan,
+        UsState::Florida,
+        UsState::Texas,
+        UsState::Iowa,
+        UsState::Wisconsin,
+        UsState::California,
+        UsState::Minnesota,
+        UsState::Oregon,
+        UsState::Kansas,
+        UsState::WestVirginia,
+        UsState::Nevada,
+        UsState::Nebraska,
+        UsState::Colorado,
+        UsState::NorthDakota,
+        UsState::SouthDakota,
+        UsState::Montana,
+        UsState::Washington,
+        UsState::Idaho,
+        UsState::Wyoming,
+        UsState::Utah,
+        UsState::Oklahoma,
+        UsState::NewMexico,
+        UsState::Arizona,
+        UsState::Alaska,
+        UsState::Hawaii,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UsState::Delaware => "Delaware",
+            UsState::Pennsylvania => "Pennsylvania",
+            UsState::NewJersey => "New Jersey",
+            UsState::Georgia => "Georgia",
+            UsState::Connecticut => "Connecticut",
+            UsState::Massachusetts => "Massachusetts",
+            UsState::Maryland => "Maryland",
+            UsState::SouthCarolina => "South Carolina",
+            UsState::NewHampshire => "New Hampshire",
+            UsState::Virginia => "Virginia",
+            UsState::NewYork => "New York",
+            UsState::NorthCarolina => "North Carolina",
+            UsState::RhodeIsland => "Rhode Island",
+            UsState::Vermont => "Vermont",
+            UsState::Kentucky => "Kentucky",
+            UsState::Tennessee => "Tennessee",
+            UsState::Ohio => "Ohio",
+            UsState::Louisiana => "Louisiana",
+            UsState::Indiana => "Indiana",
+            UsState::Mississippi => "Mississippi",
+            UsState::Illinois => "Illinois",
+            UsState::Alabama => "Alabama",
+            UsState::Maine => "Maine",
+            UsState::Missouri => "Missouri",
+            UsState::Arkansas => "Arkansas",
+            UsState::Michigan => "Michigan",
+            UsState::Florida => "Florida",
+            UsState::Texas => "Texas",
+            UsState::Iowa => "Iowa",
+            UsState::Wisconsin => "Wisconsin",
+            UsState::California => "California",
+            UsState::Minnesota => "Minnesota",
+            UsState::Oregon => "Oregon",
+            UsState::Kansas => "Kansas",
+            UsState::WestVirginia => "West Virginia",
+            UsState::Nevada => "Nevada",
+            UsState::Nebraska => "Nebraska",
+            UsState::Colorado => "Colorado",
+            UsState::NorthDakota => "North Dakota",
+            UsState::SouthDakota => "South Dakota",
+            UsState::Montana => "Montana",
+            UsState::Washington => "Washington",
+            UsState::Idaho => "Idaho",
+            UsState::Wyoming => "Wyoming",
+            UsState::Utah => "Utah",
+            UsState::Oklahoma => "Oklahoma",
+            UsState::NewMexico => "New Mexico",
+            UsState::Arizona => "Arizona",
+            UsState::Alaska => "Alaska",
+            UsState::Hawaii => "Hawaii",
+        }
+    }
+
+    // Five quarters a year, from 1999 to 2008.
+    pub fn quarter_year(&self) -> u16 {
+        1999 + *self as u16 / 5
+    }
+
+    // the name ignoring case and spaces, so "new york" and "NewYork" both work
+    pub fn from_name(name: &str) -> Option<UsState> {
+        let wanted: String = name.split_whitespace().collect();
+        UsState::ALL.into_iter().find(|state| {
+            let candidate: String = state.name().split_whitespace().collect();
+            candidate.eq_ignore_ascii_case(&wanted)
+        })
+    }
+}
+
+impl Display for UsState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeError {
+    ZeroDenomination,
+    // the coins at hand cannot add up to the amount
+    Impossible { amount: u32 },
+    // more than make_change will work out with these coins, see MAX_CHANGE and MAX_TABLE
+    TooLarge { amount: u32, max: u32 },
+}
+
+impl Display for ChangeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeError::ZeroDenomination => write!(f, "a coin cannot be worth nothing"),
+            ChangeError::Impossible { amount } => {
+                write!(f, "no combination of the coins makes {}", amount)
+            }
+            ChangeError::TooLarge { amount, max } => {
+                write!(f, "cannot make change for {}, at most {}", amount, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChangeError {}
+
+// How many coins of each value there are. Values are in the smallest unit of the currency,
+// cents for US coins.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Inventory {
+    counts: BTreeMap<u32, u32>,
+}
+
+impl Inventory {
+    pub fn new() -> Inventory {
+        Inventory::default()
+    }
+
+    // as many coins of each value as any amount needs
+    pub fn unlimited(denominations: &[u32]) -> Inventory {
+        let mut inventory = Inventory::new();
+        for &value in denominations {
+            inventory.counts.insert(value, u32::MAX);
+        }
+        inventory
+    }
+
+    pub fn from_coins(coins: &[Coin]) -> Inventory {
+        let mut inventory = Inventory::new();
+        for coin in coins {
+            inventory.add(coin.value_in_cents(), 1);
+        }
+        inventory
+    }
+
+    pub fn add(&mut self, value: u32, count: u32) {
+        let entry = self.counts.entry(value).or_insert(0);
+        *entry = entry.saturating_add(count);
+    }
+
+    pub fn count(&self, value: u32) -> u32 {
+        self.counts.get(&value).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts
+            .iter()
+            .map(|(&value, &count)| value as u64 * count as u64)
+            .sum()
+    }
+
+    // Takes the coins of a change out, for a till that pays it out. Returns false and
+    // changes nothing if some coins are missing.
+    pub fn remove(&mut self, change: &Change) -> bool {
+        if change
+            .iter()
+            .any(|(value, count)| self.count(value) < count)
+        {
+            return false;
+        }
+        for (value, count) in change.iter() {
+            if let Some(left) = self.counts.get_mut(&value) {
+                *left -= count;
+            }
+        }
+        self.counts.retain(|_, count| *count > 0);
+        true
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Change {
+    counts: BTreeMap<u32, u32>,
+}
+
+impl Change {
+    pub fn coin_count(&self) -> u32 {
+        self.counts.values().sum()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.iter()
+            .map(|(value, count)| value as u64 * count as u64)
+            .sum()
+    }
+
+    // (value, count) pairs, smallest value first
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.counts.iter().map(|(&value, &count)| (value, count))
+    }
+}
+
+// $1000 in cents
+pub const MAX_CHANGE: u32 = 100_000;
+// cells of the table make_change keeps, one byte each
+pub const MAX_TABLE: usize = 1 << 25;
+
+// The fewest coins from inventory adding up to amount exactly.
+//
+// Greedy (largest coin first) is only right for canonical systems like today's US coins,
+// so this is a bounded knapsack instead: every value with n coins is split into lots of
+// 1, 2, 4, ... coins and each lot is used at most once. It takes O(amount * lots) time and
+// memory, which is fine for till sized amounts, so anything over MAX_CHANGE is refused, and
+// so is a table of more than MAX_TABLE cells for an inventory with very many values.
+pub fn make_change(amount: u32, inventory: &Inventory) -> Result<Change, ChangeError> {
+    if inventory.counts.contains_key(&0) {
+        return Err(ChangeError::ZeroDenomination);
+    }
+    if amount > MAX_CHANGE {
+        return Err(ChangeError::TooLarge {
+            amount,
+            max: MAX_CHANGE,
+        });
+    }
+
+    let mut lots: Vec<(u32, u32)> = Vec::new();
+    for (&value, &count) in &inventory.counts {
+        let mut left = count.min(amount / value);
+        let mut size = 1;
+        while left > 0 {
+            let take = size.min(left);
+            lots.push((value, take));
+            left -= take;
+            size *= 2;
+        }
+    }
+
+    let width = amount as usize + 1;
+    let cells = lots
+        .len()
+        .checked_mul(width)
+        .filter(|&cells| cells <= MAX_TABLE);
+    let Some(cells) = cells else {
+        // the largest amount whose table would fit with these lots
+        let max = (MAX_TABLE / lots.len()).saturating_sub(1);
+        return Err(ChangeError::TooLarge {
+            amount,
+            max: u32::try_from(max).unwrap_or(u32::MAX),
+        });
+    };
+    let mut fewest = vec![u32::MAX; width];
+    fewest[0] = 0;
+    let mut used = vec![false; cells];
+    for (i, &(value, count)) in lots.iter().enumerate() {
+        let lot_value = (value * count) as usize;
+        for total in (lot_value..width).rev() {
+            let Some(coins) = fewest[total - lot_value].checked_add(count) else {
+                continue;
+            };
+            if coins < fewest[total] {
+                fewest[total] = coins;
+                used[i * width + total] = true;
+            }
+        }
+    }
+    if fewest[amount as usize] == u32::MAX {
+        return Err(ChangeError::Impossible { amount });
+    }
+
+    let mut change = Change::default();
+    let mut total = amount as usize;
+    for (i, &(value, count)) in lots.iter().enumerate().rev() {
+        if used[i * width + total] {
+            *change.counts.entry(value).or_insert(0) += count;
+            total -= (value * count) as usize;
+        }
+    }
+    Ok(change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(change: &Change) -> Vec<(u32, u32)> {
+        change.iter().collect()
+    }
+
+    #[test]
+    fn every_state_has_a_quarter() {
+        assert_eq!(UsState::ALL.len(), 50);
+        for (i, state) in UsState::ALL.iter().enumerate() {
+            assert_eq!(*state as usize, i);
+            assert_eq!(Coin::Quarter(*state).value_in_cents(), 25);
+            assert_eq!(UsState::from_name(state.name()), Some(*state));
+        }
+        assert_eq!(UsState::Delaware.quarter_year(), 1999);
+        assert_eq!(UsState::Hawaii.quarter_year(), 2008);
+        assert_eq!(
+            UsState::from_name("north   DAKOTA"),
+            Some(UsState::NorthDakota)
+        );
+        assert_eq!(UsState::from_name("Puerto Rico"), None);
+        assert_eq!(
+            Coin::Quarter(UsState::NewMexico).to_string(),
+            "New Mexico quarter"
+        );
+    }
+
+    #[test]
+    fn unlimited_us_coins() {
+        let us = Inventory::unlimited(&Coin::DENOMINATIONS);
+        let change = make_change(289, &us).unwrap();
+        assert_eq!(
+            counts(&change),
+            vec![(1, 4), (10, 1), (25, 1), (50, 1), (100, 2)]
+        );
+        assert_eq!(change.coin_count(), 9);
+        assert_eq!(change.total(), 289);
+        assert_eq!(make_change(0, &us).unwrap().coin_count(), 0);
+
+        assert_eq!(make_change(MAX_CHANGE, &us).unwrap().coin_count(), 1000);
+        assert_eq!(
+            make_change(u32::MAX, &us),
+            Err(ChangeError::TooLarge {
+                amount: u32::MAX,
+                max: MAX_CHANGE
+            })
+        );
+
+        // one coin each of 400 values makes a table too big for the full MAX_CHANGE
+        let mut jar = Inventory::new();
+        for value in 1..=400 {
+            jar.add(value, 1);
+        }
+        assert_eq!(
+            make_change(MAX_CHANGE, &jar),
+            Err(ChangeError::TooLarge {
+                amount: MAX_CHANGE,
+                max: 83_885
+            })
+        );
+        assert_eq!(make_change(80_200, &jar).unwrap().total(), 80_200);
+    }
+
+    #[test]
+    fn limited_inventory() {
+        let mut till = Inventory::from_coins(&[
+            Coin::Quarter(UsState::Ohio),
+            Coin::Dime,
+            Coin::Dime,
+            Coin::Dime,
+        ]);
+        till.add(1, 5);
+        // no nickels: three dimes beat a quarter and five pennies
+        let change = make_change(30, &till).unwrap();
+        assert_eq!(counts(&change), vec![(10, 3)]);
+
+        assert!(till.remove(&change));
+        assert_eq!(till.count(10), 0);
+        assert_eq!(till.total(), 30);
+        assert!(!till.remove(&change));
+        assert_eq!(
+            make_change(31, &till),
+            Err(ChangeError::Impossible { amount: 31 })
+        );
+    }
+
+    #[test]
+    fn non_canonical_systems() {
+        // greedy gives 4 + 1 + 1
+        let change = make_change(6, &Inventory::unlimited(&[1, 3, 4])).unwrap();
+        assert_eq!(counts(&change), vec![(3, 2)]);
+
+        // pre-decimal British coins in pence, greedy gives 30 + 12 + 6
+        let old_pence = Inventory::unlimited(&[1, 3, 6, 12, 24, 30, 60, 240]);
+        let change = make_change(48, &old_pence).unwrap();
+        assert_eq!(counts(&change), vec![(24, 2)]);
+
+        // only a 7 and a 5 make 12 once the 3 is used up
+        let mut purse = Inventory::new();
+        purse.add(3, 1);
+        purse.add(5, 2);
+        purse.add(7, 1);
+        assert_eq!(
+            counts(&make_change(12, &purse).unwrap()),
+            vec![(5, 1), (7, 1)]
+        );
+        assert_eq!(
+            make_change(2, &purse),
+            Err(ChangeError::Impossible { amount: 2 })
+        );
+
+        assert_eq!(
+            make_change(5, &Inventory::unlimited(&[0, 1])),
+            Err(ChangeError::ZeroDenomination)
+        );
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut purse = Inventory::new();
+        purse.add(1, 3);
+        purse.add(4, 2);
+        purse.add(9, 3);
+        purse.add(11, 1);
+        for amount in 0..=50 {
+            let mut best = None;
+            for a in 0..=3 {
+                for b in 0..=2 {
+                    for c in 0..=3 {
+                        for d in 0..=1 {
+                            if a + 4 * b + 9 * c + 11 * d == amount {
+                                let coins = a + b + c + d;
+                                best = Some(best.map_or(coins, |best: u32| best.min(coins)));
+                            }
+                        }
+                    }
+                }
+            }
+            let change = make_change(amount, &purse).ok();
+            assert_eq!(change.as_ref().map(Change::coin_count), best);
+            if let Some(change) = change {
+                assert_eq!(change.total(), amount as u64);
+                assert!(change
+                    .iter()
+                    .all(|(value, count)| count <= purse.count(value)));
+            }
+        }
+    }
+}
@@ -0,0 +1,663 @@
+// A collector's catalog: how many of each coin, by mint year and condition, which state
+// quarters are still missing and what it is all worth. Collections move in and out as CSV.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Coin, UsState};
+
+// The Sheldon scale grades, worst to best, without the numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Grade {
+    Poor,
+    Fair,
+    Good,
+    VeryGood,
+    Fine,
+    VeryFine,
+    ExtremelyFine,
+    AboutUncirculated,
+    Uncirculated,
+}
+
+impl Grade {
+    pub const ALL: [Grade; 9] = [
+        Grade::Poor,
+        Grade::Fair,
+        Grade::Good,
+        Grade::VeryGood,
+        Grade::Fine,
+        Grade::VeryFine,
+        Grade::ExtremelyFine,
+        Grade::AboutUncirculated,
+        Grade::Uncirculated,
+    ];
+
+    // the usual short form, MS for mint state
+    pub fn code(&self) -> &'static str {
+        match self {
+            Grade::Poor => "P",
+            Grade::Fair => "FR",
+            Grade::Good => "G",
+            Grade::VeryGood => "VG",
+            Grade::Fine => "F",
+            Grade::VeryFine => "VF",
+            Grade::ExtremelyFine => "EF",
+            Grade::AboutUncirculated => "AU",
+            Grade::Uncirculated => "MS",
+        }
+    }
+}
+
+impl Display for Grade {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+// Takes the code in any case, and XF as another name for EF.
+impl FromStr for Grade {
+    type Err = CatalogError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim().to_ascii_uppercase();
+        let code = if code == "XF" { "EF" } else { code.as_str() };
+        Grade::ALL
+            .into_iter()
+            .find(|grade| grade.code() == code)
+            .ok_or_else(|| CatalogError::UnknownGrade(s.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub enum CatalogError {
+    InvalidYear {
+        coin: Coin,
+        year: u16,
+    },
+    UnknownCoin(String),
+    UnknownState(String),
+    UnknownGrade(String),
+    NotEnough {
+        coin: Coin,
+        year: u16,
+        grade: Grade,
+        have: u32,
+    },
+    // more of one entry than a u32 can count
+    TooMany {
+        entry: Entry,
+        have: u32,
+        adding: u32,
+    },
+    // an import stops at the first bad row and adds nothing
+    Row {
+        row: usize,
+        error: Box<CatalogError>,
+    },
+    Io(io::Error),
+    Csv(csv::Error),
+}
+
+impl Display for CatalogError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CatalogError::InvalidYear { coin, year } => {
+                write!(f, "no {} was minted in {}", coin, year)
+            }
+            CatalogError::UnknownCoin(s) => write!(f, "unknown coin {:?}", s),
+            CatalogError::UnknownState(s) => write!(f, "unknown state {:?}", s),
+            CatalogError::UnknownGrade(s) => write!(f, "unknown grade {:?}", s),
+            CatalogError::NotEnough {
+                coin,
+                year,
+                grade,
+                have,
+            } => write!(
+                f,
+                "only {} {} {} {} in the catalog",
+                have, year, grade, coin
+            ),
+            CatalogError::TooMany {
+                entry,
+                have,
+                adding,
+            } => write!(
+                f,
+                "cannot add {} to the {} {} {} {} already in the catalog",
+                adding, have, entry.year, entry.grade, entry.coin
+            ),
+            CatalogError::Row { row, error } => write!(f, "row {}: {}", row, error),
+            CatalogError::Io(e) => write!(f, "{}", e),
+            CatalogError::Csv(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CatalogError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CatalogError::Row { error, .. } => Some(error.as_ref()),
+            CatalogError::Io(e) => Some(e),
+            CatalogError::Csv(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<csv::Error> for CatalogError {
+    fn from(e: csv::Error) -> Self {
+        if !e.is_io_error() {
+            return CatalogError::Csv(e);
+        }
+        match e.into_kind() {
+            csv::ErrorKind::Io(e) => CatalogError::Io(e),
+            kind => CatalogError::Io(io::Error::other(format!("{:?}", kind))),
+        }
+    }
+}
+
+impl From<io::Error> for CatalogError {
+    fn from(e: io::Error) -> Self {
+        CatalogError::Io(e)
+    }
+}
+
+// Coins go back to the first year they were struck. A state quarter only exists for its
+// own year.
+fn check_year(coin: Coin, year: u16) -> Result<(), CatalogError> {
+    let valid = match coin {
+        Coin::Penny => year >= 1793,
+        Coin::Nickel => year >= 1866,
+        Coin::Dime => year >= 1796,
+        Coin::Quarter(state) => year == state.quarter_year(),
+        Coin::HalfDollar | Coin::Dollar => year >= 1794,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(CatalogError::InvalidYear { coin, year })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entry {
+    pub coin: Coin,
+    pub year: u16,
+    pub grade: Grade,
+}
+
+// What a collector would pay for one coin, by coin and grade, in cents.
+// Coins without a price count at face value.
+#[derive(Debug, Clone, Default)]
+pub struct PriceGuide {
+    prices: BTreeMap<(Coin, Grade), u32>,
+}
+
+impl PriceGuide {
+    pub fn new() -> PriceGuide {
+        PriceGuide::default()
+    }
+
+    pub fn set(&mut self, coin: Coin, grade: Grade, cents: u32) {
+        self.prices.insert((coin, grade), cents);
+    }
+
+    pub fn price(&self, coin: Coin, grade: Grade) -> u32 {
+        self.prices
+            .get(&(coin, grade))
+            .copied()
+            .unwrap_or(coin.value_in_cents())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValuationLine {
+    pub entry: Entry,
+    pub count: u32,
+    pub unit_value: u32,
+}
+
+impl ValuationLine {
+    pub fn value(&self) -> u64 {
+        self.unit_value as u64 * self.count as u64
+    }
+}
+
+// Most valuable lines first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Valuation {
+    pub lines: Vec<ValuationLine>,
+}
+
+impl Valuation {
+    pub fn total(&self) -> u64 {
+        self.lines.iter().map(ValuationLine::value).sum()
+    }
+
+    pub fn face_value(&self) -> u64 {
+        self.lines
+            .iter()
+            .map(|line| line.entry.coin.value_in_cents() as u64 * line.count as u64)
+            .sum()
+    }
+}
+
+impl Display for Valuation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let dollars = |cents: u64| format!("${}.{:02}", cents / 100, cents % 100);
+        for line in &self.lines {
+            writeln!(
+                f,
+                "{:>4} x {} {:<28} {:>2} {:>10} {:>10}",
+                line.count,
+                line.entry.year,
+                line.entry.coin.to_string(),
+                line.entry.grade,
+                dollars(line.unit_value as u64),
+                dollars(line.value())
+            )?;
+        }
+        writeln!(f, "face value {}", dollars(self.face_value()))?;
+        write!(f, "total      {}", dollars(self.total()))
+    }
+}
+
+// One row of the CSV file. The state is only filled in for quarters.
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    coin: String,
+    state: Option<String>,
+    year: u16,
+    grade: String,
+    count: u32,
+}
+
+impl Record {
+    fn into_entry(self) -> Result<(Entry, u32), CatalogError> {
+        let coin = match (self.coin.trim().to_ascii_lowercase().as_str(), self.state) {
+            ("quarter", Some(state)) => {
+                Coin::Quarter(UsState::from_name(&state).ok_or(CatalogError::UnknownState(state))?)
+            }
+            ("quarter", None) => return Err(CatalogError::UnknownState(String::new())),
+            (name, _) => coin_from_name(name).ok_or(CatalogError::UnknownCoin(self.coin))?,
+        };
+        check_year(coin, self.year)?;
+        let entry = Entry {
+            coin,
+            year: self.year,
+            grade: self.grade.parse()?,
+        };
+        Ok((entry, self.count))
+    }
+}
+
+fn coin_name(coin: &Coin) -> &'static str {
+    match coin {
+        Coin::Penny => "penny",
+        Coin::Nickel => "nickel",
+        Coin::Dime => "dime",
+        Coin::Quarter(_) => "quarter",
+        Coin::HalfDollar => "half dollar",
+        Coin::Dollar => "dollar",
+    }
+}
+
+// every coin but the quarter, which needs a state
+fn coin_from_name(name: &str) -> Option<Coin> {
+    [
+        Coin::Penny,
+        Coin::Nickel,
+        Coin::Dime,
+        Coin::HalfDollar,
+        Coin::Dollar,
+    ]
+    .into_iter()
+    .find(|coin| coin_name(coin) == name)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Catalog {
+    entries: BTreeMap<Entry, u32>,
+}
+
+impl Catalog {
+    pub fn new() -> Catalog {
+        Catalog::default()
+    }
+
+    pub fn add(
+        &mut self,
+        coin: Coin,
+        year: u16,
+        grade: Grade,
+        count: u32,
+    ) -> Result<(), CatalogError> {
+        check_year(coin, year)?;
+        self.add_entry(Entry { coin, year, grade }, count)
+    }
+
+    // entries with a count of 0 are never kept
+    fn add_entry(&mut self, entry: Entry, count: u32) -> Result<(), CatalogError> {
+        if count == 0 {
+            return Ok(());
+        }
+        let have = self.entries.entry(entry).or_insert(0);
+        *have = have.checked_add(count).ok_or(CatalogError::TooMany {
+            entry,
+            have: *have,
+            adding: count,
+        })?;
+        Ok(())
+    }
+
+    // for a sale or a trade
+    pub fn remove(
+        &mut self,
+        coin: Coin,
+        year: u16,
+        grade: Grade,
+        count: u32,
+    ) -> Result<(), CatalogError> {
+        let entry = Entry { coin, year, grade };
+        let have = self.entries.get(&entry).copied().unwrap_or(0);
+        if have < count {
+            return Err(CatalogError::NotEnough {
+                coin,
+                year,
+                grade,
+                have,
+            });
+        }
+        if have == count {
+            self.entries.remove(&entry);
+        } else {
+            self.entries.insert(entry, have - count);
+        }
+        Ok(())
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&Entry, u32)> {
+        self.entries.iter().map(|(entry, &count)| (entry, count))
+    }
+
+    // of this coin across every year and grade
+    pub fn count(&self, coin: Coin) -> u32 {
+        self.entries()
+            .filter(|(entry, _)| entry.coin == coin)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    pub fn owned_states(&self) -> BTreeSet<UsState> {
+        self.entries
+            .keys()
+            .filter_map(|entry| match entry.coin {
+                Coin::Quarter(state) => Some(state),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // in release order
+    pub fn missing_states(&self) -> Vec<UsState> {
+        let owned = self.owned_states();
+        UsState::ALL
+            .into_iter()
+            .filter(|state| !owned.contains(state))
+            .collect()
+    }
+
+    pub fn has_full_state_set(&self) -> bool {
+        self.owned_states().len() == UsState::ALL.len()
+    }
+
+    // the best grade owned for each state
+    pub fn best_grades(&self) -> BTreeMap<UsState, Grade> {
+        let mut best = BTreeMap::new();
+        for entry in self.entries.keys() {
+            if let Coin::Quarter(state) = entry.coin {
+                let grade = best.entry(state).or_insert(entry.grade);
+                *grade = (*grade).max(entry.grade);
+            }
+        }
+        best
+    }
+
+    pub fn valuation(&self, guide: &PriceGuide) -> Valuation {
+        let mut lines: Vec<ValuationLine> = self
+            .entries()
+            .map(|(entry, count)| ValuationLine {
+                entry: *entry,
+                count,
+                unit_value: guide.price(entry.coin, entry.grade),
+            })
+            .collect();
+        lines.sort_by_key(|line| std::cmp::Reverse(line.value()));
+        Valuation { lines }
+    }
+
+    // Adds the rows of a CSV file with the columns coin, state, year, grade and count.
+    // Nothing is added when a row is bad, rows with a count of 0 add nothing like add does.
+    // Returns the number of rows read.
+    pub fn import(&mut self, reader: impl Read) -> Result<usize, CatalogError> {
+        let mut reader = csv::Reader::from_reader(reader);
+        reader.headers()?;
+        let mut staged = self.clone();
+        let mut rows = 0;
+        for (index, record) in reader.deserialize::<Record>().enumerate() {
+            let row = |error| CatalogError::Row {
+                row: index + 1,
+                error: Box::new(error),
+            };
+            // a failing reader is not the row's fault
+            let record = record.map_err(|e| match CatalogError::from(e) {
+                CatalogError::Io(e) => CatalogError::Io(e),
+                e => row(e),
+            })?;
+            let (entry, count) = record.into_entry().map_err(row)?;
+            staged.add_entry(entry, count).map_err(row)?;
+            rows += 1;
+        }
+        *self = staged;
+        Ok(rows)
+    }
+
+    pub fn export(&self, writer: impl Write) -> Result<(), CatalogError> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for (entry, count) in self.entries() {
+            writer.serialize(Record {
+                coin: coin_name(&entry.coin).to_string(),
+                state: match entry.coin {
+                    Coin::Quarter(state) => Some(state.name().to_string()),
+                    _ => None,
+                },
+                year: entry.year,
+                grade: entry.grade.to_string(),
+                count,
+            })?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quarter(state: UsState) -> Coin {
+        Coin::Quarter(state)
+    }
+
+    fn sample() -> Catalog {
+        let mut catalog = Catalog::new();
+        catalog
+            .add(quarter(UsState::Delaware), 1999, Grade::Uncirculated, 2)
+            .unwrap();
+        catalog
+            .add(quarter(UsState::Delaware), 1999, Grade::Fine, 1)
+            .unwrap();
+        catalog
+            .add(quarter(UsState::NewYork), 2001, Grade::VeryFine, 1)
+            .unwrap();
+        catalog.add(Coin::Penny, 1909, Grade::Good, 3).unwrap();
+        catalog
+    }
+
+    #[test]
+    fn counts_and_missing_states() {
+        let mut catalog = sample();
+        assert_eq!(catalog.count(quarter(UsState::Delaware)), 3);
+        assert_eq!(catalog.missing_states().len(), 48);
+        assert_eq!(catalog.missing_states()[0], UsState::Pennsylvania);
+        assert_eq!(
+            catalog.best_grades()[&UsState::Delaware],
+            Grade::Uncirculated
+        );
+        assert!(!catalog.has_full_state_set());
+
+        for state in UsState::ALL {
+            catalog
+                .add(quarter(state), state.quarter_year(), Grade::Fine, 1)
+                .unwrap();
+        }
+        assert!(catalog.has_full_state_set());
+        assert!(catalog.missing_states().is_empty());
+    }
+
+    #[test]
+    fn years_are_checked() {
+        let mut catalog = Catalog::new();
+        assert!(matches!(
+            catalog.add(quarter(UsState::Hawaii), 1999, Grade::Fine, 1),
+            Err(CatalogError::InvalidYear { year: 1999, .. })
+        ));
+        assert!(matches!(
+            catalog.add(Coin::Nickel, 1800, Grade::Fine, 1),
+            Err(CatalogError::InvalidYear { .. })
+        ));
+        assert_eq!(catalog, Catalog::new());
+    }
+
+    #[test]
+    fn removing() {
+        let mut catalog = sample();
+        catalog.remove(Coin::Penny, 1909, Grade::Good, 2).unwrap();
+        assert!(matches!(
+            catalog.remove(Coin::Penny, 1909, Grade::Good, 2),
+            Err(CatalogError::NotEnough { have: 1, .. })
+        ));
+        assert!(matches!(
+            catalog.add(Coin::Penny, 1909, Grade::Good, u32::MAX),
+            Err(CatalogError::TooMany { have: 1, .. })
+        ));
+        assert_eq!(catalog.count(Coin::Penny), 1);
+        catalog.remove(Coin::Penny, 1909, Grade::Good, 1).unwrap();
+        assert_eq!(catalog.count(Coin::Penny), 0);
+    }
+
+    #[test]
+    fn valuation_report() {
+        let mut guide = PriceGuide::new();
+        guide.set(quarter(UsState::Delaware), Grade::Uncirculated, 150);
+        guide.set(Coin::Penny, Grade::Good, 275);
+        let valuation = sample().valuation(&guide);
+
+        assert_eq!(valuation.lines[0].entry.coin, Coin::Penny);
+        assert_eq!(valuation.total(), 3 * 275 + 2 * 150 + 25 + 25);
+        assert_eq!(valuation.face_value(), 3 + 4 * 25);
+        let report = valuation.to_string();
+        assert!(report.contains("   3 x 1909 penny"));
+        assert!(report.ends_with("total      $11.75"));
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let catalog = sample();
+        let mut file = Vec::new();
+        catalog.export(&mut file).unwrap();
+        let text = String::from_utf8(file.clone()).unwrap();
+        assert!(text.starts_with("coin,state,year,grade,count\npenny,,1909,G,3\n"));
+        assert!(text.contains("quarter,New York,2001,VF,1"));
+
+        let mut loaded = Catalog::new();
+        assert_eq!(loaded.import(file.as_slice()).unwrap(), 4);
+        assert_eq!(loaded, catalog);
+
+        // a buffer with no room left fails like a full disk
+        let full: &mut [u8] = &mut [];
+        assert!(matches!(catalog.export(full), Err(CatalogError::Io(_))));
+    }
+
+    // Hands out its bytes, then fails like a pulled disk.
+    struct Failing<'a>(&'a [u8]);
+
+    impl Read for Failing<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::Error::other("disk gone"));
+            }
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn read_failures_import_nothing() {
+        let mut catalog = Catalog::new();
+        for file in ["", "coin,state,year,grade,count\ndime,,1950,F,1\n"] {
+            let error = catalog.import(Failing(file.as_bytes())).unwrap_err();
+            assert!(matches!(error, CatalogError::Io(_)), "{:?}", error);
+            assert_eq!(error.to_string(), "disk gone");
+            assert_eq!(catalog, Catalog::new());
+        }
+    }
+
+    #[test]
+    fn bad_rows_import_nothing() {
+        let file = "coin,state,year,grade,count\n\
+                    quarter,ohio,2002,xf,1\n\
+                    quarter,Atlantis,2002,F,1\n";
+        let mut catalog = Catalog::new();
+        let error = catalog.import(file.as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "row 2: unknown state \"Atlantis\"");
+        assert_eq!(catalog, Catalog::new());
+
+        let file = "coin,state,year,grade,count\ndime,,1950,shiny,1\n";
+        assert!(matches!(
+            catalog.import(file.as_bytes()),
+            Err(CatalogError::Row { row: 1, .. })
+        ));
+        let file = "coin,state,year,grade,count\ndime,,many,F,1\n";
+        assert!(matches!(
+            catalog.import(file.as_bytes()),
+            Err(CatalogError::Row { row: 1, .. })
+        ));
+
+        let file = "coin,state,year,grade,count\ndime,,1950,F,4294967295\ndime,,1950,F,1\n";
+        let error = catalog.import(file.as_bytes()).unwrap_err();
+        assert!(matches!(
+            &error,
+            CatalogError::Row { row: 2, error } if matches!(**error, CatalogError::TooMany { have: u32::MAX, adding: 1, .. })
+        ));
+        assert_eq!(catalog, Catalog::new());
+
+        let file = "coin,state,year,grade,count\nquarter,ohio,2002,xf,1\ndime,,1950,F,0\n";
+        assert_eq!(catalog.import(file.as_bytes()).unwrap(), 2);
+        assert_eq!(catalog.entries().count(), 1);
+        assert_eq!(
+            catalog.entries().next(),
+            Some((
+                &Entry {
+                    coin: quarter(UsState::Ohio),
+                    year: 2002,
+                    grade: Grade::ExtremelyFine
+                },
+                1
+            ))
+        );
+    }
+}
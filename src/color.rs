@@ -0,0 +1,620 @@
+// https://doc.rust-lang.org/book/ch05-01-defining-structs.html#using-tuple-structs-without-named-fields-to-create-different-types
+// A real color for the `Color(i32, i32, i32)` tuple struct: every channel is checked to fit in 8 bits.
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    // 255 is fully opaque
+    pub a: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColorError {
+    ChannelOutOfRange { channel: char, value: i32 },
+    InvalidHex(String),
+    UnknownName(String),
+}
+
+impl Display for ColorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorError::ChannelOutOfRange { channel, value } => write!(
+                f,
+                "channel {} must be between 0 and 255, got {}",
+                channel, value
+            ),
+            ColorError::InvalidHex(s) => {
+                write!(
+                    f,
+                    "{:?} is not a #rgb, #rgba, #rrggbb or #rrggbbaa color",
+                    s
+                )
+            }
+            ColorError::UnknownName(s) => write!(f, "{:?} is not a CSS color name", s),
+        }
+    }
+}
+
+impl std::error::Error for ColorError {}
+
+// hue in degrees [0, 360), saturation and lightness in [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsl {
+    pub h: f64,
+    pub s: f64,
+    pub l: f64,
+}
+
+// hue in degrees [0, 360), saturation and value in [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsv {
+    pub h: f64,
+    pub s: f64,
+    pub v: f64,
+}
+
+// WCAG 2 contrast thresholds, for normal and large text
+// https://www.w3.org/TR/WCAG21/#contrast-minimum
+pub const WCAG_AA: f64 = 4.5;
+pub const WCAG_AA_LARGE: f64 = 3.0;
+pub const WCAG_AAA: f64 = 7.0;
+pub const WCAG_AAA_LARGE: f64 = 4.5;
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+
+    // Accepts a CSS name (any case) or a hex color with leading '#'.
+    pub fn parse(s: &str) -> Result<Color, ColorError> {
+        let s = s.trim();
+        match s.strip_prefix('#') {
+            Some(hex) => Color::from_hex(hex).ok_or_else(|| ColorError::InvalidHex(s.to_string())),
+            None => Color::from_name(s).ok_or_else(|| ColorError::UnknownName(s.to_string())),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Color> {
+        if name.eq_ignore_ascii_case("transparent") {
+            return Some(Color::TRANSPARENT);
+        }
+        CSS_NAMES
+            .iter()
+            .find(|(css, _)| css.eq_ignore_ascii_case(name))
+            .map(|(_, color)| *color)
+    }
+
+    // the CSS name for an opaque color, when it has one
+    pub fn name(&self) -> Option<&'static str> {
+        CSS_NAMES
+            .iter()
+            .find(|(_, color)| color == self)
+            .map(|(css, _)| *css)
+    }
+
+    // rgb, rgba, rrggbb or rrggbbaa without the '#'
+    fn from_hex(hex: &str) -> Option<Color> {
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok();
+        let pair = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        match hex.len() {
+            3 | 4 => {
+                let short = |i| digit(i).map(|d| d * 17);
+                let a = if hex.len() == 4 { short(3)? } else { 255 };
+                Some(Color::rgba(short(0)?, short(1)?, short(2)?, a))
+            }
+            6 | 8 => {
+                let a = if hex.len() == 8 { pair(6)? } else { 255 };
+                Some(Color::rgba(pair(0)?, pair(2)?, pair(4)?, a))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.a == 255
+    }
+
+    pub fn with_alpha(self, a: u8) -> Color {
+        Color { a, ..self }
+    }
+
+    pub fn to_hsl(&self) -> Hsl {
+        let (r, g, b) = self.unit_channels();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        let l = (max + min) / 2.0;
+        let s = if delta == 0.0 {
+            0.0
+        } else {
+            delta / (1.0 - (2.0 * l - 1.0).abs())
+        };
+        Hsl {
+            h: hue(r, g, b, max, delta),
+            s,
+            l,
+        }
+    }
+
+    pub fn from_hsl(hsl: Hsl) -> Color {
+        let s = hsl.s.clamp(0.0, 1.0);
+        let l = hsl.l.clamp(0.0, 1.0);
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        Color::from_chroma(hsl.h, chroma, l - chroma / 2.0)
+    }
+
+    pub fn to_hsv(&self) -> Hsv {
+        let (r, g, b) = self.unit_channels();
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+        let s = if max == 0.0 { 0.0 } else { delta / max };
+        Hsv {
+            h: hue(r, g, b, max, delta),
+            s,
+            v: max,
+        }
+    }
+
+    pub fn from_hsv(hsv: Hsv) -> Color {
+        let s = hsv.s.clamp(0.0, 1.0);
+        let v = hsv.v.clamp(0.0, 1.0);
+        let chroma = v * s;
+        Color::from_chroma(hsv.h, chroma, v - chroma)
+    }
+
+    // shared end of the HSL and HSV conversions
+    fn from_chroma(h: f64, chroma: f64, m: f64) -> Color {
+        let h = h.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        Color::rgb(to_channel(r + m), to_channel(g + m), to_channel(b + m))
+    }
+
+    fn unit_channels(&self) -> (f64, f64, f64) {
+        (
+            self.r as f64 / 255.0,
+            self.g as f64 / 255.0,
+            self.b as f64 / 255.0,
+        )
+    }
+
+    // Linear mix of every channel, alpha included: 0.0 gives self and 1.0 gives other.
+    pub fn blend(&self, other: &Color, t: f64) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Color::rgba(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+            mix(self.a, other.a),
+        )
+    }
+
+    // Paints self on top of background using its alpha ("source over" compositing).
+    pub fn over(&self, background: &Color) -> Color {
+        let src_a = self.a as f64 / 255.0;
+        let dst_a = background.a as f64 / 255.0;
+        let out_a = src_a + dst_a * (1.0 - src_a);
+        if out_a == 0.0 {
+            return Color::TRANSPARENT;
+        }
+        let channel = |s: u8, d: u8| {
+            let s = s as f64 / 255.0;
+            let d = d as f64 / 255.0;
+            to_channel((s * src_a + d * dst_a * (1.0 - src_a)) / out_a)
+        };
+        Color::rgba(
+            channel(self.r, background.r),
+            channel(self.g, background.g),
+            channel(self.b, background.b),
+            to_channel(out_a),
+        )
+    }
+
+    // WCAG relative luminance, 0.0 for black and 1.0 for white. Alpha is ignored.
+    pub fn relative_luminance(&self) -> f64 {
+        let linear = |c: u8| {
+            let c = c as f64 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * linear(self.r) + 0.7152 * linear(self.g) + 0.0722 * linear(self.b)
+    }
+
+    // From 1.0 (same luminance) to 21.0 (black on white), the order of the colors does not matter.
+    pub fn contrast_ratio(&self, other: &Color) -> f64 {
+        let a = self.relative_luminance();
+        let b = other.relative_luminance();
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    // Whether text in this color is readable on the background at WCAG level AA.
+    pub fn is_readable_on(&self, background: &Color, large_text: bool) -> bool {
+        let minimum = if large_text { WCAG_AA_LARGE } else { WCAG_AA };
+        self.contrast_ratio(background) >= minimum
+    }
+}
+
+fn hue(r: f64, g: f64, b: f64, max: f64, delta: f64) -> f64 {
+    if delta == 0.0 {
+        return 0.0;
+    }
+    let h = if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    h * 60.0
+}
+
+fn to_channel(unit: f64) -> u8 {
+    (unit.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn checked(channel: char, value: i32) -> Result<u8, ColorError> {
+    u8::try_from(value).map_err(|_| ColorError::ChannelOutOfRange { channel, value })
+}
+
+// for the i32 channels of the tuple struct in the structs chapter
+impl TryFrom<(i32, i32, i32)> for Color {
+    type Error = ColorError;
+
+    fn try_from((r, g, b): (i32, i32, i32)) -> Result<Self, Self::Error> {
+        Ok(Color::rgb(
+            checked('r', r)?,
+            checked('g', g)?,
+            checked('b', b)?,
+        ))
+    }
+}
+
+impl TryFrom<(i32, i32, i32, i32)> for Color {
+    type Error = ColorError;
+
+    fn try_from((r, g, b, a): (i32, i32, i32, i32)) -> Result<Self, Self::Error> {
+        Ok(Color::rgba(
+            checked('r', r)?,
+            checked('g', g)?,
+            checked('b', b)?,
+            checked('a', a)?,
+        ))
+    }
+}
+
+impl FromStr for Color {
+    type Err = ColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Color::parse(s)
+    }
+}
+
+// #rrggbb, or #rrggbbaa when not fully opaque
+impl Display for Color {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if !self.is_opaque() {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Color::BLACK
+    }
+}
+
+// https://www.w3.org/TR/css-color-4/#named-colors
+const CSS_NAMES: [(&str, Color); 148] = [
+    ("aliceblue", Color::rgb(240, 248, 255)),
+    ("antiquewhite", Color::rgb(250, 235, 215)),
+    ("aqua", Color::rgb(0, 255, 255)),
+    ("aquamarine", Color::rgb(127, 255, 212)),
+    ("azure", Color::rgb(240, 255, 255)),
+    ("beige", Color::rgb(245, 245, 220)),
+    ("bisque", Color::rgb(255, 228, 196)),
+    ("black", Color::rgb(0, 0, 0)),
+    ("blanchedalmond", Color::rgb(255, 235, 205)),
+    ("blue", Color::rgb(0, 0, 255)),
+    ("blueviolet", Color::rgb(138, 43, 226)),
+    ("brown", Color::rgb(165, 42, 42)),
+    ("burlywood", Color::rgb(222, 184, 135)),
+    ("cadetblue", Color::rgb(95, 158, 160)),
+    ("chartreuse", Color::rgb(127, 255, 0)),
+    ("chocolate", Color::rgb(210, 105, 30)),
+    ("coral", Color::rgb(255, 127, 80)),
+    ("cornflowerblue", Color::rgb(100, 149, 237)),
+    ("cornsilk", Color::rgb(255, 248, 220)),
+    ("crimson", Color::rgb(220, 20, 60)),
+    ("cyan", Color::rgb(0, 255, 255)),
+    ("darkblue", Color::rgb(0, 0, 139)),
+    ("darkcyan", Color::rgb(0, 139, 139)),
+    ("darkgoldenrod", Color::rgb(184, 134, 11)),
+    ("darkgray", Color::rgb(169, 169, 169)),
+    ("darkgreen", Color::rgb(0, 100, 0)),
+    ("darkgrey", Color::rgb(169, 169, 169)),
+    ("darkkhaki", Color::rgb(189, 183, 107)),
+    ("darkmagenta", Color::rgb(139, 0, 139)),
+    ("darkolivegreen", Color::rgb(85, 107, 47)),
+    ("darkorange", Color::rgb(255, 140, 0)),
+    ("darkorchid", Color::rgb(153, 50, 204)),
+    ("darkred", Color::rgb(139, 0, 0)),
+    ("darksalmon", Color::rgb(233, 150, 122)),
+    ("darkseagreen", Color::rgb(143, 188, 143)),
+    ("darkslateblue", Color::rgb(72, 61, 139)),
+    ("darkslategray", Color::rgb(47, 79, 79)),
+    ("darkslategrey", Color::rgb(47, 79, 79)),
+    ("darkturquoise", Color::rgb(0, 206, 209)),
+    ("darkviolet", Color::rgb(148, 0, 211)),
+    ("deeppink", Color::rgb(255, 20, 147)),
+    ("deepskyblue", Color::rgb(0, 191, 255)),
+    ("dimgray", Color::rgb(105, 105, 105)),
+    ("dimgrey", Color::rgb(105, 105, 105)),
+    ("dodgerblue", Color::rgb(30, 144, 255)),
+    ("firebrick", Color::rgb(178, 34, 34)),
+    ("floralwhite", Color::rgb(255, 250, 240)),
+    ("forestgreen", Color::rgb(34, 139, 34)),
+    ("fuchsia", Color::rgb(255, 0, 255)),
+    ("gainsboro", Color::rgb(220, 220, 220)),
+    ("ghostwhite", Color::rgb(248, 248, 255)),
+    ("gold", Color::rgb(255, 215, 0)),
+    ("goldenrod", Color::rgb(218, 165, 32)),
+    ("gray", Color::rgb(128, 128, 128)),
+    ("green", Color::rgb(0, 128, 0)),
+    ("greenyellow", Color::rgb(173, 255, 47)),
+    ("grey", Color::rgb(128, 128, 128)),
+    ("honeydew", Color::rgb(240, 255, 240)),
+    ("hotpink", Color::rgb(255, 105, 180)),
+    ("indianred", Color::rgb(205, 92, 92)),
+    ("indigo", Color::rgb(75, 0, 130)),
+    ("ivory", Color::rgb(255, 255, 240)),
+    ("khaki", Color::rgb(240, 230, 140)),
+    ("lavender", Color::rgb(230, 230, 250)),
+    ("lavenderblush", Color::rgb(255, 240, 245)),
+    ("lawngreen", Color::rgb(124, 252, 0)),
+    ("lemonchiffon", Color::rgb(255, 250, 205)),
+    ("lightblue", Color::rgb(173, 216, 230)),
+    ("lightcoral", Color::rgb(240, 128, 128)),
+    ("lightcyan", Color::rgb(224, 255, 255)),
+    ("lightgoldenrodyellow", Color::rgb(250, 250, 210)),
+    ("lightgray", Color::rgb(211, 211, 211)),
+    ("lightgreen", Color::rgb(144, 238, 144)),
+    ("lightgrey", Color::rgb(211, 211, 211)),
+    ("lightpink", Color::rgb(255, 182, 193)),
+    ("lightsalmon", Color::rgb(255, 160, 122)),
+    ("lightseagreen", Color::rgb(32, 178, 170)),
+    ("lightskyblue", Color::rgb(135, 206, 250)),
+    ("lightslategray", Color::rgb(119, 136, 153)),
+    ("lightslategrey", Color::rgb(119, 136, 153)),
+    ("lightsteelblue", Color::rgb(176, 196, 222)),
+    ("lightyellow", Color::rgb(255, 255, 224)),
+    ("lime", Color::rgb(0, 255, 0)),
+    ("limegreen", Color::rgb(50, 205, 50)),
+    ("linen", Color::rgb(250, 240, 230)),
+    ("magenta", Color::rgb(255, 0, 255)),
+    ("maroon", Color::rgb(128, 0, 0)),
+    ("mediumaquamarine", Color::rgb(102, 205, 170)),
+    ("mediumblue", Color::rgb(0, 0, 205)),
+    ("mediumorchid", Color::rgb(186, 85, 211)),
+    ("mediumpurple", Color::rgb(147, 112, 219)),
+    ("mediumseagreen", Color::rgb(60, 179, 113)),
+    ("mediumslateblue", Color::rgb(123, 104, 238)),
+    ("mediumspringgreen", Color::rgb(0, 250, 154)),
+    ("mediumturquoise", Color::rgb(72, 209, 204)),
+    ("mediumvioletred", Color::rgb(199, 21, 133)),
+    ("midnightblue", Color::rgb(25, 25, 112)),
+    ("mintcream", Color::rgb(245, 255, 250)),
+    ("mistyrose", Color::rgb(255, 228, 225)),
+    ("moccasin", Color::rgb(255, 228, 181)),
+    ("navajowhite", Color::rgb(255, 222, 173)),
+    ("navy", Color::rgb(0, 0, 128)),
+    ("oldlace", Color::rgb(253, 245, 230)),
+    ("olive", Color::rgb(128, 128, 0)),
+    ("olivedrab", Color::rgb(107, 142, 35)),
+    ("orange", Color::rgb(255, 165, 0)),
+    ("orangered", Color::rgb(255, 69, 0)),
+    ("orchid", Color::rgb(218, 112, 214)),
+    ("palegoldenrod", Color::rgb(238, 232, 170)),
+    ("palegreen", Color::rgb(152, 251, 152)),
+    ("paleturquoise", Color::rgb(175, 238, 238)),
+    ("palevioletred", Color::rgb(219, 112, 147)),
+    ("papayawhip", Color::rgb(255, 239, 213)),
+    ("peachpuff", Color::rgb(255, 218, 185)),
+    ("peru", Color::rgb(205, 133, 63)),
+    ("pink", Color::rgb(255, 192, 203)),
+    ("plum", Color::rgb(221, 160, 221)),
+    ("powderblue", Color::rgb(176, 224, 230)),
+    ("purple", Color::rgb(128, 0, 128)),
+    ("rebeccapurple", Color::rgb(102, 51, 153)),
+    ("red", Color::rgb(255, 0, 0)),
+    ("rosybrown", Color::rgb(188, 143, 143)),
+    ("royalblue", Color::rgb(65, 105, 225)),
+    ("saddlebrown", Color::rgb(139, 69, 19)),
+    ("salmon", Color::rgb(250, 128, 114)),
+    ("sandybrown", Color::rgb(244, 164, 96)),
+    ("seagreen", Color::rgb(46, 139, 87)),
+    ("seashell", Color::rgb(255, 245, 238)),
+    ("sienna", Color::rgb(160, 82, 45)),
+    ("silver", Color::rgb(192, 192, 192)),
+    ("skyblue", Color::rgb(135, 206, 235)),
+    ("slateblue", Color::rgb(106, 90, 205)),
+    ("slategray", Color::rgb(112, 128, 144)),
+    ("slategrey", Color::rgb(112, 128, 144)),
+    ("snow", Color::rgb(255, 250, 250)),
+    ("springgreen", Color::rgb(0, 255, 127)),
+    ("steelblue", Color::rgb(70, 130, 180)),
+    ("tan", Color::rgb(210, 180, 140)),
+    ("teal", Color::rgb(0, 128, 128)),
+    ("thistle", Color::rgb(216, 191, 216)),
+    ("tomato", Color::rgb(255, 99, 71)),
+    ("turquoise", Color::rgb(64, 224, 208)),
+    ("violet", Color::rgb(238, 130, 238)),
+    ("wheat", Color::rgb(245, 222, 179)),
+    ("white", Color::rgb(255, 255, 255)),
+    ("whitesmoke", Color::rgb(245, 245, 245)),
+    ("yellow", Color::rgb(255, 255, 0)),
+    ("yellowgreen", Color::rgb(154, 205, 50)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn parse_hex_and_names() {
+        assert_eq!(Color::parse("#ff8000"), Ok(Color::rgb(255, 128, 0)));
+        assert_eq!(Color::parse("#F80"), Ok(Color::rgb(255, 136, 0)));
+        assert_eq!(Color::parse("#ff800080"), Ok(Color::rgba(255, 128, 0, 128)));
+        assert_eq!(Color::parse("#f808"), Ok(Color::rgba(255, 136, 0, 136)));
+        assert_eq!(Color::parse("RebeccaPurple"), Ok(Color::rgb(102, 51, 153)));
+        assert_eq!(Color::parse("transparent"), Ok(Color::TRANSPARENT));
+        assert_eq!(
+            Color::parse("#12345"),
+            Err(ColorError::InvalidHex(String::from("#12345")))
+        );
+        assert_eq!(
+            Color::parse("#gg0000"),
+            Err(ColorError::InvalidHex(String::from("#gg0000")))
+        );
+        assert_eq!(
+            "blurple".parse::<Color>(),
+            Err(ColorError::UnknownName(String::from("blurple")))
+        );
+        assert_eq!(CSS_NAMES.len(), 148);
+    }
+
+    #[test]
+    fn display_is_hex() {
+        assert_eq!(Color::rgb(255, 128, 0).to_string(), "#ff8000");
+        assert_eq!(Color::rgba(0, 0, 0, 0).to_string(), "#00000000");
+        assert_eq!(
+            Color::parse(&Color::rgb(1, 2, 3).to_string()),
+            Ok(Color::rgb(1, 2, 3))
+        );
+        assert_eq!(Color::rgb(0, 128, 128).name(), Some("teal"));
+    }
+
+    #[test]
+    fn channels_are_checked() {
+        assert_eq!(Color::try_from((0, 0, 0)), Ok(Color::BLACK));
+        assert_eq!(
+            Color::try_from((0, 256, 0)),
+            Err(ColorError::ChannelOutOfRange {
+                channel: 'g',
+                value: 256
+            })
+        );
+        assert_eq!(
+            Color::try_from((0, 0, 0, -1)),
+            Err(ColorError::ChannelOutOfRange {
+                channel: 'a',
+                value: -1
+            })
+        );
+    }
+
+    #[test]
+    fn hsl_and_hsv() {
+        let orange = Color::rgb(255, 128, 0);
+        let hsl = orange.to_hsl();
+        assert!(close(hsl.h, 30.118) && close(hsl.s, 1.0) && close(hsl.l, 0.5));
+        let hsv = orange.to_hsv();
+        assert!(close(hsv.h, 30.118) && close(hsv.s, 1.0) && close(hsv.v, 1.0));
+
+        assert_eq!(
+            Color::from_hsl(Hsl {
+                h: 0.0,
+                s: 0.0,
+                l: 0.5
+            }),
+            Color::rgb(128, 128, 128)
+        );
+        assert_eq!(
+            Color::from_hsv(Hsv {
+                h: 240.0,
+                s: 1.0,
+                v: 1.0
+            }),
+            Color::rgb(0, 0, 255)
+        );
+        assert_eq!(
+            Color::from_hsl(Hsl {
+                h: -120.0,
+                s: 1.0,
+                l: 0.5
+            }),
+            Color::rgb(0, 0, 255)
+        );
+
+        // every CSS color survives the round trip
+        for (_, color) in CSS_NAMES.iter() {
+            assert_eq!(Color::from_hsl(color.to_hsl()), *color);
+            assert_eq!(Color::from_hsv(color.to_hsv()), *color);
+        }
+    }
+
+    #[test]
+    fn blending() {
+        assert_eq!(
+            Color::BLACK.blend(&Color::WHITE, 0.5),
+            Color::rgb(128, 128, 128)
+        );
+        assert_eq!(Color::BLACK.blend(&Color::WHITE, 2.0), Color::WHITE);
+        let half_red = Color::rgba(255, 0, 0, 128);
+        assert_eq!(half_red.over(&Color::WHITE), Color::rgb(255, 127, 127));
+        assert_eq!(
+            Color::TRANSPARENT.over(&Color::TRANSPARENT),
+            Color::TRANSPARENT
+        );
+        assert_eq!(Color::BLACK.over(&Color::WHITE), Color::BLACK);
+    }
+
+    #[test]
+    fn contrast() {
+        assert!(close(Color::BLACK.contrast_ratio(&Color::WHITE), 21.0));
+        assert!(close(Color::WHITE.contrast_ratio(&Color::WHITE), 1.0));
+        // #767676 is the lightest gray that passes AA on white
+        let gray = Color::parse("#767676").unwrap();
+        assert!(close(gray.contrast_ratio(&Color::WHITE), 4.542));
+        assert!(gray.is_readable_on(&Color::WHITE, false));
+        assert!(!Color::parse("#777777")
+            .unwrap()
+            .is_readable_on(&Color::WHITE, false));
+        assert!(Color::parse("#777777")
+            .unwrap()
+            .is_readable_on(&Color::WHITE, true));
+    }
+}
@@ -0,0 +1,510 @@
+// https://doc.rust-lang.org/book/ch06-03-if-let.html
+// A real home for config_max: settings come from defaults, a config file, environment
+// variables and command line flags, each layer overriding the ones before it. Every value
+// remembers where it came from so a bad one can be reported with its file and line.
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub max: Option<u8>,
+    pub name: Option<String>,
+    pub threads: Option<usize>,
+    pub verbose: bool,
+}
+
+// key, what it takes and its default
+const KEYS: [(&str, &str, Option<&str>); 4] = [
+    ("max", "a number from 0 to 255", Some("3")),
+    ("name", "any text", None),
+    ("threads", "a positive number", None),
+    ("verbose", "true or false", Some("false")),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File { path: PathBuf, line: usize },
+    Env { var: String },
+    Cli { flag: String },
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "the defaults"),
+            Source::File { path, line } => write!(f, "{} line {}", path.display(), line),
+            Source::Env { var } => write!(f, "environment variable {}", var),
+            Source::Cli { flag } => write!(f, "command line flag {}", flag),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    // a line in a config file that is not key = value
+    Syntax {
+        path: PathBuf,
+        line: usize,
+    },
+    UnknownKey {
+        key: String,
+        source: Source,
+    },
+    InvalidValue {
+        key: String,
+        value: String,
+        expected: &'static str,
+        source: Source,
+    },
+    // a flag at the end of the command line without its value
+    MissingValue {
+        flag: String,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => {
+                write!(f, "cannot read {}: {}", path.display(), error)
+            }
+            ConfigError::Syntax { path, line } => {
+                write!(f, "{} line {}: expected key = value", path.display(), line)
+            }
+            ConfigError::UnknownKey { key, source } => {
+                write!(f, "unknown setting {:?} in {}", key, source)
+            }
+            ConfigError::InvalidValue {
+                key,
+                value,
+                expected,
+                source,
+            } => write!(
+                f,
+                "{} = {:?} in {} should be {}",
+                key, value, source, expected
+            ),
+            ConfigError::MissingValue { flag } => write!(f, "{} needs a value", flag),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    pub value: String,
+    pub source: Source,
+}
+
+// The merged raw values, before they are turned into a Config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Settings {
+    values: BTreeMap<String, Setting>,
+}
+
+impl Settings {
+    pub fn get(&self, key: &str) -> Option<&Setting> {
+        self.values.get(key)
+    }
+
+    fn set(&mut self, key: &str, value: &str, source: Source) -> Result<(), ConfigError> {
+        if !is_known(key) {
+            return Err(ConfigError::UnknownKey {
+                key: key.to_string(),
+                source,
+            });
+        }
+        let setting = Setting {
+            value: value.to_string(),
+            source,
+        };
+        self.values.insert(key.to_string(), setting);
+        Ok(())
+    }
+
+    fn parse<T: FromStr>(&self, key: &str) -> Result<Option<T>, ConfigError> {
+        let Some(setting) = self.get(key) else {
+            return Ok(None);
+        };
+        match setting.value.parse() {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(self.invalid(key)),
+        }
+    }
+
+    fn parse_bool(&self, key: &str) -> Result<Option<bool>, ConfigError> {
+        let Some(setting) = self.get(key) else {
+            return Ok(None);
+        };
+        match setting.value.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Ok(Some(true)),
+            "false" | "no" | "off" | "0" => Ok(Some(false)),
+            _ => Err(self.invalid(key)),
+        }
+    }
+
+    fn invalid(&self, key: &str) -> ConfigError {
+        let setting = &self.values[key];
+        let expected = KEYS
+            .iter()
+            .find(|(known, _, _)| *known == key)
+            .map_or("", |(_, expected, _)| expected);
+        ConfigError::InvalidValue {
+            key: key.to_string(),
+            value: setting.value.clone(),
+            expected,
+            source: setting.source.clone(),
+        }
+    }
+
+    pub fn to_config(&self) -> Result<Config, ConfigError> {
+        let threads = self.parse::<usize>("threads")?;
+        if threads == Some(0) {
+            return Err(self.invalid("threads"));
+        }
+        Ok(Config {
+            max: self.parse("max")?,
+            name: self.parse("name")?,
+            threads,
+            verbose: self.parse_bool("verbose")?.unwrap_or(false),
+        })
+    }
+}
+
+enum Layer {
+    File {
+        path: PathBuf,
+        optional: bool,
+    },
+    Text {
+        path: PathBuf,
+        text: String,
+    },
+    Env {
+        prefix: String,
+        vars: Vec<(String, String)>,
+    },
+    Args(Vec<String>),
+}
+
+// Collects the layers in the order they apply, later ones win. Nothing is read until load.
+//
+//     let config = Loader::new()
+//         .with_optional_file("app.conf")
+//         .with_env("APP_", std::env::vars())
+//         .with_args(std::env::args().skip(1))
+//         .load()?;
+#[derive(Default)]
+pub struct Loader {
+    layers: Vec<Layer>,
+}
+
+impl Loader {
+    pub fn new() -> Loader {
+        Loader::default()
+    }
+
+    // Lines of key = value. Blank lines and lines starting with # are skipped.
+    pub fn with_file(mut self, path: impl AsRef<Path>) -> Loader {
+        self.layers.push(Layer::File {
+            path: path.as_ref().to_path_buf(),
+            optional: false,
+        });
+        self
+    }
+
+    // like with_file, but a missing file is skipped
+    pub fn with_optional_file(mut self, path: impl AsRef<Path>) -> Loader {
+        self.layers.push(Layer::File {
+            path: path.as_ref().to_path_buf(),
+            optional: true,
+        });
+        self
+    }
+
+    // file contents that are already in memory, path is only used in errors
+    pub fn with_file_text(mut self, path: impl AsRef<Path>, text: &str) -> Loader {
+        self.layers.push(Layer::Text {
+            path: path.as_ref().to_path_buf(),
+            text: text.to_string(),
+        });
+        self
+    }
+
+    // Variables named prefix + key in any case, like APP_MAX. Others are ignored, also ones
+    // with the prefix but no known key after it.
+    pub fn with_env(
+        mut self,
+        prefix: &str,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Loader {
+        self.layers.push(Layer::Env {
+            prefix: prefix.to_string(),
+            vars: vars.into_iter().collect(),
+        });
+        self
+    }
+
+    // --key value, --key=value, and --verbose or --no-verbose for true or false.
+    // Arguments that are not flags are ignored.
+    pub fn with_args(mut self, args: impl IntoIterator<Item = String>) -> Loader {
+        self.layers.push(Layer::Args(args.into_iter().collect()));
+        self
+    }
+
+    pub fn settings(&self) -> Result<Settings, ConfigError> {
+        let mut settings = Settings::default();
+        for (key, _, default) in KEYS {
+            if let Some(value) = default {
+                settings.set(key, value, Source::Default)?;
+            }
+        }
+        for layer in &self.layers {
+            match layer {
+                Layer::File { path, optional } => match fs::read_to_string(path) {
+                    Ok(text) => read_file(&mut settings, path, &text)?,
+                    Err(e) if *optional && e.kind() == io::ErrorKind::NotFound => {}
+                    Err(error) => {
+                        return Err(ConfigError::Io {
+                            path: path.clone(),
+                            error,
+                        })
+                    }
+                },
+                Layer::Text { path, text } => read_file(&mut settings, path, text)?,
+                Layer::Env { prefix, vars } => read_env(&mut settings, prefix, vars)?,
+                Layer::Args(args) => read_args(&mut settings, args)?,
+            }
+        }
+        Ok(settings)
+    }
+
+    pub fn load(&self) -> Result<Config, ConfigError> {
+        self.settings()?.to_config()
+    }
+}
+
+fn read_file(settings: &mut Settings, path: &Path, text: &str) -> Result<(), ConfigError> {
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(ConfigError::Syntax {
+                path: path.to_path_buf(),
+                line: line_number,
+            });
+        };
+        let source = Source::File {
+            path: path.to_path_buf(),
+            line: line_number,
+        };
+        settings.set(key.trim(), unquote(value.trim()), source)?;
+    }
+    Ok(())
+}
+
+// "text" and 'text' lose their quotes, so values can keep spaces at the ends
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+fn is_known(key: &str) -> bool {
+    KEYS.iter().any(|(known, _, _)| *known == key)
+}
+
+// Other programs can have variables with the same prefix, so unknown keys are skipped here
+// while files and flags refuse them.
+fn read_env(
+    settings: &mut Settings,
+    prefix: &str,
+    vars: &[(String, String)],
+) -> Result<(), ConfigError> {
+    for (var, value) in vars {
+        let Some(key) = var.strip_prefix(prefix) else {
+            continue;
+        };
+        let key = key.to_ascii_lowercase();
+        if !is_known(&key) {
+            continue;
+        }
+        let source = Source::Env { var: var.clone() };
+        settings.set(&key, value, source)?;
+    }
+    Ok(())
+}
+
+fn read_args(settings: &mut Settings, args: &[String]) -> Result<(), ConfigError> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.strip_prefix("--") else {
+            continue;
+        };
+        let source = Source::Cli { flag: arg.clone() };
+        if let Some((key, value)) = flag.split_once('=') {
+            settings.set(key, value, source)?;
+        } else if flag == "verbose" {
+            settings.set(flag, "true", source)?;
+        } else if flag == "no-verbose" {
+            settings.set("verbose", "false", source)?;
+        } else {
+            let value = args
+                .next()
+                .ok_or_else(|| ConfigError::MissingValue { flag: arg.clone() })?;
+            settings.set(flag, value, source)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn defaults_only() {
+        let config = Loader::new().load().unwrap();
+        assert_eq!(
+            config,
+            Config {
+                max: Some(3),
+                name: None,
+                threads: None,
+                verbose: false,
+            }
+        );
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let file = "# app settings\nmax = 10\nname = \" ferris \"\n\nthreads=4\n";
+        let loader = Loader::new()
+            .with_file_text("app.conf", file)
+            .with_env(
+                "APP_",
+                vars(&[("APP_MAX", "20"), ("HOME", "/root"), ("APP_VERBOSE", "yes")]),
+            )
+            .with_args(args(&["run", "--max", "30", "--no-verbose"]));
+        let config = loader.load().unwrap();
+        assert_eq!(config.max, Some(30));
+        assert_eq!(config.name.as_deref(), Some(" ferris "));
+        assert_eq!(config.threads, Some(4));
+        assert!(!config.verbose);
+
+        let settings = loader.settings().unwrap();
+        assert_eq!(
+            settings.get("threads").unwrap().source,
+            Source::File {
+                path: PathBuf::from("app.conf"),
+                line: 5
+            }
+        );
+        assert_eq!(
+            settings.get("max").unwrap().source,
+            Source::Cli {
+                flag: String::from("--max")
+            }
+        );
+    }
+
+    #[test]
+    fn errors_name_the_layer() {
+        let error = Loader::new()
+            .with_file_text("app.conf", "name = x\nmax = 300\n")
+            .load()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "max = \"300\" in app.conf line 2 should be a number from 0 to 255"
+        );
+
+        let error = Loader::new()
+            .with_file_text("app.conf", "\nmax 3\n")
+            .load()
+            .unwrap_err();
+        assert_eq!(error.to_string(), "app.conf line 2: expected key = value");
+
+        let error = Loader::new()
+            .with_file_text("app.conf", "colour = red\n")
+            .load()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown setting \"colour\" in app.conf line 1"
+        );
+        // another program's variable, not a typo
+        let config = Loader::new()
+            .with_env("APP_", vars(&[("APP_COLOUR", "red"), ("APP_MAX", "3")]))
+            .load()
+            .unwrap();
+        assert_eq!(config.max, Some(3));
+
+        let error = Loader::new()
+            .with_args(args(&["--threads=0"]))
+            .load()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "threads = \"0\" in command line flag --threads=0 should be a positive number"
+        );
+
+        let error = Loader::new()
+            .with_args(args(&["--verbose", "--name"]))
+            .load()
+            .unwrap_err();
+        assert!(matches!(error, ConfigError::MissingValue { flag } if flag == "--name"));
+    }
+
+    #[test]
+    fn files_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.conf");
+        fs::write(&path, "verbose = on\n").unwrap();
+        assert!(Loader::new().with_file(&path).load().unwrap().verbose);
+
+        let missing = dir.path().join("missing.conf");
+        assert!(Loader::new().with_optional_file(&missing).load().is_ok());
+        assert!(matches!(
+            Loader::new().with_file(&missing).load(),
+            Err(ConfigError::Io { .. })
+        ));
+    }
+}
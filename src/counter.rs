@@ -0,0 +1,338 @@
+// https://doc.rust-lang.org/book/ch08-03-hash-maps.html#hashing-functions
+// The map.entry(word).or_insert(0) counter as a type of its own: a multiset over any key that
+// can be hashed, with the hasher as a type parameter. Every key remembers when it was first
+// counted, so most_common and ordered iteration come out the same whatever the hasher does.
+use std::borrow::Borrow;
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+
+// SipHash with fixed keys, hash order stays the same from run to run.
+pub type StableHasher = BuildHasherDefault<DefaultHasher>;
+pub type FnvBuildHasher = BuildHasherDefault<FnvHasher>;
+
+// 64-bit FNV-1a. Quicker than SipHash for short keys like words but not safe against keys
+// picked to collide, so only for input that is trusted.
+#[derive(Debug, Clone, Copy)]
+pub struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tally {
+    count: usize,
+    // when the key was first counted
+    first: u64,
+}
+
+// Keys with a count of 0 are never kept.
+#[derive(Debug, Clone)]
+pub struct Counter<K, S = RandomState> {
+    tallies: HashMap<K, Tally, S>,
+    next: u64,
+}
+
+impl<K: Hash + Eq> Counter<K, RandomState> {
+    pub fn new() -> Counter<K, RandomState> {
+        Counter::with_hasher(RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, S: BuildHasher + Default> Default for Counter<K, S> {
+    fn default() -> Self {
+        Counter::with_hasher(S::default())
+    }
+}
+
+impl<K: Hash + Eq, S: BuildHasher> Counter<K, S> {
+    pub fn with_hasher(hasher: S) -> Counter<K, S> {
+        Counter {
+            tallies: HashMap::with_hasher(hasher),
+            next: 0,
+        }
+    }
+
+    pub fn add(&mut self, key: K) -> usize {
+        self.add_n(key, 1)
+    }
+
+    // returns the new count
+    pub fn add_n(&mut self, key: K, n: usize) -> usize {
+        if n == 0 {
+            return self.get(&key);
+        }
+        let next = &mut self.next;
+        let tally = self.tallies.entry(key).or_insert_with(|| {
+            *next += 1;
+            Tally {
+                count: 0,
+                first: *next,
+            }
+        });
+        tally.count = tally.count.saturating_add(n);
+        tally.count
+    }
+
+    // takes away at most what is there, returns the new count
+    pub fn remove_n<Q>(&mut self, key: &Q, n: usize) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(tally) = self.tallies.get_mut(key) else {
+            return 0;
+        };
+        tally.count = tally.count.saturating_sub(n);
+        let count = tally.count;
+        if count == 0 {
+            self.tallies.remove(key);
+        }
+        count
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.tallies.get(key).map_or(0, |tally| tally.count)
+    }
+
+    // distinct keys
+    pub fn len(&self) -> usize {
+        self.tallies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tallies.is_empty()
+    }
+
+    // all counts added up, stopping at usize::MAX like the counts themselves
+    pub fn total(&self) -> usize {
+        self.tallies
+            .values()
+            .fold(0, |total: usize, tally| total.saturating_add(tally.count))
+    }
+
+    // in whatever order the hasher puts them, quickest when order does not matter
+    pub fn iter(&self) -> impl Iterator<Item = (&K, usize)> {
+        self.tallies.iter().map(|(key, tally)| (key, tally.count))
+    }
+
+    // in the order the keys were first counted
+    pub fn iter_insertion(&self) -> impl Iterator<Item = (&K, usize)> {
+        let mut tallies: Vec<(&K, &Tally)> = self.tallies.iter().collect();
+        tallies.sort_by_key(|(_, tally)| tally.first);
+        tallies.into_iter().map(|(key, tally)| (key, tally.count))
+    }
+
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (&K, usize)>
+    where
+        K: Ord,
+    {
+        let mut counts: Vec<(&K, usize)> = self.iter().collect();
+        counts.sort_by(|a, b| a.0.cmp(b.0));
+        counts.into_iter()
+    }
+
+    // The n highest counts, a tie goes to the key counted first.
+    pub fn most_common(&self, n: usize) -> Vec<(&K, usize)> {
+        let mut tallies: Vec<(&K, &Tally)> = self.tallies.iter().collect();
+        tallies.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.1.first.cmp(&b.1.first)));
+        tallies
+            .into_iter()
+            .take(n)
+            .map(|(key, tally)| (key, tally.count))
+            .collect()
+    }
+
+    // Adds the counts of other, its new keys come after ours in the order they came to it.
+    pub fn merge<S2: BuildHasher>(&mut self, other: &Counter<K, S2>)
+    where
+        K: Clone,
+    {
+        for (key, count) in other.iter_insertion() {
+            self.add_n(key.clone(), count);
+        }
+    }
+
+    // Takes away the counts of other, keys that drop to 0 or below are gone.
+    pub fn subtract<S2: BuildHasher>(&mut self, other: &Counter<K, S2>) {
+        for (key, count) in other.iter() {
+            self.remove_n(key, count);
+        }
+    }
+
+    // Keeps the keys both have, with the smaller count.
+    pub fn intersect<S2: BuildHasher>(&mut self, other: &Counter<K, S2>) {
+        self.tallies.retain(|key, tally| {
+            tally.count = tally.count.min(other.get(key));
+            tally.count > 0
+        });
+    }
+}
+
+impl<K: Hash + Eq, S: BuildHasher> PartialEq for Counter<K, S> {
+    // same keys and counts, the order they came in does not matter
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(key, count)| other.get(key) == count)
+    }
+}
+
+impl<K: Hash + Eq, S: BuildHasher> Eq for Counter<K, S> {}
+
+impl<K: Hash + Eq, S: BuildHasher> Extend<K> for Counter<K, S> {
+    fn extend<I: IntoIterator<Item = K>>(&mut self, keys: I) {
+        for key in keys {
+            self.add(key);
+        }
+    }
+}
+
+impl<K: Hash + Eq, S: BuildHasher + Default> FromIterator<K> for Counter<K, S> {
+    fn from_iter<I: IntoIterator<Item = K>>(keys: I) -> Self {
+        let mut counter = Counter::default();
+        counter.extend(keys);
+        counter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::BTreeMap;
+
+    fn words<S: BuildHasher + Default>(text: &str) -> Counter<&str, S> {
+        text.split_whitespace().collect()
+    }
+
+    #[test]
+    fn counts_the_book_example() {
+        let mut counter: Counter<&str> = words("hello world wonderful world");
+        assert_eq!(counter.get("world"), 2);
+        assert_eq!(counter.get("missing"), 0);
+        assert_eq!((counter.len(), counter.total()), (3, 4));
+        assert_eq!(counter.most_common(2), [(&"world", 2), (&"hello", 1)]);
+        assert_eq!(
+            counter.iter_insertion().collect::<Vec<_>>(),
+            [(&"hello", 1), (&"world", 2), (&"wonderful", 1)]
+        );
+        assert_eq!(
+            counter.iter_sorted().collect::<Vec<_>>(),
+            [(&"hello", 1), (&"wonderful", 1), (&"world", 2)]
+        );
+
+        assert_eq!(counter.remove_n("world", 5), 0);
+        assert_eq!(counter.len(), 2);
+        assert_eq!(counter.add_n("world", 0), 0);
+        assert_eq!(counter.len(), 2);
+
+        counter.add_n("hello", usize::MAX);
+        assert_eq!(counter.get("hello"), usize::MAX);
+        assert_eq!(counter.total(), usize::MAX);
+    }
+
+    #[test]
+    fn same_results_with_any_hasher() {
+        let text = "b a c a b d e f g a h i j b k";
+        let random: Counter<&str> = words(text);
+        let stable: Counter<&str, StableHasher> = words(text);
+        let fnv: Counter<&str, FnvBuildHasher> = words(text);
+        assert_eq!(random.most_common(5), stable.most_common(5));
+        assert_eq!(stable.most_common(5), fnv.most_common(5));
+        assert_eq!(
+            random.iter_insertion().collect::<Vec<_>>(),
+            fnv.iter_insertion().collect::<Vec<_>>()
+        );
+        assert_eq!(random.most_common(3), [(&"b", 3), (&"a", 3), (&"c", 1)]);
+
+        // stable hash order is the same for two counters built the same way
+        let again: Counter<&str, StableHasher> = words(text);
+        assert!(stable.iter().eq(again.iter()));
+    }
+
+    #[test]
+    fn set_operations() {
+        let mut a: Counter<char> = "aaabbc".chars().collect();
+        let b: Counter<char, FnvBuildHasher> = "abbbd".chars().collect();
+
+        let mut merged = a.clone();
+        merged.merge(&b);
+        assert_eq!(
+            merged.iter_insertion().collect::<Vec<_>>(),
+            [(&'a', 4), (&'b', 5), (&'c', 1), (&'d', 1)]
+        );
+
+        let mut difference = a.clone();
+        difference.subtract(&b);
+        assert_eq!(difference, "aac".chars().collect());
+
+        a.intersect(&b);
+        assert_eq!(a, "abb".chars().collect());
+    }
+
+    fn model(keys: &[u8]) -> BTreeMap<u8, usize> {
+        let mut counts = BTreeMap::new();
+        for key in keys {
+            *counts.entry(*key).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    fn counts(counter: &Counter<u8>) -> BTreeMap<u8, usize> {
+        counter.iter().map(|(key, count)| (*key, count)).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn behaves_like_a_multiset(a in prop::collection::vec(0u8..8, 0..30), b in prop::collection::vec(0u8..8, 0..30)) {
+            let (ma, mb) = (model(&a), model(&b));
+            let ca: Counter<u8> = a.iter().copied().collect();
+            let cb: Counter<u8> = b.iter().copied().collect();
+
+            let mut merged = ca.clone();
+            merged.merge(&cb);
+            let expected = model(&[a.clone(), b.clone()].concat());
+            prop_assert_eq!(counts(&merged), expected);
+
+            let mut difference = ca.clone();
+            difference.subtract(&cb);
+            let expected: BTreeMap<u8, usize> = ma
+                .iter()
+                .map(|(k, n)| (*k, n.saturating_sub(mb.get(k).copied().unwrap_or(0))))
+                .filter(|(_, n)| *n > 0)
+                .collect();
+            prop_assert_eq!(counts(&difference), expected);
+
+            let mut both = ca.clone();
+            both.intersect(&cb);
+            let expected: BTreeMap<u8, usize> = ma
+                .iter()
+                .filter_map(|(k, n)| mb.get(k).map(|m| (*k, *n.min(m))))
+                .collect();
+            prop_assert_eq!(counts(&both), expected);
+
+            let top = ca.most_common(usize::MAX);
+            prop_assert!(top.windows(2).all(|w| w[0].1 >= w[1].1));
+            prop_assert_eq!(top.len(), ma.len());
+        }
+    }
+}
@@ -0,0 +1,309 @@
+// https://doc.rust-lang.org/book/ch09-02-recoverable-errors-with-result.html#propagating-errors
+// One error type for code that mixes I/O, parsing and the modules of this crate, so every
+// step can use ? and the message still says which file and which operation failed. Context
+// wraps an error instead of replacing it: Display shows the outermost layer, "{:#}" the
+// whole chain, and source() walks it.
+use std::fmt::{self, Debug, Display, Formatter};
+use std::io;
+use std::num::{ParseFloatError, ParseIntError};
+use std::path::{Path, PathBuf};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+pub enum Error {
+    Io(io::Error),
+    ParseInt(ParseIntError),
+    ParseFloat(ParseFloatError),
+    Json(serde_json::Error),
+    // the typed errors of the other modules, kept whole for source() and downcasting
+    Library(Box<dyn std::error::Error + Send + Sync>),
+    // for checks the program makes itself
+    Invalid(String),
+    Context {
+        operation: String,
+        path: Option<PathBuf>,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    pub fn invalid(message: impl Into<String>) -> Error {
+        Error::Invalid(message.into())
+    }
+
+    pub fn context(self, operation: impl Into<String>) -> Error {
+        Error::Context {
+            operation: operation.into(),
+            path: None,
+            source: Box::new(self),
+        }
+    }
+
+    pub fn file_context(self, operation: impl Into<String>, path: impl AsRef<Path>) -> Error {
+        Error::Context {
+            operation: operation.into(),
+            path: Some(path.as_ref().to_path_buf()),
+            source: Box::new(self),
+        }
+    }
+
+    // the error under all the context
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            error => error,
+        }
+    }
+
+    // the file of the innermost context that names one
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Context { path, source, .. } => source.path().or(path.as_deref()),
+            _ => None,
+        }
+    }
+
+    // so callers can fall back on a missing file and still report everything else
+    pub fn is_not_found(&self) -> bool {
+        matches!(self.root(), Error::Io(e) if e.kind() == io::ErrorKind::NotFound)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::ParseInt(e) => write!(f, "not a whole number: {}", e),
+            Error::ParseFloat(e) => write!(f, "not a number: {}", e),
+            Error::Json(e) => write!(f, "bad JSON: {}", e),
+            Error::Library(e) => write!(f, "{}", e),
+            Error::Invalid(message) => write!(f, "{}", message),
+            Error::Context {
+                operation,
+                path,
+                source,
+            } => {
+                write!(f, "{}", operation)?;
+                if let Some(path) = path {
+                    write!(f, " {}", path.display())?;
+                }
+                if f.alternate() {
+                    write!(f, ": {:#}", source)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// A main that returns an Error prints it with Debug, so Debug shows the whole chain as well.
+impl Debug for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::ParseInt(e) => Some(e),
+            Error::ParseFloat(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Library(e) => Some(e.as_ref()),
+            Error::Invalid(_) => None,
+            Error::Context { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ParseIntError> for Error {
+    fn from(e: ParseIntError) -> Self {
+        Error::ParseInt(e)
+    }
+}
+
+impl From<ParseFloatError> for Error {
+    fn from(e: ParseFloatError) -> Self {
+        Error::ParseFloat(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+macro_rules! from_library {
+    ($($error:ty),* $(,)?) => {
+        $(
+            impl From<$error> for Error {
+                fn from(e: $error) -> Self {
+                    Error::Library(Box::new(e))
+                }
+            }
+        )*
+    };
+}
+
+from_library!(
+    crate::auth::AuthError,
+    crate::coin::ChangeError,
+    crate::coin::catalog::CatalogError,
+    crate::color::ColorError,
+    crate::config::ConfigError,
+    crate::game::GameError,
+    crate::message::ApplyError,
+    crate::message::DecodeError,
+    crate::message::EncodeError,
+    crate::message::TextError,
+    crate::message::bus::BusError,
+    crate::network::ParseError,
+    crate::network::pool::PoolError,
+    crate::scoreboard::ScoreError,
+    crate::spreadsheet::SheetError,
+    crate::spreadsheet::csv::CsvError,
+    crate::spreadsheet::formula::FormulaError,
+    crate::strings::SliceError,
+    crate::user::EmailError,
+    crate::user::UsernameError,
+    crate::user::ValidationError,
+    crate::user::audit::AuditError,
+    crate::user::profile::InvalidProfile,
+    crate::user::profile::ProfileError,
+    crate::user::registry::ExportError,
+    crate::user::registry::ImportError,
+);
+
+// .context() on any result whose error converts into Error.
+pub trait Context<T> {
+    fn context(self, operation: impl Into<String>) -> Result<T>;
+
+    fn file_context(self, operation: impl Into<String>, path: impl AsRef<Path>) -> Result<T>;
+
+    // for messages that cost something to build, only made on an error
+    fn with_context<S: Into<String>>(self, operation: impl FnOnce() -> S) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context(self, operation: impl Into<String>) -> Result<T> {
+        self.map_err(|e| e.into().context(operation))
+    }
+
+    fn file_context(self, operation: impl Into<String>, path: impl AsRef<Path>) -> Result<T> {
+        self.map_err(|e| e.into().file_context(operation, path))
+    }
+
+    fn with_context<S: Into<String>>(self, operation: impl FnOnce() -> S) -> Result<T> {
+        self.map_err(|e| e.into().context(operation()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn read_number(path: &Path) -> Result<i64> {
+        let text = fs::read_to_string(path).file_context("reading", path)?;
+        let number = text.trim().parse::<i64>().file_context("parsing", path)?;
+        Ok(number)
+    }
+
+    #[test]
+    fn context_names_the_file_and_operation() {
+        let dir = tempfile::tempdir().unwrap();
+        let good = dir.path().join("good.txt");
+        let bad = dir.path().join("bad.txt");
+        fs::write(&good, "42\n").unwrap();
+        fs::write(&bad, "forty-two").unwrap();
+        assert_eq!(read_number(&good).unwrap(), 42);
+
+        let missing = read_number(&dir.path().join("missing.txt"))
+            .context("loading settings")
+            .unwrap_err();
+        assert!(missing.is_not_found());
+        assert_eq!(missing.to_string(), "loading settings");
+        assert_eq!(
+            missing.path(),
+            Some(dir.path().join("missing.txt").as_path())
+        );
+        assert!(format!("{:#}", missing).starts_with(&format!(
+            "loading settings: reading {}: ",
+            dir.path().join("missing.txt").display()
+        )));
+
+        let parse = read_number(&bad).unwrap_err();
+        assert!(!parse.is_not_found());
+        assert!(matches!(parse.root(), Error::ParseInt(_)));
+        assert_eq!(
+            format!("{:#}", parse),
+            format!(
+                "parsing {}: not a whole number: invalid digit found in string",
+                bad.display()
+            )
+        );
+    }
+
+    #[test]
+    fn source_walks_the_chain() {
+        let error = "x"
+            .parse::<f64>()
+            .context("inner")
+            .context("outer")
+            .unwrap_err();
+        let mut chain = Vec::new();
+        let mut next: Option<&dyn std::error::Error> = Some(&error);
+        while let Some(e) = next {
+            chain.push(e.to_string());
+            next = e.source();
+        }
+        assert_eq!(
+            chain,
+            [
+                "outer",
+                "inner",
+                "not a number: invalid float literal",
+                "invalid float literal"
+            ]
+        );
+    }
+
+    #[test]
+    fn library_errors_convert() {
+        fn parse_cidr(text: &str) -> Result<crate::network::Cidr> {
+            Ok(text.parse::<crate::network::Cidr>()?)
+        }
+        let error = parse_cidr("10.0.0.1/8")
+            .with_context(|| format!("reading the {} range", "lab"))
+            .unwrap_err();
+        let Error::Library(inner) = error.root() else {
+            panic!("expected a library error, got {:?}", error);
+        };
+        assert!(inner.downcast_ref::<crate::network::ParseError>().is_some());
+        assert!(format!("{:#}", error).starts_with("reading the lab range: "));
+        assert_eq!(Error::invalid("too big").to_string(), "too big");
+    }
+
+    #[test]
+    fn debug_shows_the_chain() {
+        let error = "x".parse::<i32>().file_context("parsing", "age.txt");
+        let error = error.context("loading the user").unwrap_err();
+        assert_eq!(
+            format!("{:?}", error),
+            "loading the user: parsing age.txt: not a whole number: invalid digit found in string"
+        );
+        let error = Error::from(crate::user::registry::ImportError::Duplicate {
+            row: 2,
+            username: crate::user::Username::parse("alice").unwrap(),
+        });
+        assert_eq!(format!("{:?}", error), "row 2: user alice already exists");
+    }
+}
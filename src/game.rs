@@ -0,0 +1,469 @@
+// https://doc.rust-lang.org/book/ch06-02-match.html#catch-all-patterns-and-the-_-placeholder
+// The dice_roll example grown into a small board game: players roll, move along a board,
+// get a fancy hat on square 3 and lose one on square 7. Every turn is written to a log.
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// Rolls from a seeded generator, or a fixed list of rolls for tests that need exact moves.
+#[derive(Debug, Clone)]
+pub struct Dice {
+    count: u8,
+    sides: u8,
+    source: Source,
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    Seeded(Box<StdRng>),
+    // repeats from the start when used up
+    Scripted { rolls: Vec<u32>, next: usize },
+}
+
+impl Dice {
+    // count dice with the given number of sides each, at least one of each
+    pub fn seeded(count: u8, sides: u8, seed: u64) -> Dice {
+        Dice {
+            count: count.max(1),
+            sides: sides.max(1),
+            source: Source::Seeded(Box::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    pub fn scripted(rolls: &[u32]) -> Dice {
+        Dice {
+            count: 1,
+            sides: 1,
+            source: Source::Scripted {
+                rolls: if rolls.is_empty() {
+                    vec![1]
+                } else {
+                    rolls.to_vec()
+                },
+                next: 0,
+            },
+        }
+    }
+
+    pub fn roll(&mut self) -> u32 {
+        match &mut self.source {
+            Source::Seeded(rng) => (0..self.count)
+                .map(|_| rng.gen_range(1..=self.sides as u32))
+                .sum(),
+            Source::Scripted { rolls, next } => {
+                let roll = rolls[*next % rolls.len()];
+                *next += 1;
+                roll
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Square {
+    AddHat,
+    RemoveHat,
+}
+
+// Squares are numbered from 0, where everyone starts, to the finish at length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Board {
+    length: u32,
+    specials: BTreeMap<u32, Square>,
+}
+
+impl Board {
+    pub fn new(length: u32) -> Board {
+        Board {
+            length,
+            specials: BTreeMap::new(),
+        }
+    }
+
+    // the board from the book, 3 adds a fancy hat and 7 removes one
+    pub fn classic(length: u32) -> Board {
+        Board::new(length)
+            .with_square(3, Square::AddHat)
+            .with_square(7, Square::RemoveHat)
+    }
+
+    pub fn with_square(mut self, position: u32, square: Square) -> Board {
+        if position > 0 && position < self.length {
+            self.specials.insert(position, square);
+        }
+        self
+    }
+
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    pub fn square(&self, position: u32) -> Option<Square> {
+        self.specials.get(&position).copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WinCondition {
+    // first to reach or pass the finish
+    ReachEnd,
+    // the finish has to be hit exactly, overshooting bounces back by the rest
+    ExactEnd,
+    // first to wear this many hats at once
+    Hats(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rules {
+    pub win: WinCondition,
+    // the game is a draw when nobody has won after this many rounds
+    pub max_rounds: u32,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            win: WinCondition::ReachEnd,
+            max_rounds: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Player {
+    pub name: String,
+    pub position: u32,
+    pub hats: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Rolled { player: usize, roll: u32 },
+    Moved { player: usize, from: u32, to: u32 },
+    GotHat { player: usize, hats: u32 },
+    LostHat { player: usize, hats: u32 },
+    // landed on 7 without a hat
+    NoHatToLose { player: usize },
+    Won { player: usize },
+    Draw { rounds: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Winner(usize),
+    Draw,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameError {
+    NoPlayers,
+    EmptyBoard,
+}
+
+impl Display for GameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::NoPlayers => write!(f, "a game needs at least one player"),
+            GameError::EmptyBoard => write!(f, "the board needs at least one square"),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+#[derive(Debug, Clone)]
+pub struct Game {
+    board: Board,
+    rules: Rules,
+    dice: Dice,
+    players: Vec<Player>,
+    turn: usize,
+    log: Vec<Event>,
+    outcome: Option<Outcome>,
+}
+
+impl Game {
+    pub fn new(names: &[&str], board: Board, rules: Rules, dice: Dice) -> Result<Game, GameError> {
+        if names.is_empty() {
+            return Err(GameError::NoPlayers);
+        }
+        if board.length == 0 {
+            return Err(GameError::EmptyBoard);
+        }
+        let players = names
+            .iter()
+            .map(|name| Player {
+                name: name.to_string(),
+                position: 0,
+                hats: 0,
+            })
+            .collect();
+        Ok(Game {
+            board,
+            rules,
+            dice,
+            players,
+            turn: 0,
+            log: Vec::new(),
+            outcome: None,
+        })
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    pub fn log(&self) -> &[Event] {
+        &self.log
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    // whose turn it is next
+    pub fn current_player(&self) -> usize {
+        self.turn % self.players.len()
+    }
+
+    pub fn round(&self) -> u32 {
+        (self.turn / self.players.len()) as u32
+    }
+
+    // One player rolls and moves. Returns the outcome once the game is over, after that
+    // nothing changes any more.
+    pub fn play_turn(&mut self) -> Option<Outcome> {
+        if self.outcome.is_some() {
+            return self.outcome;
+        }
+        if self.round() >= self.rules.max_rounds {
+            self.log.push(Event::Draw {
+                rounds: self.round(),
+            });
+            self.outcome = Some(Outcome::Draw);
+            return self.outcome;
+        }
+
+        let player = self.current_player();
+        let roll = self.dice.roll();
+        self.log.push(Event::Rolled { player, roll });
+
+        let from = self.players[player].position;
+        let to = self.destination(from, roll);
+        self.players[player].position = to;
+        self.log.push(Event::Moved { player, from, to });
+
+        match self.board.square(to) {
+            Some(Square::AddHat) => self.add_fancy_hat(player),
+            Some(Square::RemoveHat) => self.remove_fancy_hat(player),
+            None => (),
+        }
+
+        if self.has_won(player) {
+            self.log.push(Event::Won { player });
+            self.outcome = Some(Outcome::Winner(player));
+        }
+        self.turn += 1;
+        self.outcome
+    }
+
+    // Plays until someone wins or the round limit is reached.
+    pub fn play(&mut self) -> Outcome {
+        loop {
+            if let Some(outcome) = self.play_turn() {
+                return outcome;
+            }
+        }
+    }
+
+    fn destination(&self, from: u32, roll: u32) -> u32 {
+        let end = self.board.length;
+        let to = from.saturating_add(roll);
+        match self.rules.win {
+            WinCondition::ExactEnd if to > end => end - (to - end).min(end),
+            _ => to.min(end),
+        }
+    }
+
+    fn has_won(&self, player: usize) -> bool {
+        let player = &self.players[player];
+        match self.rules.win {
+            WinCondition::ReachEnd | WinCondition::ExactEnd => player.position == self.board.length,
+            WinCondition::Hats(needed) => player.hats >= needed,
+        }
+    }
+
+    fn add_fancy_hat(&mut self, player: usize) {
+        self.players[player].hats += 1;
+        let hats = self.players[player].hats;
+        self.log.push(Event::GotHat { player, hats });
+    }
+
+    fn remove_fancy_hat(&mut self, player: usize) {
+        match self.players[player].hats {
+            0 => self.log.push(Event::NoHatToLose { player }),
+            hats => {
+                self.players[player].hats = hats - 1;
+                self.log.push(Event::LostHat {
+                    player,
+                    hats: hats - 1,
+                });
+            }
+        }
+    }
+
+    // the log as text, with player names
+    pub fn describe(&self, event: &Event) -> String {
+        let name = |player: &usize| self.players[*player].name.as_str();
+        match event {
+            Event::Rolled { player, roll } => format!("{} rolls {}", name(player), roll),
+            Event::Moved { player, from, to } => {
+                format!("{} moves from {} to {}", name(player), from, to)
+            }
+            Event::GotHat { player, hats } => {
+                format!("{} gets a fancy hat and now has {}", name(player), hats)
+            }
+            Event::LostHat { player, hats } => {
+                format!("{} loses a fancy hat and now has {}", name(player), hats)
+            }
+            Event::NoHatToLose { player } => format!("{} has no hat to lose", name(player)),
+            Event::Won { player } => format!("{} wins", name(player)),
+            Event::Draw { rounds } => format!("nobody won after {} rounds", rounds),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(rolls: &[u32], rules: Rules) -> Game {
+        Game::new(
+            &["ann", "bob"],
+            Board::classic(10),
+            rules,
+            Dice::scripted(rolls),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn hats_on_3_and_7() {
+        // ann: 3 (hat), 7 (loses it); bob: 1, 7 (nothing to lose)
+        let mut game = game(&[3, 1, 4, 6], Rules::default());
+        for _ in 0..4 {
+            assert_eq!(game.play_turn(), None);
+        }
+        assert_eq!(game.players()[0].position, 7);
+        assert_eq!(game.players()[0].hats, 0);
+        let text: Vec<String> = game.log().iter().map(|e| game.describe(e)).collect();
+        assert_eq!(
+            text,
+            vec![
+                "ann rolls 3",
+                "ann moves from 0 to 3",
+                "ann gets a fancy hat and now has 1",
+                "bob rolls 1",
+                "bob moves from 0 to 1",
+                "ann rolls 4",
+                "ann moves from 3 to 7",
+                "ann loses a fancy hat and now has 0",
+                "bob rolls 6",
+                "bob moves from 1 to 7",
+                "bob has no hat to lose",
+            ]
+        );
+    }
+
+    #[test]
+    fn first_past_the_end_wins() {
+        let mut game = game(&[6, 2, 6], Rules::default());
+        assert_eq!(game.play(), Outcome::Winner(0));
+        assert_eq!(game.players()[0].position, 10);
+        assert_eq!(game.log().last(), Some(&Event::Won { player: 0 }));
+        // a finished game stays finished
+        let length = game.log().len();
+        assert_eq!(game.play_turn(), Some(Outcome::Winner(0)));
+        assert_eq!(game.log().len(), length);
+    }
+
+    #[test]
+    fn exact_end_bounces_back() {
+        let rules = Rules {
+            win: WinCondition::ExactEnd,
+            ..Rules::default()
+        };
+        // ann: 8, then 5 overshoots by 3 and bounces to 7, then 3 wins
+        let mut game = game(&[8, 1, 5, 1, 3], rules);
+        assert_eq!(game.play(), Outcome::Winner(0));
+        assert!(game.log().contains(&Event::Moved {
+            player: 0,
+            from: 8,
+            to: 7
+        }));
+    }
+
+    #[test]
+    fn hat_collecting_and_draws() {
+        let hats = Rules {
+            win: WinCondition::Hats(1),
+            ..Rules::default()
+        };
+        assert_eq!(game(&[1, 3], hats).play(), Outcome::Winner(1));
+
+        let short = Rules {
+            win: WinCondition::Hats(2),
+            max_rounds: 3,
+        };
+        let mut game = game(&[1], short);
+        assert_eq!(game.play(), Outcome::Draw);
+        assert_eq!(game.log().last(), Some(&Event::Draw { rounds: 3 }));
+        assert_eq!(game.players()[0].position, 3);
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let play = |seed| {
+            let dice = Dice::seeded(2, 6, seed);
+            let mut game = Game::new(
+                &["ann", "bob", "cy"],
+                Board::classic(40),
+                Rules::default(),
+                dice,
+            )
+            .unwrap();
+            let outcome = game.play();
+            (outcome, game.log().to_vec())
+        };
+        assert_eq!(play(42), play(42));
+        let (_, log) = play(7);
+        for event in log {
+            if let Event::Rolled { roll, .. } = event {
+                assert!((2..=12).contains(&roll));
+            }
+        }
+    }
+
+    #[test]
+    fn setup_errors() {
+        let board = Board::classic(10);
+        assert_eq!(
+            Game::new(&[], board, Rules::default(), Dice::scripted(&[1])).unwrap_err(),
+            GameError::NoPlayers
+        );
+        assert_eq!(
+            Game::new(
+                &["ann"],
+                Board::new(0),
+                Rules::default(),
+                Dice::scripted(&[1])
+            )
+            .unwrap_err(),
+            GameError::EmptyBoard
+        );
+        // specials off the board are ignored
+        assert_eq!(Board::classic(5).square(7), None);
+    }
+}
@@ -0,0 +1,292 @@
+// https://doc.rust-lang.org/book/ch05-02-example-structs.html
+// https://doc.rust-lang.org/book/ch05-03-method-syntax.html
+// The Rectangle from the structs and writing_tests examples, now with a position.
+// x grows to the right and y grows down, like screen coordinates.
+
+pub mod index;
+pub mod packing;
+pub mod space;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rectangle {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rectangle {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // at the origin, for when only the size matters
+    pub fn with_size(width: u32, height: u32) -> Rectangle {
+        Rectangle::new(0, 0, width, height)
+    }
+
+    pub fn build_square(size: u32) -> Rectangle {
+        Rectangle::with_size(size, size)
+    }
+
+    // Smallest rectangle covering both corners, in any order.
+    pub fn from_corners(a: (i32, i32), b: (i32, i32)) -> Rectangle {
+        let (left, right) = (a.0.min(b.0), a.0.max(b.0));
+        let (top, bottom) = (a.1.min(b.1), a.1.max(b.1));
+        Rectangle::new(left, top, right.abs_diff(left), bottom.abs_diff(top))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // u64 so that u32::MAX x u32::MAX still fits
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    // Edges are i64 because x + width can go past i32::MAX.
+    pub fn left(&self) -> i64 {
+        self.x as i64
+    }
+
+    pub fn top(&self) -> i64 {
+        self.y as i64
+    }
+
+    pub fn right(&self) -> i64 {
+        self.x as i64 + self.width as i64
+    }
+
+    pub fn bottom(&self) -> i64 {
+        self.y as i64 + self.height as i64
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (
+            self.x as f64 + self.width as f64 / 2.0,
+            self.y as f64 + self.height as f64 / 2.0,
+        )
+    }
+
+    // Size only comparison, as in the book: both sides strictly larger, position is ignored.
+    // A rotated rectangle (width and height swapped) also counts.
+    pub fn can_hold(&self, other: &Rectangle) -> bool {
+        let straight = self.width > other.width && self.height > other.height;
+        let rotated = self.width > other.height && self.height > other.width;
+        straight || rotated
+    }
+
+    // Like can_hold but an exact fit counts and there is no rotation, which is what
+    // placing one rectangle inside another needs.
+    pub fn can_fit(&self, other: &Rectangle) -> bool {
+        self.width >= other.width && self.height >= other.height
+    }
+
+    pub fn contains_point(&self, x: i64, y: i64) -> bool {
+        x >= self.left() && x < self.right() && y >= self.top() && y < self.bottom()
+    }
+
+    // other lies completely inside self, touching edges included
+    pub fn contains(&self, other: &Rectangle) -> bool {
+        other.left() >= self.left()
+            && other.top() >= self.top()
+            && other.right() <= self.right()
+            && other.bottom() <= self.bottom()
+    }
+
+    // Rectangles that only share an edge do not overlap.
+    pub fn overlaps(&self, other: &Rectangle) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    pub fn intersection(&self, other: &Rectangle) -> Option<Rectangle> {
+        let left = self.left().max(other.left());
+        let top = self.top().max(other.top());
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if left >= right || top >= bottom {
+            return None;
+        }
+        // left and top come from one of the inputs, so they fit in i32,
+        // and the sizes are at most the smaller input size
+        Some(Rectangle::new(
+            left as i32,
+            top as i32,
+            (right - left) as u32,
+            (bottom - top) as u32,
+        ))
+    }
+
+    pub fn overlap_area(&self, other: &Rectangle) -> u64 {
+        self.intersection(other).map_or(0, |r| r.area())
+    }
+
+    // Bounding box of both rectangles. Sizes saturate at u32::MAX for rectangles that are
+    // further apart than that.
+    pub fn union(&self, other: &Rectangle) -> Rectangle {
+        let left = self.left().min(other.left());
+        let top = self.top().min(other.top());
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+        Rectangle::new(
+            left as i32,
+            top as i32,
+            saturate(right - left),
+            saturate(bottom - top),
+        )
+    }
+
+    // Scales the size and keeps the top-left corner. Results are rounded and saturate
+    // at 0 and u32::MAX.
+    pub fn scale(&self, factor: f64) -> Rectangle {
+        Rectangle {
+            width: (self.width as f64 * factor).round() as u32,
+            height: (self.height as f64 * factor).round() as u32,
+            ..*self
+        }
+    }
+
+    pub fn translate(&self, dx: i32, dy: i32) -> Rectangle {
+        Rectangle {
+            x: self.x.saturating_add(dx),
+            y: self.y.saturating_add(dy),
+            ..*self
+        }
+    }
+
+    // width and height swapped, same top-left corner
+    pub fn rotated(&self) -> Rectangle {
+        Rectangle {
+            width: self.height,
+            height: self.width,
+            ..*self
+        }
+    }
+}
+
+fn saturate(length: i64) -> u32 {
+    length.clamp(0, u32::MAX as i64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn larger_can_hold_smaller() {
+        let larger = Rectangle::with_size(8, 7);
+        let smaller = Rectangle::with_size(5, 1);
+        assert!(larger.can_hold(&smaller));
+        assert!(!smaller.can_hold(&larger));
+    }
+
+    #[test]
+    fn can_hold_rotated() {
+        let wide = Rectangle::with_size(30, 10);
+        let tall = Rectangle::with_size(5, 20);
+        assert!(wide.can_hold(&tall));
+        assert!(!wide.can_hold(&Rectangle::with_size(5, 31)));
+    }
+
+    #[test]
+    fn intersection_and_union() {
+        let a = Rectangle::new(0, 0, 10, 10);
+        let b = Rectangle::new(5, -5, 10, 10);
+        assert_eq!(a.intersection(&b), Some(Rectangle::new(5, 0, 5, 5)));
+        assert_eq!(a.overlap_area(&b), 25);
+        assert_eq!(a.union(&b), Rectangle::new(0, -5, 15, 15));
+
+        let touching = Rectangle::new(10, 0, 5, 5);
+        assert!(!a.overlaps(&touching));
+        assert_eq!(a.overlap_area(&touching), 0);
+    }
+
+    #[test]
+    fn containment() {
+        let outer = Rectangle::new(-10, -10, 20, 20);
+        assert!(outer.contains(&Rectangle::new(-10, -10, 20, 20)));
+        assert!(outer.contains(&Rectangle::new(0, 0, 10, 10)));
+        assert!(!outer.contains(&Rectangle::new(0, 0, 11, 10)));
+        assert!(outer.contains_point(-10, 9));
+        assert!(!outer.contains_point(10, 0));
+    }
+
+    #[test]
+    fn scale_keeps_position() {
+        let r = Rectangle::new(3, 4, 10, 5).scale(1.5);
+        assert_eq!(r, Rectangle::new(3, 4, 15, 8));
+        assert_eq!(Rectangle::build_square(10).scale(-1.0).area(), 0);
+    }
+
+    #[test]
+    fn extreme_coordinates_do_not_overflow() {
+        let far_left = Rectangle::new(i32::MIN, 0, u32::MAX, 1);
+        let far_right = Rectangle::new(i32::MAX, 0, u32::MAX, 1);
+        assert_eq!(far_left.union(&far_right).width, u32::MAX);
+        assert_eq!(far_right.right(), i32::MAX as i64 + u32::MAX as i64);
+    }
+
+    fn rectangle() -> impl Strategy<Value = Rectangle> {
+        (-1000i32..1000, -1000i32..1000, 0u32..500, 0u32..500)
+            .prop_map(|(x, y, width, height)| Rectangle::new(x, y, width, height))
+    }
+
+    proptest! {
+        #[test]
+        fn intersection_is_commutative_and_contained(a in rectangle(), b in rectangle()) {
+            prop_assert_eq!(a.intersection(&b), b.intersection(&a));
+            if let Some(i) = a.intersection(&b) {
+                prop_assert!(a.contains(&i) && b.contains(&i));
+                prop_assert!(i.area() <= a.area().min(b.area()));
+            }
+        }
+
+        #[test]
+        fn union_contains_both(a in rectangle(), b in rectangle()) {
+            let u = a.union(&b);
+            prop_assert!(u.contains(&a) && u.contains(&b));
+            prop_assert_eq!(u, b.union(&a));
+        }
+
+        #[test]
+        fn overlap_area_is_bounded(a in rectangle(), b in rectangle()) {
+            let overlap = a.overlap_area(&b);
+            prop_assert_eq!(overlap, b.overlap_area(&a));
+            prop_assert!(a.area() + b.area() - overlap <= a.union(&b).area());
+        }
+
+        #[test]
+        fn contains_itself(a in rectangle()) {
+            prop_assert!(a.contains(&a));
+            prop_assert_eq!(a.union(&a), a);
+        }
+
+        #[test]
+        fn can_hold_ignores_rotation(a in rectangle(), b in rectangle()) {
+            prop_assert_eq!(a.can_hold(&b), a.can_hold(&b.rotated()));
+            prop_assert!(!(a.can_hold(&b) && b.can_hold(&a)));
+        }
+
+        #[test]
+        fn translate_keeps_size(a in rectangle(), dx in -1000i32..1000, dy in -1000i32..1000) {
+            let moved = a.translate(dx, dy);
+            prop_assert_eq!(moved.area(), a.area());
+            prop_assert_eq!(moved.left(), a.left() + dx as i64);
+        }
+    }
+}
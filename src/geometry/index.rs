@@ -0,0 +1,364 @@
+// A quadtree over positioned rectangles, to answer "what overlaps this region" without
+// checking every rectangle. Each rectangle lives in the smallest node that fully contains it.
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+
+use super::Rectangle;
+
+const DEFAULT_NODE_CAPACITY: usize = 8;
+const DEFAULT_MAX_DEPTH: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ItemId(u64);
+
+#[derive(Debug)]
+struct Node {
+    bounds: Rectangle,
+    depth: usize,
+    items: Vec<(ItemId, Rectangle)>,
+    children: Option<Box<[Node; 4]>>,
+}
+
+impl Node {
+    fn new(bounds: Rectangle, depth: usize) -> Node {
+        Node {
+            bounds,
+            depth,
+            items: Vec::new(),
+            children: None,
+        }
+    }
+
+    fn child_for(&mut self, rect: &Rectangle) -> Option<&mut Node> {
+        self.children
+            .as_mut()
+            .and_then(|children| children.iter_mut().find(|c| c.bounds.contains(rect)))
+    }
+
+    fn insert(&mut self, id: ItemId, rect: Rectangle, capacity: usize, max_depth: usize) {
+        if let Some(child) = self.child_for(&rect) {
+            return child.insert(id, rect, capacity, max_depth);
+        }
+        self.items.push((id, rect));
+        if self.children.is_none() && self.items.len() > capacity {
+            self.split(capacity, max_depth);
+        }
+    }
+
+    fn split(&mut self, capacity: usize, max_depth: usize) {
+        let b = self.bounds;
+        if self.depth >= max_depth || b.width < 2 || b.height < 2 {
+            return;
+        }
+        let (w1, h1) = (b.width / 2, b.height / 2);
+        let (w2, h2) = (b.width - w1, b.height - h1);
+        // a node that runs past i32::MAX may have its middle out there, then it stays whole
+        let (Ok(mid_x), Ok(mid_y)) = (
+            i32::try_from(b.left() + w1 as i64),
+            i32::try_from(b.top() + h1 as i64),
+        ) else {
+            return;
+        };
+        let depth = self.depth + 1;
+        self.children = Some(Box::new([
+            Node::new(Rectangle::new(b.x, b.y, w1, h1), depth),
+            Node::new(Rectangle::new(mid_x, b.y, w2, h1), depth),
+            Node::new(Rectangle::new(b.x, mid_y, w1, h2), depth),
+            Node::new(Rectangle::new(mid_x, mid_y, w2, h2), depth),
+        ]));
+
+        for (id, rect) in std::mem::take(&mut self.items) {
+            self.insert(id, rect, capacity, max_depth);
+        }
+    }
+
+    // the item can only be on the path of nodes that contain its rectangle
+    fn remove(&mut self, id: ItemId, rect: &Rectangle) -> bool {
+        if let Some(pos) = self.items.iter().position(|(item, _)| *item == id) {
+            self.items.swap_remove(pos);
+            return true;
+        }
+        match self.child_for(rect) {
+            Some(child) => child.remove(id, rect),
+            None => false,
+        }
+    }
+
+    fn query(&self, region: &Rectangle, found: &mut Vec<ItemId>) {
+        found.extend(
+            self.items
+                .iter()
+                .filter(|(_, rect)| rect.overlaps(region))
+                .map(|(id, _)| *id),
+        );
+        if let Some(children) = &self.children {
+            for child in children.iter().filter(|c| c.bounds.overlaps(region)) {
+                child.query(region, found);
+            }
+        }
+    }
+
+    fn collisions<'a>(
+        &'a self,
+        ancestors: &mut Vec<&'a (ItemId, Rectangle)>,
+        pairs: &mut Vec<(ItemId, ItemId)>,
+    ) {
+        for (i, a) in self.items.iter().enumerate() {
+            for b in ancestors.iter().copied().chain(&self.items[i + 1..]) {
+                if a.1.overlaps(&b.1) {
+                    pairs.push((a.0.min(b.0), a.0.max(b.0)));
+                }
+            }
+        }
+        if let Some(children) = &self.children {
+            let depth = ancestors.len();
+            ancestors.extend(self.items.iter());
+            for child in children.iter() {
+                child.collisions(ancestors, pairs);
+            }
+            ancestors.truncate(depth);
+        }
+    }
+}
+
+// squared distance from a point to the closest point of the rectangle, 0 when inside
+fn distance_squared(rect: &Rectangle, x: i64, y: i64) -> u128 {
+    let dx = (rect.left() - x).max(x - rect.right()).max(0) as u128;
+    let dy = (rect.top() - y).max(y - rect.bottom()).max(0) as u128;
+    dx * dx + dy * dy
+}
+
+enum Candidate<'a> {
+    Node(&'a Node),
+    Item(ItemId),
+}
+
+// heap entry ordered by distance only
+struct Queued<'a>(Reverse<u128>, Candidate<'a>);
+
+impl PartialEq for Queued<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Queued<'_> {}
+
+impl PartialOrd for Queued<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+#[derive(Debug)]
+pub struct QuadTree {
+    root: Node,
+    rects: HashMap<ItemId, Rectangle>,
+    next_id: u64,
+    capacity: usize,
+    max_depth: usize,
+}
+
+impl QuadTree {
+    // Rectangles outside bounds are still accepted, they just stay in the root node.
+    pub fn new(bounds: Rectangle) -> QuadTree {
+        QuadTree::with_limits(bounds, DEFAULT_NODE_CAPACITY, DEFAULT_MAX_DEPTH)
+    }
+
+    // capacity: items a node holds before splitting, max_depth: how often nodes can split
+    pub fn with_limits(bounds: Rectangle, capacity: usize, max_depth: usize) -> QuadTree {
+        QuadTree {
+            root: Node::new(bounds, 0),
+            rects: HashMap::new(),
+            next_id: 0,
+            capacity: capacity.max(1),
+            max_depth,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    pub fn get(&self, id: ItemId) -> Option<&Rectangle> {
+        self.rects.get(&id)
+    }
+
+    pub fn insert(&mut self, rect: Rectangle) -> ItemId {
+        let id = ItemId(self.next_id);
+        self.next_id += 1;
+        self.rects.insert(id, rect);
+        self.root.insert(id, rect, self.capacity, self.max_depth);
+        id
+    }
+
+    pub fn remove(&mut self, id: ItemId) -> Option<Rectangle> {
+        let rect = self.rects.remove(&id)?;
+        let removed = self.root.remove(id, &rect);
+        debug_assert!(removed, "{:?} was indexed but not found in the tree", id);
+        Some(rect)
+    }
+
+    // Items that overlap region, sharing an edge is not enough. Sorted by id.
+    pub fn query(&self, region: &Rectangle) -> Vec<ItemId> {
+        let mut found = Vec::new();
+        self.root.query(region, &mut found);
+        found.sort_unstable();
+        found
+    }
+
+    // The item closest to the point and its distance, 0.0 when the point is inside it.
+    // Ties go to any of the closest items.
+    pub fn nearest(&self, x: i32, y: i32) -> Option<(ItemId, f64)> {
+        let (x, y) = (x as i64, y as i64);
+        let mut heap = BinaryHeap::new();
+        // the root may hold items outside its bounds, so it is always visited first
+        heap.push(Queued(Reverse(0), Candidate::Node(&self.root)));
+        while let Some(Queued(Reverse(distance), candidate)) = heap.pop() {
+            match candidate {
+                Candidate::Item(id) => return Some((id, (distance as f64).sqrt())),
+                Candidate::Node(node) => {
+                    for (id, rect) in &node.items {
+                        heap.push(Queued(
+                            Reverse(distance_squared(rect, x, y)),
+                            Candidate::Item(*id),
+                        ));
+                    }
+                    if let Some(children) = &node.children {
+                        for child in children.iter() {
+                            heap.push(Queued(
+                                Reverse(distance_squared(&child.bounds, x, y)),
+                                Candidate::Node(child),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // Every pair of overlapping items once, as (smaller id, larger id), sorted.
+    pub fn collisions(&self) -> Vec<(ItemId, ItemId)> {
+        let mut pairs = Vec::new();
+        self.root.collisions(&mut Vec::new(), &mut pairs);
+        pairs.sort_unstable();
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn naive_collisions(tree: &QuadTree) -> Vec<(ItemId, ItemId)> {
+        let mut items: Vec<_> = tree.rects.iter().collect();
+        items.sort_by_key(|(id, _)| **id);
+        let mut pairs = Vec::new();
+        for (i, (a, ra)) in items.iter().enumerate() {
+            for (b, rb) in &items[i + 1..] {
+                if ra.overlaps(rb) {
+                    pairs.push((**a, **b));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn query_finds_overlapping_items() {
+        let mut tree = QuadTree::with_limits(Rectangle::with_size(100, 100), 2, 6);
+        let a = tree.insert(Rectangle::new(0, 0, 10, 10));
+        let b = tree.insert(Rectangle::new(5, 5, 10, 10));
+        let c = tree.insert(Rectangle::new(80, 80, 5, 5));
+        let d = tree.insert(Rectangle::new(40, 40, 30, 30)); // crosses the middle
+        let outside = tree.insert(Rectangle::new(-50, -50, 20, 20));
+
+        assert_eq!(tree.query(&Rectangle::new(0, 0, 6, 6)), vec![a, b]);
+        assert_eq!(tree.query(&Rectangle::new(60, 60, 30, 30)), vec![c, d]);
+        assert_eq!(tree.query(&Rectangle::new(-40, -40, 1, 1)), vec![outside]);
+        assert_eq!(tree.query(&Rectangle::new(10, 5, 1, 1)), vec![b]);
+        assert_eq!(tree.collisions(), vec![(a, b)]);
+    }
+
+    #[test]
+    fn nodes_past_i32_max_stay_whole() {
+        let near = i32::MAX - 1;
+        let mut tree = QuadTree::with_limits(Rectangle::new(near, near, 8, 8), 1, 6);
+        let ids: Vec<_> = (0..2)
+            .map(|i| tree.insert(Rectangle::new(near - i, near, 1, 1)))
+            .collect();
+        assert!(tree.root.children.is_none());
+        assert_eq!(tree.query(&Rectangle::new(near, near, 1, 1)), vec![ids[0]]);
+        assert_eq!(
+            tree.query(&Rectangle::new(near - 1, near, 1, 1)),
+            vec![ids[1]]
+        );
+    }
+
+    #[test]
+    fn removed_items_are_gone() {
+        let mut tree = QuadTree::with_limits(Rectangle::with_size(64, 64), 1, 6);
+        let ids: Vec<_> = (0..16)
+            .map(|i| tree.insert(Rectangle::new(i * 4, i * 4, 3, 3)))
+            .collect();
+        assert_eq!(tree.remove(ids[3]), Some(Rectangle::new(12, 12, 3, 3)));
+        assert_eq!(tree.remove(ids[3]), None);
+        assert_eq!(tree.len(), 15);
+        assert!(tree.query(&Rectangle::new(12, 12, 3, 3)).is_empty());
+    }
+
+    #[test]
+    fn nearest_item() {
+        let mut tree = QuadTree::with_limits(Rectangle::with_size(100, 100), 1, 6);
+        assert_eq!(tree.nearest(0, 0), None);
+        let far = tree.insert(Rectangle::new(90, 90, 5, 5));
+        let near = tree.insert(Rectangle::new(10, 10, 5, 5));
+        let _other = tree.insert(Rectangle::new(50, 0, 5, 5));
+
+        assert_eq!(tree.nearest(12, 12), Some((near, 0.0)));
+        assert_eq!(tree.nearest(0, 10), Some((near, 10.0)));
+        assert_eq!(tree.nearest(98, 99), Some((far, 5.0)));
+    }
+
+    fn rectangles() -> impl proptest::strategy::Strategy<Value = Vec<Rectangle>> {
+        proptest::collection::vec(
+            (-20i32..120, -20i32..120, 0u32..30, 0u32..30)
+                .prop_map(|(x, y, w, h)| Rectangle::new(x, y, w, h)),
+            0..80,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn matches_brute_force(rects in rectangles(), region in (0i32..100, 0i32..100, 1u32..50, 1u32..50), removed in 0usize..10) {
+            let mut tree = QuadTree::with_limits(Rectangle::with_size(100, 100), 2, 8);
+            let ids: Vec<_> = rects.iter().map(|r| tree.insert(*r)).collect();
+            for id in ids.iter().step_by(removed + 1) {
+                tree.remove(*id);
+            }
+
+            prop_assert_eq!(tree.collisions(), naive_collisions(&tree));
+
+            let region = Rectangle::new(region.0, region.1, region.2, region.3);
+            let mut expected: Vec<_> = tree.rects.iter().filter(|(_, r)| r.overlaps(&region)).map(|(id, _)| *id).collect();
+            expected.sort();
+            prop_assert_eq!(tree.query(&region), expected);
+
+            let best = tree.rects.values().map(|r| distance_squared(r, 50, 50)).min();
+            let nearest = tree.nearest(50, 50).map(|(id, _)| distance_squared(&tree.rects[&id], 50, 50));
+            prop_assert_eq!(nearest, best);
+        }
+    }
+}
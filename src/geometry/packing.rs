@@ -0,0 +1,502 @@
+// Places many item rectangles inside one container, like laying out widgets on a screen or
+// jobs on a print sheet. Only the item sizes are used, their x and y are ignored.
+use super::Rectangle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    // rows of items, each row as tall as its tallest item
+    Shelf,
+    // every placement cuts the free space it used into two smaller free rectangles
+    Guillotine,
+    // keeps all maximal free rectangles, usually the tightest of the three
+    #[default]
+    MaxRects,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PackOptions {
+    pub strategy: Strategy,
+    // lets items be turned 90 degrees when that helps them fit
+    pub allow_rotation: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    // index into the items slice
+    pub item: usize,
+    // where the item ended up, in the container coordinates
+    pub rect: Rectangle,
+    pub rotated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packing {
+    pub container: Rectangle,
+    pub placements: Vec<Placement>,
+    // indices of the items that did not fit, in input order
+    pub unplaced: Vec<usize>,
+    // indices of the items with no width or no height, in input order. They cover nothing
+    // so they are not placed, and they do not make the packing incomplete.
+    pub empty: Vec<usize>,
+}
+
+impl Packing {
+    pub fn used_area(&self) -> u64 {
+        self.placements.iter().map(|p| p.rect.area()).sum()
+    }
+
+    // share of the container covered by placed items, from 0.0 to 1.0
+    pub fn utilization(&self) -> f64 {
+        if self.container.is_empty() {
+            return 0.0;
+        }
+        self.used_area() as f64 / self.container.area() as f64
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.unplaced.is_empty()
+    }
+}
+
+pub fn pack(container: &Rectangle, items: &[Rectangle], options: PackOptions) -> Packing {
+    let mut packer: Box<dyn Packer> = match options.strategy {
+        Strategy::Shelf => Box::new(ShelfPacker::new(*container)),
+        Strategy::Guillotine => Box::new(GuillotinePacker::new(*container)),
+        Strategy::MaxRects => Box::new(MaxRectsPacker::new(*container)),
+    };
+
+    // Big items first leaves the small ones to fill the gaps.
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    match options.strategy {
+        Strategy::Shelf => order.sort_by_key(|&i| {
+            std::cmp::Reverse(if options.allow_rotation {
+                items[i].width.min(items[i].height)
+            } else {
+                items[i].height
+            })
+        }),
+        _ => order.sort_by_key(|&i| std::cmp::Reverse(items[i].area())),
+    }
+
+    let mut placements = Vec::new();
+    let mut unplaced = Vec::new();
+    let mut empty = Vec::new();
+    for i in order {
+        let item = items[i];
+        if item.is_empty() {
+            empty.push(i);
+            continue;
+        }
+        match packer.place(item.width, item.height, options.allow_rotation) {
+            Some((rect, rotated)) => placements.push(Placement {
+                item: i,
+                rect,
+                rotated,
+            }),
+            None => unplaced.push(i),
+        }
+    }
+    unplaced.sort_unstable();
+    empty.sort_unstable();
+
+    Packing {
+        container: *container,
+        placements,
+        unplaced,
+        empty,
+    }
+}
+
+// Rectangle lets an edge run past i32::MAX but not a position, so space that starts out
+// there is out of reach and nothing is placed in it.
+trait Packer {
+    fn place(&mut self, width: u32, height: u32, allow_rotation: bool)
+        -> Option<(Rectangle, bool)>;
+}
+
+// the sizes to try for an item, the original one first
+fn orientations(width: u32, height: u32, allow_rotation: bool) -> Vec<(u32, u32, bool)> {
+    let mut sizes = vec![(width, height, false)];
+    if allow_rotation && width != height {
+        sizes.push((height, width, true));
+    }
+    sizes
+}
+
+struct Shelf {
+    y: i64,
+    height: u32,
+    used_width: u32,
+}
+
+struct ShelfPacker {
+    container: Rectangle,
+    shelves: Vec<Shelf>,
+}
+
+impl ShelfPacker {
+    fn new(container: Rectangle) -> ShelfPacker {
+        ShelfPacker {
+            container,
+            shelves: Vec::new(),
+        }
+    }
+
+    fn free_height(&self) -> i64 {
+        let used: i64 = self.shelves.iter().map(|s| s.height as i64).sum();
+        self.container.height as i64 - used
+    }
+}
+
+impl Packer for ShelfPacker {
+    fn place(
+        &mut self,
+        width: u32,
+        height: u32,
+        allow_rotation: bool,
+    ) -> Option<(Rectangle, bool)> {
+        let sizes = orientations(width, height, allow_rotation);
+
+        // first shelf with room, trying the flattest orientation first to waste less height
+        let mut flattest = sizes.clone();
+        flattest.sort_by_key(|&(_, h, _)| h);
+        for shelf in self.shelves.iter_mut() {
+            for &(w, h, rotated) in &flattest {
+                if h <= shelf.height
+                    && shelf.used_width as u64 + w as u64 <= self.container.width as u64
+                {
+                    let Ok(x) = i32::try_from(self.container.left() + shelf.used_width as i64)
+                    else {
+                        continue;
+                    };
+                    let Ok(y) = i32::try_from(shelf.y) else {
+                        continue;
+                    };
+                    let rect = Rectangle::new(x, y, w, h);
+                    shelf.used_width += w;
+                    return Some((rect, rotated));
+                }
+            }
+        }
+
+        // new shelf, as low as possible
+        let free_height = self.free_height();
+        let y = self.container.bottom() - free_height;
+        let top = i32::try_from(y).ok()?;
+        for &(w, h, rotated) in &flattest {
+            if w <= self.container.width && h as i64 <= free_height {
+                self.shelves.push(Shelf {
+                    y,
+                    height: h,
+                    used_width: w,
+                });
+                return Some((Rectangle::new(self.container.x, top, w, h), rotated));
+            }
+        }
+        None
+    }
+}
+
+struct GuillotinePacker {
+    free: Vec<Rectangle>,
+}
+
+impl GuillotinePacker {
+    fn new(container: Rectangle) -> GuillotinePacker {
+        GuillotinePacker {
+            free: vec![container],
+        }
+    }
+}
+
+impl Packer for GuillotinePacker {
+    fn place(
+        &mut self,
+        width: u32,
+        height: u32,
+        allow_rotation: bool,
+    ) -> Option<(Rectangle, bool)> {
+        // best area fit: the free rectangle with the least area left over
+        let mut best: Option<(usize, u32, u32, bool, u64)> = None;
+        for (i, free) in self.free.iter().enumerate() {
+            for (w, h, rotated) in orientations(width, height, allow_rotation) {
+                if free.can_fit(&Rectangle::with_size(w, h)) {
+                    let waste = free.area() - Rectangle::with_size(w, h).area();
+                    if !matches!(best, Some((.., best_waste)) if best_waste <= waste) {
+                        best = Some((i, w, h, rotated, waste));
+                    }
+                }
+            }
+        }
+        let (i, w, h, rotated, _) = best?;
+        let free = self.free.swap_remove(i);
+        let placed = Rectangle::new(free.x, free.y, w, h);
+
+        // split along the shorter leftover side, so the bigger piece stays as large as possible
+        let leftover_width = free.width - w;
+        let leftover_height = free.height - h;
+        let (right_height, below_width) = if leftover_width < leftover_height {
+            (h, free.width)
+        } else {
+            (free.height, w)
+        };
+        let right = i32::try_from(placed.right())
+            .ok()
+            .map(|x| Rectangle::new(x, free.y, leftover_width, right_height));
+        let below = i32::try_from(placed.bottom())
+            .ok()
+            .map(|y| Rectangle::new(free.x, y, below_width, leftover_height));
+        self.free.extend(
+            [right, below]
+                .into_iter()
+                .flatten()
+                .filter(|r| !r.is_empty()),
+        );
+        Some((placed, rotated))
+    }
+}
+
+struct MaxRectsPacker {
+    free: Vec<Rectangle>,
+}
+
+impl MaxRectsPacker {
+    fn new(container: Rectangle) -> MaxRectsPacker {
+        MaxRectsPacker {
+            free: vec![container],
+        }
+    }
+
+    // the parts of free that are not covered by used, each as large as possible
+    fn split(free: &Rectangle, used: &Rectangle) -> Vec<Rectangle> {
+        let mut parts = Vec::with_capacity(4);
+        if used.left() > free.left() {
+            parts.push(Rectangle::new(
+                free.x,
+                free.y,
+                (used.left() - free.left()) as u32,
+                free.height,
+            ));
+        }
+        if let (true, Ok(x)) = (used.right() < free.right(), i32::try_from(used.right())) {
+            parts.push(Rectangle::new(
+                x,
+                free.y,
+                (free.right() - used.right()) as u32,
+                free.height,
+            ));
+        }
+        if used.top() > free.top() {
+            parts.push(Rectangle::new(
+                free.x,
+                free.y,
+                free.width,
+                (used.top() - free.top()) as u32,
+            ));
+        }
+        if let (true, Ok(y)) = (used.bottom() < free.bottom(), i32::try_from(used.bottom())) {
+            parts.push(Rectangle::new(
+                free.x,
+                y,
+                free.width,
+                (free.bottom() - used.bottom()) as u32,
+            ));
+        }
+        parts
+    }
+
+    fn prune(&mut self) {
+        let mut kept: Vec<Rectangle> = Vec::with_capacity(self.free.len());
+        for (i, rect) in self.free.iter().enumerate() {
+            let redundant = self.free.iter().enumerate().any(|(j, other)| {
+                // of two equal rectangles only the first one is kept
+                i != j && other.contains(rect) && (other != rect || j < i)
+            });
+            if !redundant {
+                kept.push(*rect);
+            }
+        }
+        self.free = kept;
+    }
+}
+
+impl Packer for MaxRectsPacker {
+    fn place(
+        &mut self,
+        width: u32,
+        height: u32,
+        allow_rotation: bool,
+    ) -> Option<(Rectangle, bool)> {
+        // best short side fit: the smallest leftover along the tighter side, then the other side
+        let mut best: Option<(Rectangle, bool, (u32, u32))> = None;
+        for free in &self.free {
+            for (w, h, rotated) in orientations(width, height, allow_rotation) {
+                if !free.can_fit(&Rectangle::with_size(w, h)) {
+                    continue;
+                }
+                let (dw, dh) = (free.width - w, free.height - h);
+                let score = (dw.min(dh), dw.max(dh));
+                if !matches!(best, Some((.., best_score)) if best_score <= score) {
+                    best = Some((Rectangle::new(free.x, free.y, w, h), rotated, score));
+                }
+            }
+        }
+        let (placed, rotated, _) = best?;
+
+        let mut free = Vec::with_capacity(self.free.len() + 4);
+        for rect in &self.free {
+            if rect.overlaps(&placed) {
+                free.extend(MaxRectsPacker::split(rect, &placed));
+            } else {
+                free.push(*rect);
+            }
+        }
+        self.free = free;
+        self.prune();
+        Some((placed, rotated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Strategy; // proptest's prelude has a Strategy too
+    use super::*;
+    use proptest::prelude::*;
+
+    const STRATEGIES: [Strategy; 3] = [Strategy::Shelf, Strategy::Guillotine, Strategy::MaxRects];
+
+    fn options(strategy: Strategy, allow_rotation: bool) -> PackOptions {
+        PackOptions {
+            strategy,
+            allow_rotation,
+        }
+    }
+
+    fn assert_valid(packing: &Packing, items: &[Rectangle]) {
+        for (i, p) in packing.placements.iter().enumerate() {
+            assert!(packing.container.contains(&p.rect), "{:?} outside", p);
+            let item = items[p.item];
+            let size = if p.rotated {
+                (item.height, item.width)
+            } else {
+                (item.width, item.height)
+            };
+            assert_eq!((p.rect.width, p.rect.height), size);
+            for q in &packing.placements[i + 1..] {
+                assert!(!p.rect.overlaps(&q.rect), "{:?} overlaps {:?}", p, q);
+            }
+        }
+        assert_eq!(
+            packing.placements.len() + packing.unplaced.len() + packing.empty.len(),
+            items.len()
+        );
+    }
+
+    #[test]
+    fn exact_fit_fills_the_container() {
+        let container = Rectangle::new(10, 20, 4, 4);
+        let items = vec![Rectangle::build_square(2); 4];
+        for strategy in STRATEGIES {
+            let packing = pack(&container, &items, options(strategy, false));
+            assert_valid(&packing, &items);
+            assert!(packing.is_complete(), "{:?}", strategy);
+            assert_eq!(packing.utilization(), 1.0);
+        }
+    }
+
+    #[test]
+    fn items_that_do_not_fit_are_reported() {
+        let container = Rectangle::with_size(10, 10);
+        let items = vec![
+            Rectangle::with_size(6, 6),
+            Rectangle::with_size(11, 1),
+            Rectangle::with_size(6, 6),
+            Rectangle::with_size(4, 4),
+        ];
+        for strategy in STRATEGIES {
+            let packing = pack(&container, &items, options(strategy, true));
+            assert_valid(&packing, &items);
+            assert!(packing.unplaced.contains(&1));
+            assert!(packing.unplaced.contains(&0) || packing.unplaced.contains(&2));
+        }
+    }
+
+    #[test]
+    fn empty_items_are_reported_apart() {
+        let container = Rectangle::with_size(10, 10);
+        let items = vec![
+            Rectangle::with_size(0, 5),
+            Rectangle::with_size(5, 5),
+            Rectangle::with_size(5, 0),
+            Rectangle::with_size(11, 11),
+        ];
+        for strategy in STRATEGIES {
+            let packing = pack(&container, &items, options(strategy, false));
+            assert_valid(&packing, &items);
+            assert_eq!(packing.empty, [0, 2]);
+            assert_eq!(packing.unplaced, [3]);
+        }
+        let packing = pack(&container, &items[..3], PackOptions::default());
+        assert!(packing.is_complete());
+    }
+
+    #[test]
+    fn rotation_makes_items_fit() {
+        let container = Rectangle::with_size(10, 3);
+        let items = vec![Rectangle::with_size(3, 10)];
+        for strategy in STRATEGIES {
+            assert_eq!(
+                pack(&container, &items, options(strategy, false)).unplaced,
+                vec![0]
+            );
+            let packing = pack(&container, &items, options(strategy, true));
+            assert!(packing.is_complete());
+            assert!(packing.placements[0].rotated);
+        }
+    }
+
+    #[test]
+    fn maxrects_packs_mixed_sizes() {
+        let container = Rectangle::with_size(10, 10);
+        let items = vec![
+            Rectangle::with_size(10, 5),
+            Rectangle::with_size(5, 5),
+            Rectangle::with_size(5, 3),
+            Rectangle::with_size(5, 2),
+        ];
+        let packing = pack(&container, &items, PackOptions::default());
+        assert_valid(&packing, &items);
+        assert!(packing.is_complete());
+    }
+
+    #[test]
+    fn nothing_is_placed_past_i32_max() {
+        // the container runs 89 past i32::MAX, where no item can start
+        let container = Rectangle::new(i32::MAX - 10, i32::MAX - 10, 100, 100);
+        let items = vec![Rectangle::build_square(50); 4];
+        for strategy in STRATEGIES {
+            let packing = pack(&container, &items, options(strategy, false));
+            assert_valid(&packing, &items);
+            assert_eq!(packing.unplaced, [1, 2, 3], "{:?}", strategy);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn placements_never_overlap(
+            sizes in proptest::collection::vec((1u32..20, 1u32..20), 0..40),
+            strategy in prop_oneof![
+                Just(Strategy::Shelf),
+                Just(Strategy::Guillotine),
+                Just(Strategy::MaxRects)
+            ],
+            allow_rotation in any::<bool>(),
+        ) {
+            let container = Rectangle::new(-5, 7, 50, 40);
+            let items: Vec<_> = sizes.iter().map(|&(w, h)| Rectangle::with_size(w, h)).collect();
+            let packing = pack(&container, &items, options(strategy, allow_rotation));
+            assert_valid(&packing, &items);
+            prop_assert!(packing.utilization() <= 1.0);
+        }
+    }
+}
@@ -0,0 +1,470 @@
+// https://doc.rust-lang.org/book/ch19-03-advanced-traits.html#default-generic-type-parameters-and-operator-overloading
+// 3D points and vectors for the `Point(i16, i16, i16)` tuple struct, generic over the coordinate type.
+// A point is a position and a vector is a displacement: point - point = vector, point + vector = point.
+use std::fmt::Debug;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use super::Rectangle;
+
+// The number types a coordinate can have.
+pub trait Scalar:
+    Copy + Debug + PartialOrd + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self>
+{
+    fn zero() -> Self;
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_scalar {
+    ($($t:ty),*) => {
+        $(impl Scalar for $t {
+            fn zero() -> Self {
+                0 as $t
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+        })*
+    };
+}
+
+impl_scalar!(i16, i32, i64, f32, f64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Point3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Vector3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+impl<T: Scalar> Point3<T> {
+    pub fn new(x: T, y: T, z: T) -> Point3<T> {
+        Point3 { x, y, z }
+    }
+
+    pub fn origin() -> Point3<T> {
+        Point3::new(T::zero(), T::zero(), T::zero())
+    }
+
+    // Each coordinate goes to f64 before subtracting, the difference of two integer
+    // points may not fit their type.
+    pub fn distance(&self, other: &Point3<T>) -> f64 {
+        let (a, b) = (self.to_f64(), other.to_f64());
+        (b - a).length()
+    }
+
+    pub fn to_f64(self) -> Point3<f64> {
+        Point3::new(self.x.to_f64(), self.y.to_f64(), self.z.to_f64())
+    }
+
+    // the vector from the origin to this point
+    pub fn to_vector(self) -> Vector3<T> {
+        Vector3::new(self.x, self.y, self.z)
+    }
+}
+
+impl<T: Scalar> Vector3<T> {
+    pub fn new(x: T, y: T, z: T) -> Vector3<T> {
+        Vector3 { x, y, z }
+    }
+
+    pub fn zero() -> Vector3<T> {
+        Vector3::new(T::zero(), T::zero(), T::zero())
+    }
+
+    pub fn dot(&self, other: &Vector3<T>) -> T {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: &Vector3<T>) -> Vector3<T> {
+        Vector3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    // Computed in f64, so that integer vectors do not overflow on the way.
+    pub fn length(&self) -> f64 {
+        let (x, y, z) = (self.x.to_f64(), self.y.to_f64(), self.z.to_f64());
+        (x * x + y * y + z * z).sqrt()
+    }
+
+    // Same direction with length 1, or None for the zero vector which has no direction.
+    pub fn normalize(&self) -> Option<Vector3<f64>> {
+        let length = self.length();
+        if length == 0.0 {
+            return None;
+        }
+        Some(Vector3::new(
+            self.x.to_f64() / length,
+            self.y.to_f64() / length,
+            self.z.to_f64() / length,
+        ))
+    }
+
+    pub fn to_f64(self) -> Vector3<f64> {
+        Vector3::new(self.x.to_f64(), self.y.to_f64(), self.z.to_f64())
+    }
+}
+
+impl<T: Scalar> From<(T, T, T)> for Point3<T> {
+    fn from((x, y, z): (T, T, T)) -> Self {
+        Point3::new(x, y, z)
+    }
+}
+
+impl<T: Scalar> From<(T, T, T)> for Vector3<T> {
+    fn from((x, y, z): (T, T, T)) -> Self {
+        Vector3::new(x, y, z)
+    }
+}
+
+impl<T: Scalar> Sub for Point3<T> {
+    type Output = Vector3<T>;
+
+    fn sub(self, other: Point3<T>) -> Vector3<T> {
+        Vector3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl<T: Scalar> Add<Vector3<T>> for Point3<T> {
+    type Output = Point3<T>;
+
+    fn add(self, v: Vector3<T>) -> Point3<T> {
+        Point3::new(self.x + v.x, self.y + v.y, self.z + v.z)
+    }
+}
+
+impl<T: Scalar> Sub<Vector3<T>> for Point3<T> {
+    type Output = Point3<T>;
+
+    fn sub(self, v: Vector3<T>) -> Point3<T> {
+        Point3::new(self.x - v.x, self.y - v.y, self.z - v.z)
+    }
+}
+
+impl<T: Scalar> AddAssign<Vector3<T>> for Point3<T> {
+    fn add_assign(&mut self, v: Vector3<T>) {
+        *self = *self + v;
+    }
+}
+
+impl<T: Scalar> SubAssign<Vector3<T>> for Point3<T> {
+    fn sub_assign(&mut self, v: Vector3<T>) {
+        *self = *self - v;
+    }
+}
+
+impl<T: Scalar> Add for Vector3<T> {
+    type Output = Vector3<T>;
+
+    fn add(self, other: Vector3<T>) -> Vector3<T> {
+        Vector3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl<T: Scalar> Sub for Vector3<T> {
+    type Output = Vector3<T>;
+
+    fn sub(self, other: Vector3<T>) -> Vector3<T> {
+        Vector3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl<T: Scalar> AddAssign for Vector3<T> {
+    fn add_assign(&mut self, other: Vector3<T>) {
+        *self = *self + other;
+    }
+}
+
+impl<T: Scalar> SubAssign for Vector3<T> {
+    fn sub_assign(&mut self, other: Vector3<T>) {
+        *self = *self - other;
+    }
+}
+
+// vector * scalar
+impl<T: Scalar> Mul<T> for Vector3<T> {
+    type Output = Vector3<T>;
+
+    fn mul(self, k: T) -> Vector3<T> {
+        Vector3::new(self.x * k, self.y * k, self.z * k)
+    }
+}
+
+impl<T: Scalar + Neg<Output = T>> Neg for Vector3<T> {
+    type Output = Vector3<T>;
+
+    fn neg(self) -> Vector3<T> {
+        Vector3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+// Axis-aligned bounding box, min and max corners included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Aabb<T> {
+    pub min: Point3<T>,
+    pub max: Point3<T>,
+}
+
+fn min<T: Scalar>(a: T, b: T) -> T {
+    if b < a {
+        b
+    } else {
+        a
+    }
+}
+
+fn max<T: Scalar>(a: T, b: T) -> T {
+    if b > a {
+        b
+    } else {
+        a
+    }
+}
+
+impl<T: Scalar> Aabb<T> {
+    // the corners can be given in any order
+    pub fn new(a: Point3<T>, b: Point3<T>) -> Aabb<T> {
+        Aabb {
+            min: Point3::new(min(a.x, b.x), min(a.y, b.y), min(a.z, b.z)),
+            max: Point3::new(max(a.x, b.x), max(a.y, b.y), max(a.z, b.z)),
+        }
+    }
+
+    // Smallest box around all the points, None when there are none.
+    pub fn from_points(points: &[Point3<T>]) -> Option<Aabb<T>> {
+        let (first, rest) = points.split_first()?;
+        Some(rest.iter().fold(Aabb::new(*first, *first), |aabb, p| {
+            aabb.union(&Aabb::new(*p, *p))
+        }))
+    }
+
+    // Subtracts in T, so like any integer subtraction it overflows for a box wider than
+    // T can count, e.g. from i16::MIN to i16::MAX. volume and footprint do not.
+    pub fn size(&self) -> Vector3<T> {
+        self.max - self.min
+    }
+
+    pub fn volume(&self) -> f64 {
+        let size = self.max.to_f64() - self.min.to_f64();
+        size.x * size.y * size.z
+    }
+
+    pub fn center(&self) -> Point3<f64> {
+        Point3::new(
+            (self.min.x.to_f64() + self.max.x.to_f64()) / 2.0,
+            (self.min.y.to_f64() + self.max.y.to_f64()) / 2.0,
+            (self.min.z.to_f64() + self.max.z.to_f64()) / 2.0,
+        )
+    }
+
+    pub fn contains_point(&self, p: &Point3<T>) -> bool {
+        p.x >= self.min.x
+            && p.x <= self.max.x
+            && p.y >= self.min.y
+            && p.y <= self.max.y
+            && p.z >= self.min.z
+            && p.z <= self.max.z
+    }
+
+    // Touching faces count as intersecting, since both corners are part of the box.
+    // Use overlaps for the Rectangle rule.
+    pub fn intersects(&self, other: &Aabb<T>) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+            && self.min.z <= other.max.z
+            && other.min.z <= self.max.z
+    }
+
+    // The boxes share some volume. As with Rectangle::overlaps, boxes that only share a face
+    // do not overlap, and a flat box overlaps nothing.
+    pub fn overlaps(&self, other: &Aabb<T>) -> bool {
+        max(self.min.x, other.min.x) < min(self.max.x, other.max.x)
+            && max(self.min.y, other.min.y) < min(self.max.y, other.max.y)
+            && max(self.min.z, other.min.z) < min(self.max.z, other.max.z)
+    }
+
+    pub fn union(&self, other: &Aabb<T>) -> Aabb<T> {
+        Aabb {
+            min: Point3::new(
+                min(self.min.x, other.min.x),
+                min(self.min.y, other.min.y),
+                min(self.min.z, other.min.z),
+            ),
+            max: Point3::new(
+                max(self.max.x, other.max.x),
+                max(self.max.y, other.max.y),
+                max(self.max.z, other.max.z),
+            ),
+        }
+    }
+
+    pub fn translate(&self, v: Vector3<T>) -> Aabb<T> {
+        Aabb {
+            min: self.min + v,
+            max: self.max + v,
+        }
+    }
+}
+
+// Interop with the 2D Rectangle, which lies on the z = 0 plane. i64 holds every rectangle edge.
+impl Aabb<i64> {
+    // The rectangle pushed along z, from z to z + depth. None when that passes i64::MAX.
+    pub fn extrude(rect: &Rectangle, z: i64, depth: u32) -> Option<Aabb<i64>> {
+        Some(Aabb {
+            min: Point3::new(rect.left(), rect.top(), z),
+            max: Point3::new(rect.right(), rect.bottom(), z.checked_add(depth.into())?),
+        })
+    }
+
+    // The shadow of the box on the xy plane, None when it does not fit the Rectangle types.
+    pub fn footprint(&self) -> Option<Rectangle> {
+        Some(Rectangle::new(
+            i32::try_from(self.min.x).ok()?,
+            i32::try_from(self.min.y).ok()?,
+            u32::try_from(self.max.x.checked_sub(self.min.x)?).ok()?,
+            u32::try_from(self.max.y.checked_sub(self.min.y)?).ok()?,
+        ))
+    }
+}
+
+impl From<Rectangle> for Aabb<i64> {
+    // a flat box with no depth
+    fn from(rect: Rectangle) -> Self {
+        Aabb {
+            min: Point3::new(rect.left(), rect.top(), 0),
+            max: Point3::new(rect.right(), rect.bottom(), 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators() {
+        let a = Point3::new(1, 2, 3);
+        let b = Point3::new(4, 6, 3);
+        let v = b - a;
+        assert_eq!(v, Vector3::new(3, 4, 0));
+        assert_eq!(a + v, b);
+        assert_eq!(b - v, a);
+        assert_eq!(v * 2, Vector3::new(6, 8, 0));
+        assert_eq!(-v, Vector3::new(-3, -4, 0));
+        assert_eq!(v + v - v, v);
+
+        let mut p = Point3::<i16>::origin();
+        p += Vector3::new(1, 1, 1);
+        p -= Vector3::new(0, 1, 0);
+        assert_eq!(p, Point3::from((1, 0, 1)));
+    }
+
+    #[test]
+    fn products_and_lengths() {
+        let x = Vector3::new(1.0, 0.0, 0.0);
+        let y = Vector3::new(0.0, 1.0, 0.0);
+        assert_eq!(x.dot(&y), 0.0);
+        assert_eq!(x.cross(&y), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(y.cross(&x), Vector3::new(0.0, 0.0, -1.0));
+
+        let v = Vector3::new(3, 4, 12);
+        assert_eq!(v.dot(&v), 169);
+        assert_eq!(v.length(), 13.0);
+        assert_eq!(Point3::new(1, 1, 1).distance(&Point3::new(4, 5, 13)), 13.0);
+
+        let unit = v.normalize().unwrap();
+        assert!((unit.length() - 1.0).abs() < 1e-12);
+        assert_eq!(Vector3::<i32>::zero().normalize(), None);
+
+        // i16 coordinates do not overflow when measuring
+        let far = Vector3::new(i16::MAX, i16::MAX, i16::MAX);
+        assert!((far.length() - 32767.0 * 3f64.sqrt()).abs() < 1e-6);
+        // nor when the points are further apart than i16 can count
+        let (low, high) = (Point3::new(i16::MIN, 0, 0), Point3::new(i16::MAX, 0, 0));
+        assert_eq!(low.distance(&high), 65535.0);
+    }
+
+    #[test]
+    fn bounding_boxes() {
+        let points = [
+            Point3::new(1, 5, -2),
+            Point3::new(-3, 2, 4),
+            Point3::new(0, 0, 0),
+        ];
+        let aabb = Aabb::from_points(&points).unwrap();
+        assert_eq!(aabb.min, Point3::new(-3, 0, -2));
+        assert_eq!(aabb.max, Point3::new(1, 5, 4));
+        assert_eq!(aabb.volume(), 4.0 * 5.0 * 6.0);
+        assert!(points.iter().all(|p| aabb.contains_point(p)));
+        assert_eq!(Aabb::<i32>::from_points(&[]), None);
+
+        let moved = aabb.translate(Vector3::new(4, 0, 0));
+        assert!(aabb.intersects(&moved));
+        assert!(!aabb.intersects(&moved.translate(Vector3::new(1, 0, 0))));
+        assert_eq!(aabb.union(&moved).size(), Vector3::new(8, 5, 6));
+    }
+
+    #[test]
+    fn rectangles_become_boxes_and_back() {
+        let rect = Rectangle::new(-5, 10, 20, 30);
+        let slab = Aabb::extrude(&rect, 2, 8).unwrap();
+        assert_eq!(slab.min, Point3::new(-5, 10, 2));
+        assert_eq!(slab.max, Point3::new(15, 40, 10));
+        assert_eq!(slab.footprint(), Some(rect));
+        assert_eq!(slab.volume(), rect.area() as f64 * 8.0);
+        assert_eq!(Aabb::from(rect).footprint(), Some(rect));
+
+        let huge = Aabb::new(Point3::new(0, 0, 0), Point3::new(i64::MAX, 1, 1));
+        assert_eq!(huge.footprint(), None);
+        let wider_than_i64 = Aabb::new(Point3::new(-1, 0, 0), Point3::new(i64::MAX, 0, 0));
+        assert_eq!(wider_than_i64.footprint(), None);
+        assert_eq!(wider_than_i64.volume(), 0.0);
+        let corners = Aabb::new(Point3::new(i16::MIN, 0, 0), Point3::new(i16::MAX, 1, 1));
+        assert_eq!(corners.volume(), 65535.0);
+        assert_eq!(
+            Aabb::extrude(&rect, i64::MAX - 8, 8).unwrap().max.z,
+            i64::MAX
+        );
+        assert_eq!(Aabb::extrude(&rect, i64::MAX - 7, 8), None);
+    }
+
+    #[test]
+    fn boxes_overlap_like_their_rectangles() {
+        let rects = [
+            Rectangle::new(0, 0, 10, 10),
+            Rectangle::new(10, 0, 10, 10),
+            Rectangle::new(9, 9, 5, 5),
+            Rectangle::new(5, 5, 0, 10),
+            Rectangle::new(-5, -5, 5, 5),
+            Rectangle::new(20, 20, 1, 1),
+        ];
+        for a in &rects {
+            for b in &rects {
+                let (slab_a, slab_b) = (
+                    Aabb::extrude(a, 0, 4).unwrap(),
+                    Aabb::extrude(b, 0, 4).unwrap(),
+                );
+                assert_eq!(slab_a.overlaps(&slab_b), a.overlaps(b), "{:?} {:?}", a, b);
+                // closed boxes also meet when they only touch
+                assert!(!a.overlaps(b) || slab_a.intersects(&slab_b));
+            }
+        }
+        // the slabs of one rectangle stacked on top of each other only touch
+        let lower = Aabb::extrude(&rects[0], 0, 4).unwrap();
+        let upper = Aabb::extrude(&rects[0], 4, 4).unwrap();
+        assert!(lower.intersects(&upper) && !lower.overlaps(&upper));
+        assert!(!Aabb::from(rects[0]).overlaps(&lower));
+    }
+}
@@ -47,4 +47,21 @@ pub mod api {
     pub fn add_two(a: i32) -> i32 {
         a + 2
     }
-}
\ No newline at end of file
+}
+
+pub mod auth;
+pub mod coin;
+pub mod color;
+pub mod config;
+pub mod counter;
+pub mod error;
+pub mod files;
+pub mod game;
+pub mod geometry;
+pub mod message;
+pub mod network;
+pub mod scoreboard;
+pub mod spreadsheet;
+pub mod strings;
+pub mod text;
+pub mod user;
@@ -0,0 +1,841 @@
+// https://doc.rust-lang.org/book/ch06-01-defining-an-enum.html
+// The Message enum from the enums chapter as a small command protocol. Messages travel as
+// length prefixed binary frames or as one line of text each, and a Screen applies them.
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use crate::color::{Color, ColorError};
+
+pub mod bus;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Message {
+    Quit,
+    Move { x: i32, y: i32 },
+    Write(String),
+    ChangeColor(i32, i32, i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Quit,
+    Move,
+    Write,
+    ChangeColor,
+}
+
+// Frames claiming to be longer than this are rejected as soon as their length is read.
+pub const MAX_FRAME_LEN: usize = 1 << 20;
+
+const TAG_QUIT: u8 = 0;
+const TAG_MOVE: u8 = 1;
+const TAG_WRITE: u8 = 2;
+const TAG_CHANGE_COLOR: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    EmptyFrame,
+    UnknownTag(u8),
+    // the frame ended in the middle of a field
+    Truncated,
+    TrailingBytes(usize),
+    InvalidVarint,
+    InvalidUtf8,
+    FrameTooLarge { len: u64, max: usize },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::EmptyFrame => write!(f, "frame is empty"),
+            DecodeError::UnknownTag(tag) => write!(f, "unknown message tag {}", tag),
+            DecodeError::Truncated => write!(f, "frame ends in the middle of a field"),
+            DecodeError::TrailingBytes(n) => write!(f, "{} bytes left after the message", n),
+            DecodeError::InvalidVarint => write!(f, "number is too long"),
+            DecodeError::InvalidUtf8 => write!(f, "text is not valid UTF-8"),
+            DecodeError::FrameTooLarge { len, max } => {
+                write!(f, "frame of {} bytes is larger than {}", len, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    // the decoder would refuse the frame, mostly from text that is too long
+    FrameTooLarge { len: usize, max: usize },
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::FrameTooLarge { len, max } => {
+                write!(f, "message of {} bytes is larger than {}", len, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextError {
+    Empty,
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    InvalidNumber(String),
+    // text must be quoted, with \\, \", \n, \r and \t as the only escapes
+    InvalidString(String),
+    TrailingInput(String),
+}
+
+impl Display for TextError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TextError::Empty => write!(f, "line is empty"),
+            TextError::UnknownCommand(s) => write!(f, "unknown command {:?}", s),
+            TextError::MissingArgument(name) => write!(f, "missing argument {}", name),
+            TextError::InvalidNumber(s) => write!(f, "{:?} is not a number", s),
+            TextError::InvalidString(s) => write!(f, "{} is not a quoted string", s),
+            TextError::TrailingInput(s) => write!(f, "unexpected {:?} after the command", s),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+impl Message {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Message::Quit => Kind::Quit,
+            Message::Move { .. } => Kind::Move,
+            Message::Write(_) => Kind::Write,
+            Message::ChangeColor(..) => Kind::ChangeColor,
+        }
+    }
+
+    // Appends one frame: the length of the rest as a varint, a tag byte, then the fields.
+    // Numbers are zigzag varints, so small values of either sign take one byte.
+    // A message longer than MAX_FRAME_LEN is refused and nothing is appended.
+    pub fn encode(&self, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+        let mut body = Vec::new();
+        match self {
+            Message::Quit => body.push(TAG_QUIT),
+            Message::Move { x, y } => {
+                body.push(TAG_MOVE);
+                write_i32(&mut body, *x);
+                write_i32(&mut body, *y);
+            }
+            Message::Write(text) => {
+                body.push(TAG_WRITE);
+                write_varint(&mut body, text.len() as u64);
+                body.extend_from_slice(text.as_bytes());
+            }
+            Message::ChangeColor(r, g, b) => {
+                body.push(TAG_CHANGE_COLOR);
+                for channel in [r, g, b] {
+                    write_i32(&mut body, *channel);
+                }
+            }
+        }
+        if body.len() > MAX_FRAME_LEN {
+            return Err(EncodeError::FrameTooLarge {
+                len: body.len(),
+                max: MAX_FRAME_LEN,
+            });
+        }
+        write_varint(out, body.len() as u64);
+        out.extend_from_slice(&body);
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut out = Vec::new();
+        self.encode(&mut out)?;
+        Ok(out)
+    }
+
+    // Exactly one complete frame.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Message, DecodeError> {
+        let mut decoder = Decoder::new();
+        decoder.feed(bytes);
+        let message = decoder.next_message()?.ok_or(DecodeError::Truncated)?;
+        match decoder.buffered() {
+            0 => Ok(message),
+            n => Err(DecodeError::TrailingBytes(n)),
+        }
+    }
+
+    fn decode_body(body: &[u8]) -> Result<Message, DecodeError> {
+        let (&tag, rest) = body.split_first().ok_or(DecodeError::EmptyFrame)?;
+        let mut fields = Fields(rest);
+        let message = match tag {
+            TAG_QUIT => Message::Quit,
+            TAG_MOVE => Message::Move {
+                x: fields.i32()?,
+                y: fields.i32()?,
+            },
+            TAG_WRITE => {
+                let len = fields.varint()? as usize;
+                let bytes = fields.take(len)?;
+                let text =
+                    String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)?;
+                Message::Write(text)
+            }
+            TAG_CHANGE_COLOR => Message::ChangeColor(fields.i32()?, fields.i32()?, fields.i32()?),
+            other => return Err(DecodeError::UnknownTag(other)),
+        };
+        match fields.0.len() {
+            0 => Ok(message),
+            n => Err(DecodeError::TrailingBytes(n)),
+        }
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_i32(out: &mut Vec<u8>, value: i32) {
+    let zigzag = ((value << 1) ^ (value >> 31)) as u32;
+    write_varint(out, zigzag as u64);
+}
+
+// Ok(None) while the varint is not complete yet, otherwise the value and its length.
+fn read_varint(bytes: &[u8]) -> Result<Option<(u64, usize)>, DecodeError> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().enumerate() {
+        // nine bytes hold 63 bits, the tenth may only add the last one and must end it
+        if i == 9 && byte > 1 {
+            return Err(DecodeError::InvalidVarint);
+        }
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    Ok(None)
+}
+
+// the fields of one complete frame
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let (value, len) = read_varint(self.0)?.ok_or(DecodeError::Truncated)?;
+        self.0 = &self.0[len..];
+        Ok(value)
+    }
+
+    fn i32(&mut self) -> Result<i32, DecodeError> {
+        let zigzag = u32::try_from(self.varint()?).map_err(|_| DecodeError::InvalidVarint)?;
+        Ok((zigzag >> 1) as i32 ^ -((zigzag & 1) as i32))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if len > self.0.len() {
+            return Err(DecodeError::Truncated);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+}
+
+// Turns a byte stream cut at arbitrary points back into messages. Bytes are fed in as they
+// arrive and complete frames are taken out. A frame that fails to decode is dropped and the
+// error returned, decoding carries on with the next frame.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder::default()
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    // bytes waiting for the rest of their frame
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    // Ok(None) until a whole frame has been fed.
+    pub fn next_message(&mut self) -> Result<Option<Message>, DecodeError> {
+        // without a length to trust, nothing after it can be trusted either
+        let header = read_varint(&self.buffer).inspect_err(|_| self.buffer.clear())?;
+        let Some((len, header)) = header else {
+            return Ok(None);
+        };
+        if len > MAX_FRAME_LEN as u64 {
+            self.buffer.clear();
+            return Err(DecodeError::FrameTooLarge {
+                len,
+                max: MAX_FRAME_LEN,
+            });
+        }
+        let end = header + len as usize;
+        if self.buffer.len() < end {
+            return Ok(None);
+        }
+        let message = Message::decode_body(&self.buffer[header..end]);
+        self.buffer.drain(..end);
+        message.map(Some)
+    }
+}
+
+impl Iterator for Decoder {
+    type Item = Result<Message, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_message().transpose()
+    }
+}
+
+// The text form, one message per line: QUIT, MOVE 1 -2, WRITE "hi\n" and COLOR 255 0 0.
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Message::Quit => write!(f, "QUIT"),
+            Message::Move { x, y } => write!(f, "MOVE {} {}", x, y),
+            Message::Write(text) => {
+                write!(f, "WRITE \"")?;
+                for c in text.chars() {
+                    match c {
+                        '\\' => write!(f, "\\\\")?,
+                        '"' => write!(f, "\\\"")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\t' => write!(f, "\\t")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Message::ChangeColor(r, g, b) => write!(f, "COLOR {} {} {}", r, g, b),
+        }
+    }
+}
+
+impl FromStr for Message {
+    type Err = TextError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim_start();
+        match command.to_ascii_uppercase().as_str() {
+            "" => Err(TextError::Empty),
+            "QUIT" => {
+                numbers::<0>(rest, [])?;
+                Ok(Message::Quit)
+            }
+            "MOVE" => {
+                let [x, y] = numbers(rest, ["x", "y"])?;
+                Ok(Message::Move { x, y })
+            }
+            "WRITE" => parse_quoted(rest).map(Message::Write),
+            "COLOR" => {
+                let [r, g, b] = numbers(rest, ["red", "green", "blue"])?;
+                Ok(Message::ChangeColor(r, g, b))
+            }
+            _ => Err(TextError::UnknownCommand(command.to_string())),
+        }
+    }
+}
+
+fn numbers<const N: usize>(args: &str, names: [&'static str; N]) -> Result<[i32; N], TextError> {
+    let mut words = args.split_whitespace();
+    let mut values = [0; N];
+    for (value, name) in values.iter_mut().zip(names) {
+        let word = words.next().ok_or(TextError::MissingArgument(name))?;
+        *value = word
+            .parse()
+            .map_err(|_| TextError::InvalidNumber(word.to_string()))?;
+    }
+    match words.next() {
+        Some(extra) => Err(TextError::TrailingInput(extra.to_string())),
+        None => Ok(values),
+    }
+}
+
+fn parse_quoted(arg: &str) -> Result<String, TextError> {
+    let invalid = || TextError::InvalidString(arg.to_string());
+    if arg.is_empty() {
+        return Err(TextError::MissingArgument("text"));
+    }
+    let mut chars = arg.strip_prefix('"').ok_or_else(invalid)?.chars();
+    let mut text = String::new();
+    loop {
+        match chars.next().ok_or_else(invalid)? {
+            '"' => break,
+            '\\' => text.push(match chars.next().ok_or_else(invalid)? {
+                '\\' => '\\',
+                '"' => '"',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                _ => return Err(invalid()),
+            }),
+            c => text.push(c),
+        }
+    }
+    match chars.as_str().trim() {
+        "" => Ok(text),
+        rest => Err(TextError::TrailingInput(rest.to_string())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApplyError {
+    // nothing is applied after Quit
+    Stopped,
+    NegativePosition { x: i32, y: i32 },
+    // a move or a character past the size of the screen
+    OutOfBounds { column: usize, line: usize },
+    InvalidColor(ColorError),
+}
+
+impl Display for ApplyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplyError::Stopped => write!(f, "screen has already quit"),
+            ApplyError::NegativePosition { x, y } => {
+                write!(f, "cannot move the cursor to ({}, {})", x, y)
+            }
+            ApplyError::OutOfBounds { column, line } => {
+                write!(f, "({}, {}) is off the screen", column, line)
+            }
+            ApplyError::InvalidColor(e) => write!(f, "invalid color: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ApplyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApplyError::InvalidColor(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ColorError> for ApplyError {
+    fn from(e: ColorError) -> Self {
+        ApplyError::InvalidColor(e)
+    }
+}
+
+// the size of Screen::new, a classic terminal
+pub const DEFAULT_COLUMNS: usize = 80;
+pub const DEFAULT_LINES: usize = 24;
+
+// What the messages act on: a cursor over lines of text and the current color.
+// The cursor counts characters across and lines down, both starting at 0, and nothing can
+// be written outside the size, so a message cannot make the screen grow without end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screen {
+    size: (usize, usize),
+    cursor: (usize, usize),
+    lines: Vec<String>,
+    color: Color,
+    running: bool,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Screen::with_size(DEFAULT_COLUMNS, DEFAULT_LINES)
+    }
+}
+
+impl Screen {
+    pub fn new() -> Screen {
+        Screen::default()
+    }
+
+    pub fn with_size(columns: usize, lines: usize) -> Screen {
+        Screen {
+            size: (columns, lines),
+            cursor: (0, 0),
+            lines: Vec::new(),
+            color: Color::BLACK,
+            running: true,
+        }
+    }
+
+    // columns and lines
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    // all lines joined with '\n'
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    // A failed message leaves the screen as it was.
+    pub fn apply(&mut self, message: &Message) -> Result<(), ApplyError> {
+        if !self.running {
+            return Err(ApplyError::Stopped);
+        }
+        match message {
+            Message::Quit => self.running = false,
+            Message::Move { x, y } => {
+                let position = (usize::try_from(*x), usize::try_from(*y));
+                let (Ok(column), Ok(line)) = position else {
+                    return Err(ApplyError::NegativePosition { x: *x, y: *y });
+                };
+                if column >= self.size.0 || line >= self.size.1 {
+                    return Err(ApplyError::OutOfBounds { column, line });
+                }
+                self.cursor = (column, line);
+            }
+            Message::Write(text) => self.write(text)?,
+            Message::ChangeColor(r, g, b) => self.color = Color::try_from((*r, *g, *b))?,
+        }
+        Ok(())
+    }
+
+    // Applies messages until one fails or the screen quits, and returns how many were applied.
+    pub fn run<'a>(
+        &mut self,
+        messages: impl IntoIterator<Item = &'a Message>,
+    ) -> Result<usize, ApplyError> {
+        let mut applied = 0;
+        for message in messages {
+            if !self.running {
+                break;
+            }
+            self.apply(message)?;
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    // Overwrites from the cursor on, padding with spaces when the cursor is past the end.
+    // A newline moves to the start of the next line. Every character has to fit on the
+    // screen, the text is checked before anything is written.
+    fn write(&mut self, text: &str) -> Result<(), ApplyError> {
+        let (mut column, mut line) = self.cursor;
+        let mut parts = Vec::new();
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                (column, line) = (0, line + 1);
+            }
+            let len = part.chars().count();
+            if len > 0 {
+                let last = column + len - 1;
+                if last >= self.size.0 || line >= self.size.1 {
+                    return Err(ApplyError::OutOfBounds { column: last, line });
+                }
+                parts.push((column, line, part));
+            }
+            column += len;
+        }
+
+        for (column, line, part) in parts {
+            if self.lines.len() <= line {
+                self.lines.resize(line + 1, String::new());
+            }
+            let mut chars: Vec<char> = self.lines[line].chars().collect();
+            let end = column + part.chars().count();
+            if chars.len() < end {
+                chars.resize(end, ' ');
+            }
+            chars.splice(column..end, part.chars());
+            self.lines[line] = chars.into_iter().collect();
+        }
+        self.cursor = (column, line);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn samples() -> Vec<Message> {
+        vec![
+            Message::Quit,
+            Message::Move { x: 1, y: -2 },
+            Message::Write(String::from("hello \"world\"\n\tнапиши")),
+            Message::ChangeColor(255, 0, 128),
+            Message::Move {
+                x: i32::MIN,
+                y: i32::MAX,
+            },
+        ]
+    }
+
+    #[test]
+    fn binary_is_compact() {
+        assert_eq!(Message::Quit.to_bytes().unwrap(), [1, 0]);
+        assert_eq!(
+            Message::Move { x: 1, y: -1 }.to_bytes().unwrap(),
+            [3, 1, 2, 1]
+        );
+        assert_eq!(
+            Message::Write(String::from("hi")).to_bytes().unwrap(),
+            [4, 2, 2, b'h', b'i']
+        );
+    }
+
+    #[test]
+    fn refuses_frames_the_decoder_would_refuse() {
+        // tag and a 3 byte length around the text
+        let fits = Message::Write("a".repeat(MAX_FRAME_LEN - 4));
+        assert_eq!(fits.to_bytes().unwrap().len(), MAX_FRAME_LEN + 3);
+
+        let mut out = vec![7];
+        assert_eq!(
+            Message::Write("a".repeat(MAX_FRAME_LEN)).encode(&mut out),
+            Err(EncodeError::FrameTooLarge {
+                len: MAX_FRAME_LEN + 4,
+                max: MAX_FRAME_LEN
+            })
+        );
+        assert_eq!(out, [7]);
+    }
+
+    #[test]
+    fn varint_uses_all_64_bits_and_no_more() {
+        let mut max = Vec::new();
+        write_varint(&mut max, u64::MAX);
+        assert_eq!(max.len(), 10);
+        assert_eq!(read_varint(&max), Ok(Some((u64::MAX, 10))));
+
+        // a tenth byte of 2 would be bit 64
+        let mut overflow = vec![0xff; 9];
+        overflow.push(0x02);
+        assert_eq!(read_varint(&overflow), Err(DecodeError::InvalidVarint));
+        assert_eq!(read_varint(&[0xff; 9]), Ok(None));
+    }
+
+    #[test]
+    fn decoder_handles_partial_frames() {
+        let mut stream = Vec::new();
+        for message in samples() {
+            message.encode(&mut stream).unwrap();
+        }
+        let mut decoder = Decoder::new();
+        let mut decoded = Vec::new();
+        for byte in stream {
+            decoder.feed(&[byte]);
+            decoded.extend(decoder.by_ref().map(Result::unwrap));
+        }
+        assert_eq!(decoded, samples());
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn decoder_skips_bad_frames() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[2, 9, 0]);
+        decoder.feed(&Message::Quit.to_bytes().unwrap());
+        decoder.feed(&[3, TAG_WRITE, 5, b'a']);
+        decoder.feed(&[3, TAG_WRITE, 1, 0xff]);
+        assert_eq!(decoder.next_message(), Err(DecodeError::UnknownTag(9)));
+        assert_eq!(decoder.next_message(), Ok(Some(Message::Quit)));
+        assert_eq!(decoder.next_message(), Err(DecodeError::Truncated));
+        assert_eq!(decoder.next_message(), Err(DecodeError::InvalidUtf8));
+        assert_eq!(decoder.next_message(), Ok(None));
+
+        decoder.feed(&[0x80; 12]);
+        assert_eq!(decoder.next_message(), Err(DecodeError::InvalidVarint));
+        assert_eq!(decoder.buffered(), 0);
+
+        assert_eq!(
+            Message::from_bytes(&[3, TAG_WRITE, 1, 0xff]),
+            Err(DecodeError::InvalidUtf8)
+        );
+        assert_eq!(
+            Message::from_bytes(&[2, TAG_QUIT, 0]),
+            Err(DecodeError::TrailingBytes(1))
+        );
+        assert_eq!(
+            Message::from_bytes(&[1, 0, 0]),
+            Err(DecodeError::TrailingBytes(1))
+        );
+        assert_eq!(Message::from_bytes(&[0]), Err(DecodeError::EmptyFrame));
+        assert_eq!(
+            Message::from_bytes(&[0xff, 0xff, 0xff, 0x7f]),
+            Err(DecodeError::FrameTooLarge {
+                len: 0xfffffff,
+                max: MAX_FRAME_LEN
+            })
+        );
+    }
+
+    #[test]
+    fn text_form() {
+        let lines: Vec<String> = samples().iter().map(|m| m.to_string()).collect();
+        assert_eq!(lines[1], "MOVE 1 -2");
+        assert_eq!(lines[2], r#"WRITE "hello \"world\"\n\tнапиши""#);
+        assert_eq!(lines[3], "COLOR 255 0 128");
+        assert_eq!("  move 3   4 ".parse(), Ok(Message::Move { x: 3, y: 4 }));
+
+        let parse = |s: &str| s.parse::<Message>();
+        assert_eq!(parse(""), Err(TextError::Empty));
+        assert_eq!(
+            parse("JUMP 1"),
+            Err(TextError::UnknownCommand(String::from("JUMP")))
+        );
+        assert_eq!(parse("MOVE 1"), Err(TextError::MissingArgument("y")));
+        assert_eq!(
+            parse("MOVE 1 two"),
+            Err(TextError::InvalidNumber(String::from("two")))
+        );
+        assert_eq!(
+            parse("QUIT now"),
+            Err(TextError::TrailingInput(String::from("now")))
+        );
+        assert_eq!(
+            parse(r#"WRITE "a\q""#),
+            Err(TextError::InvalidString(String::from(r#""a\q""#)))
+        );
+        assert_eq!(
+            parse(r#"WRITE "a" b"#),
+            Err(TextError::TrailingInput(String::from("b")))
+        );
+    }
+
+    #[test]
+    fn screen_applies_messages() {
+        let mut screen = Screen::new();
+        let script = [
+            Message::Write(String::from("hello\nworld")),
+            Message::Move { x: 0, y: 0 },
+            Message::Write(String::from("J")),
+            Message::Move { x: 7, y: 1 },
+            Message::Write(String::from("!")),
+            Message::ChangeColor(255, 0, 0),
+            Message::Quit,
+            Message::Write(String::from("ignored")),
+        ];
+        assert_eq!(screen.run(&script), Ok(7));
+        assert_eq!(screen.text(), "Jello\nworld  !");
+        assert_eq!(screen.cursor(), (8, 1));
+        assert_eq!(screen.color(), Color::rgb(255, 0, 0));
+        assert!(!screen.is_running());
+        assert_eq!(screen.apply(&Message::Quit), Err(ApplyError::Stopped));
+    }
+
+    #[test]
+    fn invalid_messages_change_nothing() {
+        let mut screen = Screen::new();
+        assert_eq!(
+            screen.apply(&Message::Move { x: -1, y: 0 }),
+            Err(ApplyError::NegativePosition { x: -1, y: 0 })
+        );
+        assert!(matches!(
+            screen.apply(&Message::ChangeColor(0, 256, 0)),
+            Err(ApplyError::InvalidColor(_))
+        ));
+        assert_eq!(
+            screen.apply(&Message::Move { x: 80, y: 0 }),
+            Err(ApplyError::OutOfBounds {
+                column: 80,
+                line: 0
+            })
+        );
+        assert_eq!(screen, Screen::new());
+    }
+
+    #[test]
+    fn writes_stay_on_the_screen() {
+        let mut screen = Screen::with_size(4, 2);
+        assert_eq!(
+            screen.apply(&Message::Write(String::from("abcd\nef"))),
+            Ok(())
+        );
+        assert_eq!(screen.cursor(), (2, 1));
+        let before = screen.clone();
+        assert_eq!(
+            screen.apply(&Message::Write(String::from("gh\nij"))),
+            Err(ApplyError::OutOfBounds { column: 1, line: 2 })
+        );
+        assert_eq!(
+            screen.apply(&Message::Write(String::from("ghi"))),
+            Err(ApplyError::OutOfBounds { column: 4, line: 1 })
+        );
+        assert_eq!(screen, before);
+
+        // the cursor may rest just past the edge, only writing there fails
+        assert_eq!(screen.apply(&Message::Write(String::from("gh\n"))), Ok(()));
+        assert_eq!(screen.cursor(), (0, 2));
+        assert_eq!(screen.text(), "abcd\nefgh");
+        assert_eq!(
+            screen.apply(&Message::Move {
+                x: i32::MAX,
+                y: i32::MAX
+            }),
+            Err(ApplyError::OutOfBounds {
+                column: i32::MAX as usize,
+                line: i32::MAX as usize
+            })
+        );
+    }
+
+    fn message() -> impl Strategy<Value = Message> {
+        prop_oneof![
+            Just(Message::Quit),
+            (any::<i32>(), any::<i32>()).prop_map(|(x, y)| Message::Move { x, y }),
+            any::<String>().prop_map(Message::Write),
+            (any::<i32>(), any::<i32>(), any::<i32>())
+                .prop_map(|(r, g, b)| Message::ChangeColor(r, g, b)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn binary_round_trips(messages in proptest::collection::vec(message(), 0..10), cut in any::<usize>()) {
+            let mut stream = Vec::new();
+            for m in &messages {
+                m.encode(&mut stream).unwrap();
+            }
+            let cut = cut % (stream.len() + 1);
+            let mut decoder = Decoder::new();
+            decoder.feed(&stream[..cut]);
+            let mut decoded: Vec<Message> = decoder.by_ref().map(Result::unwrap).collect();
+            decoder.feed(&stream[cut..]);
+            decoded.extend(decoder.by_ref().map(Result::unwrap));
+            prop_assert_eq!(decoded, messages);
+        }
+
+        #[test]
+        fn text_round_trips(m in message()) {
+            prop_assert_eq!(m.to_string().parse::<Message>(), Ok(m.clone()));
+            prop_assert!(!m.to_string().contains('\n'));
+        }
+
+        #[test]
+        fn decoder_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..64)) {
+            let mut decoder = Decoder::new();
+            decoder.feed(&bytes);
+            for _ in decoder.by_ref().take(64) {}
+        }
+    }
+}
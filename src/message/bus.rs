@@ -0,0 +1,443 @@
+// Passes messages between components inside one process. Every subscriber runs its handler
+// on its own thread and reads from its own bounded queue, so a slow subscriber only holds
+// up the publishers that wait for it.
+use std::any::Any;
+use std::cell::Cell;
+use std::fmt::{self, Display, Formatter};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+
+use super::{Kind, Message};
+
+// Which kinds of messages a subscriber wants. Quit is always delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Filter(u8);
+
+impl Filter {
+    pub const ALL: Filter = Filter(u8::MAX);
+
+    pub fn only(kinds: &[Kind]) -> Filter {
+        Filter(kinds.iter().fold(0, |bits, &kind| bits | Filter::bit(kind)))
+    }
+
+    pub fn matches(&self, message: &Message) -> bool {
+        let kind = message.kind();
+        kind == Kind::Quit || self.0 & Filter::bit(kind) != 0
+    }
+
+    fn bit(kind: Kind) -> u8 {
+        1 << kind as u8
+    }
+}
+
+// A message whose handler panicked. The subscriber keeps running with the next message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+    pub subscriber: String,
+    pub message: Message,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BusError {
+    // Quit has been published
+    Closed,
+    // from try_publish, or publish inside a handler: these subscribers had a full queue and
+    // did not get the message, the others did
+    Full(Vec<String>),
+}
+
+impl Display for BusError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BusError::Closed => write!(f, "bus is shutting down"),
+            BusError::Full(names) => write!(f, "queue full for {}", names.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for BusError {}
+
+struct Subscriber {
+    name: String,
+    filter: Filter,
+    queue: SyncSender<Message>,
+}
+
+#[derive(Default)]
+struct Shared {
+    subscribers: Vec<Subscriber>,
+    closed: bool,
+}
+
+thread_local! {
+    // set on the threads that run handlers
+    static IN_HANDLER: Cell<bool> = const { Cell::new(false) };
+}
+
+// No code that can panic runs while one of the bus locks is held, so a poisoned lock still
+// guards consistent data.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// A cheap handle for publishing, it can be cloned into handlers and other threads.
+#[derive(Clone, Default)]
+pub struct Publisher {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Publisher {
+    // Waits for room in every matching queue, which is the backpressure on fast publishers.
+    // Returns how many subscribers got the message.
+    //
+    // Inside a handler this never waits and works like try_publish: the handler would be
+    // waiting on its own queue, or on a subscriber that is waiting on it.
+    pub fn publish(&self, message: Message) -> Result<usize, BusError> {
+        if IN_HANDLER.get() {
+            return self.try_publish(message);
+        }
+        if message == Message::Quit {
+            return self.close();
+        }
+        let mut delivered = 0;
+        for (_, queue) in self.recipients(&message)? {
+            // a subscriber that already stopped is not an error
+            if queue.send(message.clone()).is_ok() {
+                delivered += 1;
+            }
+        }
+        Ok(delivered)
+    }
+
+    // Never waits. Subscribers with a full queue miss the message and are named in the error.
+    pub fn try_publish(&self, message: Message) -> Result<usize, BusError> {
+        if message == Message::Quit {
+            return self.close();
+        }
+        let mut delivered = 0;
+        let mut full = Vec::new();
+        for (name, queue) in self.recipients(&message)? {
+            match queue.try_send(message.clone()) {
+                Ok(()) => delivered += 1,
+                Err(TrySendError::Full(_)) => full.push(name),
+                Err(TrySendError::Disconnected(_)) => {}
+            }
+        }
+        if full.is_empty() {
+            Ok(delivered)
+        } else {
+            Err(BusError::Full(full))
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        lock(&self.shared).closed
+    }
+
+    // Quit is never queued. Dropping the bus's ends of the queues closes them once the
+    // publishers still sending have finished, and each worker hands its handler Quit after
+    // the last message, so nothing published before Quit is lost and Quit never waits.
+    fn close(&self) -> Result<usize, BusError> {
+        let mut shared = lock(&self.shared);
+        if shared.closed {
+            return Err(BusError::Closed);
+        }
+        shared.closed = true;
+        Ok(shared.subscribers.drain(..).count())
+    }
+
+    // The lock is only held to copy the queues, so that a handler can publish without
+    // waiting for the publisher that is waiting for it.
+    fn recipients(
+        &self,
+        message: &Message,
+    ) -> Result<Vec<(String, SyncSender<Message>)>, BusError> {
+        let shared = lock(&self.shared);
+        if shared.closed {
+            return Err(BusError::Closed);
+        }
+        Ok(shared
+            .subscribers
+            .iter()
+            .filter(|s| s.filter.matches(message))
+            .map(|s| (s.name.clone(), s.queue.clone()))
+            .collect())
+    }
+}
+
+// Publishing Quit closes the bus: every subscriber gets Quit after the messages already in
+// its queue and those still being sent, runs its handler on it and stops, and publishing
+// after that fails with Closed.
+#[derive(Default)]
+pub struct Bus {
+    publisher: Publisher,
+    workers: Vec<JoinHandle<()>>,
+    dead_letters: Arc<Mutex<Vec<DeadLetter>>>,
+}
+
+impl Bus {
+    pub fn new() -> Bus {
+        Bus::default()
+    }
+
+    pub fn publisher(&self) -> Publisher {
+        self.publisher.clone()
+    }
+
+    pub fn publish(&self, message: Message) -> Result<usize, BusError> {
+        self.publisher.publish(message)
+    }
+
+    pub fn try_publish(&self, message: Message) -> Result<usize, BusError> {
+        self.publisher.try_publish(message)
+    }
+
+    // capacity is the number of messages that can wait in the queue, at least 1.
+    pub fn subscribe(
+        &mut self,
+        name: &str,
+        filter: Filter,
+        capacity: usize,
+        mut handler: impl FnMut(&Message) + Send + 'static,
+    ) -> Result<(), BusError> {
+        let (queue, inbox) = mpsc::sync_channel::<Message>(capacity.max(1));
+        {
+            let mut shared = lock(&self.publisher.shared);
+            if shared.closed {
+                return Err(BusError::Closed);
+            }
+            shared.subscribers.push(Subscriber {
+                name: name.to_string(),
+                filter,
+                queue,
+            });
+        }
+
+        let name = name.to_string();
+        let dead_letters = Arc::clone(&self.dead_letters);
+        self.workers.push(thread::spawn(move || {
+            IN_HANDLER.set(true);
+            let mut handle = |message: &Message| {
+                if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| handler(message))) {
+                    lock(&dead_letters).push(DeadLetter {
+                        subscriber: name.clone(),
+                        message: message.clone(),
+                        reason: panic_reason(payload),
+                    });
+                }
+            };
+            // the queue only ends after Quit, see Publisher::close
+            for message in inbox {
+                handle(&message);
+            }
+            handle(&Message::Quit);
+        }));
+        Ok(())
+    }
+
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        lock(&self.dead_letters).clone()
+    }
+
+    // Waits until some component publishes Quit and every subscriber has stopped.
+    pub fn wait(self) -> Vec<DeadLetter> {
+        for worker in self.workers {
+            // handler panics are caught, so a worker cannot fail
+            let _ = worker.join();
+        }
+        let dead_letters = lock(&self.dead_letters);
+        dead_letters.clone()
+    }
+
+    // Publishes Quit unless someone already did, then waits like wait.
+    pub fn shutdown(self) -> Vec<DeadLetter> {
+        let _ = self.publish(Message::Quit);
+        self.wait()
+    }
+}
+
+fn panic_reason(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(reason) => *reason,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(reason) => reason.to_string(),
+            Err(_) => String::from("unknown panic"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(text: &str) -> Message {
+        Message::Write(String::from(text))
+    }
+
+    // a handler that forwards everything it sees to the test
+    fn recorder() -> (
+        impl FnMut(&Message) + Send + 'static,
+        mpsc::Receiver<Message>,
+    ) {
+        let (sender, receiver) = mpsc::channel();
+        (move |m: &Message| sender.send(m.clone()).unwrap(), receiver)
+    }
+
+    #[test]
+    fn fans_out_to_matching_subscribers() {
+        let mut bus = Bus::new();
+        let (all, all_seen) = recorder();
+        let (writes, writes_seen) = recorder();
+        bus.subscribe("all", Filter::ALL, 8, all).unwrap();
+        bus.subscribe("writes", Filter::only(&[Kind::Write]), 8, writes)
+            .unwrap();
+
+        assert_eq!(bus.publish(Message::Move { x: 1, y: 2 }), Ok(1));
+        assert_eq!(bus.publish(write("hi")), Ok(2));
+        assert!(bus.shutdown().is_empty());
+
+        let all: Vec<Message> = all_seen.iter().collect();
+        assert_eq!(
+            all,
+            vec![Message::Move { x: 1, y: 2 }, write("hi"), Message::Quit]
+        );
+        let writes: Vec<Message> = writes_seen.iter().collect();
+        assert_eq!(writes, vec![write("hi"), Message::Quit]);
+    }
+
+    #[test]
+    fn full_queues_push_back() {
+        let mut bus = Bus::new();
+        let (gate, gate_open) = mpsc::channel::<()>();
+        let gate_open = Mutex::new(gate_open);
+        bus.subscribe("slow", Filter::ALL, 1, move |m| {
+            if *m != Message::Quit {
+                gate_open.lock().unwrap().recv().unwrap();
+            }
+        })
+        .unwrap();
+
+        // the first is being handled, the second waits in the queue
+        bus.publish(write("1")).unwrap();
+        bus.publish(write("2")).unwrap();
+        assert_eq!(
+            bus.try_publish(write("3")),
+            Err(BusError::Full(vec![String::from("slow")]))
+        );
+
+        gate.send(()).unwrap();
+        gate.send(()).unwrap();
+        assert!(bus.shutdown().is_empty());
+    }
+
+    #[test]
+    fn panicking_handlers_end_up_in_dead_letters() {
+        let mut bus = Bus::new();
+        let (rest, seen) = recorder();
+        let mut rest = rest;
+        bus.subscribe("fragile", Filter::ALL, 4, move |m| {
+            if *m == write("boom") {
+                panic!("cannot write boom");
+            }
+            rest(m);
+        })
+        .unwrap();
+
+        bus.publish(write("boom")).unwrap();
+        bus.publish(write("fine")).unwrap();
+        let dead_letters = bus.shutdown();
+        assert_eq!(
+            dead_letters,
+            vec![DeadLetter {
+                subscriber: String::from("fragile"),
+                message: write("boom"),
+                reason: String::from("cannot write boom"),
+            }]
+        );
+        assert_eq!(
+            seen.iter().collect::<Vec<_>>(),
+            vec![write("fine"), Message::Quit]
+        );
+    }
+
+    #[test]
+    fn quit_from_a_handler_shuts_everything_down() {
+        let mut bus = Bus::new();
+        let publisher = bus.publisher();
+        bus.subscribe("stopper", Filter::only(&[Kind::Write]), 4, move |m| {
+            if *m == write("stop") {
+                publisher.publish(Message::Quit).unwrap();
+            }
+        })
+        .unwrap();
+        let (other, other_seen) = recorder();
+        bus.subscribe("other", Filter::ALL, 4, other).unwrap();
+
+        bus.publish(write("stop")).unwrap();
+        let publisher = bus.publisher();
+        assert!(bus.wait().is_empty());
+        assert!(publisher.is_closed());
+        assert_eq!(publisher.publish(write("late")), Err(BusError::Closed));
+        assert_eq!(
+            other_seen.iter().collect::<Vec<_>>(),
+            vec![write("stop"), Message::Quit]
+        );
+    }
+
+    #[test]
+    fn handlers_publishing_to_a_full_queue_do_not_wait() {
+        let mut bus = Bus::new();
+        let publisher = bus.publisher();
+        let (results, results_seen) = mpsc::channel();
+        bus.subscribe("echo", Filter::ALL, 1, move |m| {
+            if *m == write("start") {
+                // its own queue has room for one
+                for text in ["a", "b"] {
+                    results.send(publisher.publish(write(text))).unwrap();
+                }
+            }
+        })
+        .unwrap();
+
+        bus.publish(write("start")).unwrap();
+        assert_eq!(results_seen.recv(), Ok(Ok(1)));
+        assert_eq!(
+            results_seen.recv(),
+            Ok(Err(BusError::Full(vec![String::from("echo")])))
+        );
+        assert!(bus.shutdown().is_empty());
+    }
+
+    #[test]
+    fn messages_sent_while_quitting_come_before_quit() {
+        let mut bus = Bus::new();
+        let (seen, all_seen) = recorder();
+        bus.subscribe("all", Filter::ALL, 4, seen).unwrap();
+
+        // a publisher that got the queues just before Quit and sends just after it
+        let late = bus.publisher().recipients(&write("late")).unwrap();
+        assert_eq!(bus.publish(Message::Quit), Ok(1));
+        assert_eq!(bus.publish(write("after")), Err(BusError::Closed));
+        for (_, queue) in late {
+            queue.send(write("late")).unwrap();
+        }
+
+        assert!(bus.wait().is_empty());
+        assert_eq!(
+            all_seen.iter().collect::<Vec<_>>(),
+            vec![write("late"), Message::Quit]
+        );
+    }
+
+    #[test]
+    fn filters() {
+        let moves = Filter::only(&[Kind::Move]);
+        assert!(moves.matches(&Message::Move { x: 0, y: 0 }));
+        assert!(moves.matches(&Message::Quit));
+        assert!(!moves.matches(&Message::ChangeColor(0, 0, 0)));
+        assert!(Filter::ALL.matches(&write("")));
+    }
+}
@@ -0,0 +1,780 @@
+// https://doc.rust-lang.org/book/ch06-01-defining-an-enum.html
+// The IpAddr enum from the enums chapter, holding validated addresses instead of strings,
+// plus CIDR blocks. Both families are handled as plain integers for the subnet math.
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+pub mod pool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    pub fn bits(&self) -> u8 {
+        match self {
+            IpFamily::V4 => 32,
+            IpFamily::V6 => 128,
+        }
+    }
+}
+
+// Ordered by family first, then numerically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IpAddr {
+    V4([u8; 4]),
+    V6([u16; 8]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressClass {
+    Unspecified,
+    Loopback,
+    Private,
+    LinkLocal,
+    Multicast,
+    Global,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    WrongOctetCount(usize),
+    InvalidOctet(String),
+    WrongGroupCount(usize),
+    InvalidGroup(String),
+    MultipleCompressions,
+    MissingPrefix,
+    InvalidPrefix(String),
+    PrefixTooLong { prefix: u8, max: u8 },
+    HostBitsSet,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "address is empty"),
+            ParseError::WrongOctetCount(n) => write!(f, "IPv4 needs 4 octets, got {}", n),
+            ParseError::InvalidOctet(s) => write!(f, "{:?} is not an octet from 0 to 255", s),
+            ParseError::WrongGroupCount(n) => write!(f, "IPv6 needs 8 groups, got {}", n),
+            ParseError::InvalidGroup(s) => write!(f, "{:?} is not a 16 bit hex group", s),
+            ParseError::MultipleCompressions => write!(f, "'::' can only appear once"),
+            ParseError::MissingPrefix => write!(f, "CIDR block needs a /prefix"),
+            ParseError::InvalidPrefix(s) => write!(f, "{:?} is not a prefix length", s),
+            ParseError::PrefixTooLong { prefix, max } => {
+                write!(f, "prefix /{} is longer than /{}", prefix, max)
+            }
+            ParseError::HostBitsSet => write!(f, "address has bits set after the prefix"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl IpAddr {
+    pub const V4_LOOPBACK: IpAddr = IpAddr::V4([127, 0, 0, 1]);
+    pub const V6_LOOPBACK: IpAddr = IpAddr::V6([0, 0, 0, 0, 0, 0, 0, 1]);
+
+    pub fn parse(s: &str) -> Result<IpAddr, ParseError> {
+        if s.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        if s.contains(':') {
+            parse_v6(s).map(IpAddr::V6)
+        } else {
+            parse_v4(s).map(IpAddr::V4)
+        }
+    }
+
+    pub fn family(&self) -> IpFamily {
+        match self {
+            IpAddr::V4(_) => IpFamily::V4,
+            IpAddr::V6(_) => IpFamily::V6,
+        }
+    }
+
+    // the address as a number, IPv4 uses the low 32 bits
+    pub fn to_bits(&self) -> u128 {
+        match self {
+            IpAddr::V4(octets) => u32::from_be_bytes(*octets) as u128,
+            IpAddr::V6(groups) => groups.iter().fold(0, |bits, &g| bits << 16 | g as u128),
+        }
+    }
+
+    // extra high bits are dropped for IPv4
+    pub fn from_bits(family: IpFamily, bits: u128) -> IpAddr {
+        match family {
+            IpFamily::V4 => IpAddr::V4((bits as u32).to_be_bytes()),
+            IpFamily::V6 => {
+                let mut groups = [0u16; 8];
+                for (i, group) in groups.iter_mut().enumerate() {
+                    *group = (bits >> (16 * (7 - i))) as u16;
+                }
+                IpAddr::V6(groups)
+            }
+        }
+    }
+
+    // The next address, None after the last one of the family.
+    pub fn next(&self) -> Option<IpAddr> {
+        let bits = self.to_bits().checked_add(1)?;
+        (bits <= max_bits(self.family())).then(|| IpAddr::from_bits(self.family(), bits))
+    }
+
+    pub fn is_unspecified(&self) -> bool {
+        self.to_bits() == 0
+    }
+
+    pub fn is_loopback(&self) -> bool {
+        match self {
+            IpAddr::V4(octets) => octets[0] == 127,
+            IpAddr::V6(_) => *self == IpAddr::V6_LOOPBACK,
+        }
+    }
+
+    // RFC 1918 ranges for IPv4, unique local fc00::/7 for IPv6
+    pub fn is_private(&self) -> bool {
+        match self {
+            IpAddr::V4([10, ..]) | IpAddr::V4([192, 168, ..]) => true,
+            IpAddr::V4([172, b, ..]) => (16..32).contains(b),
+            IpAddr::V4(_) => false,
+            IpAddr::V6(groups) => groups[0] & 0xfe00 == 0xfc00,
+        }
+    }
+
+    pub fn is_link_local(&self) -> bool {
+        match self {
+            IpAddr::V4(octets) => octets[0] == 169 && octets[1] == 254,
+            IpAddr::V6(groups) => groups[0] & 0xffc0 == 0xfe80,
+        }
+    }
+
+    pub fn is_multicast(&self) -> bool {
+        match self {
+            IpAddr::V4(octets) => octets[0] & 0xf0 == 224,
+            IpAddr::V6(groups) => groups[0] & 0xff00 == 0xff00,
+        }
+    }
+
+    pub fn class(&self) -> AddressClass {
+        if self.is_unspecified() {
+            AddressClass::Unspecified
+        } else if self.is_loopback() {
+            AddressClass::Loopback
+        } else if self.is_private() {
+            AddressClass::Private
+        } else if self.is_link_local() {
+            AddressClass::LinkLocal
+        } else if self.is_multicast() {
+            AddressClass::Multicast
+        } else {
+            AddressClass::Global
+        }
+    }
+}
+
+fn max_bits(family: IpFamily) -> u128 {
+    match family {
+        IpFamily::V4 => u32::MAX as u128,
+        IpFamily::V6 => u128::MAX,
+    }
+}
+
+// dotted decimal, no leading zeros so "010" cannot be mistaken for octal
+fn parse_v4(s: &str) -> Result<[u8; 4], ParseError> {
+    let parts: Vec<&str> = s.split('.').collect();
+    if parts.len() != 4 {
+        return Err(ParseError::WrongOctetCount(parts.len()));
+    }
+    let mut octets = [0u8; 4];
+    for (octet, part) in octets.iter_mut().zip(parts) {
+        let valid = !part.is_empty()
+            && part.len() <= 3
+            && part.bytes().all(|b| b.is_ascii_digit())
+            && (part == "0" || !part.starts_with('0'));
+        *octet = match part.parse() {
+            Ok(value) if valid => value,
+            _ => return Err(ParseError::InvalidOctet(part.to_string())),
+        };
+    }
+    Ok(octets)
+}
+
+// Groups of one side of "::". The last group of the address may be dotted IPv4 and counts twice.
+fn parse_groups(s: &str, allow_v4: bool) -> Result<Vec<u16>, ParseError> {
+    if s.is_empty() {
+        return Ok(Vec::new());
+    }
+    let parts: Vec<&str> = s.split(':').collect();
+    let mut groups = Vec::with_capacity(parts.len() + 1);
+    for (i, part) in parts.iter().enumerate() {
+        if allow_v4 && i == parts.len() - 1 && part.contains('.') {
+            let [a, b, c, d] = parse_v4(part)?;
+            groups.push(u16::from_be_bytes([a, b]));
+            groups.push(u16::from_be_bytes([c, d]));
+            continue;
+        }
+        let valid = !part.is_empty() && part.len() <= 4;
+        match u16::from_str_radix(part, 16) {
+            Ok(group) if valid && !part.starts_with('+') => groups.push(group),
+            _ => return Err(ParseError::InvalidGroup(part.to_string())),
+        }
+    }
+    Ok(groups)
+}
+
+fn parse_v6(s: &str) -> Result<[u16; 8], ParseError> {
+    let mut halves = s.split("::");
+    let head = halves.next().unwrap_or_default();
+    let tail = halves.next();
+    if halves.next().is_some() {
+        return Err(ParseError::MultipleCompressions);
+    }
+
+    let mut groups = [0u16; 8];
+    match tail {
+        None => {
+            let all = parse_groups(head, true)?;
+            if all.len() != 8 {
+                return Err(ParseError::WrongGroupCount(all.len()));
+            }
+            groups.copy_from_slice(&all);
+        }
+        Some(tail) => {
+            let head = parse_groups(head, false)?;
+            let tail = parse_groups(tail, true)?;
+            // "::" stands for at least one zero group
+            if head.len() + tail.len() > 7 {
+                return Err(ParseError::WrongGroupCount(head.len() + tail.len()));
+            }
+            groups[..head.len()].copy_from_slice(&head);
+            groups[8 - tail.len()..].copy_from_slice(&tail);
+        }
+    }
+    Ok(groups)
+}
+
+impl FromStr for IpAddr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        IpAddr::parse(s)
+    }
+}
+
+// IPv6 uses the RFC 5952 canonical text form: lowercase, no leading zeros, the longest run of
+// two or more zero groups as "::" (the first one on a tie) and dotted IPv4 for ::ffff:0:0/96.
+impl Display for IpAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            IpAddr::V4([a, b, c, d]) => write!(f, "{}.{}.{}.{}", a, b, c, d),
+            IpAddr::V6(groups) => {
+                if groups[..6] == [0, 0, 0, 0, 0, 0xffff] {
+                    let [a, b] = groups[6].to_be_bytes();
+                    let [c, d] = groups[7].to_be_bytes();
+                    return write!(f, "::ffff:{}.{}.{}.{}", a, b, c, d);
+                }
+
+                let (mut best_start, mut best_len) = (0, 0);
+                let mut i = 0;
+                while i < 8 {
+                    let len = groups[i..].iter().take_while(|&&g| g == 0).count();
+                    if len > best_len {
+                        (best_start, best_len) = (i, len);
+                    }
+                    i += len.max(1);
+                }
+
+                let write_groups = |f: &mut Formatter<'_>, groups: &[u16]| {
+                    let text: Vec<String> = groups.iter().map(|g| format!("{:x}", g)).collect();
+                    write!(f, "{}", text.join(":"))
+                };
+                if best_len < 2 {
+                    return write_groups(f, groups);
+                }
+                write_groups(f, &groups[..best_start])?;
+                write!(f, "::")?;
+                write_groups(f, &groups[best_start + best_len..])
+            }
+        }
+    }
+}
+
+// Both types are stored in their text form, so saved files stay readable.
+macro_rules! serde_as_text {
+    ($t:ty) => {
+        impl Serialize for $t {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $t {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let text = String::deserialize(deserializer)?;
+                text.parse().map_err(de::Error::custom)
+            }
+        }
+    };
+}
+
+serde_as_text!(IpAddr);
+serde_as_text!(Cidr);
+
+// A network such as 10.0.0.0/8. The address is always the first one of the block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    // Fails when bits after the prefix are set, like 10.0.0.1/8.
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Cidr, ParseError> {
+        let cidr = Cidr::containing(addr, prefix)?;
+        if cidr.network != addr {
+            return Err(ParseError::HostBitsSet);
+        }
+        Ok(cidr)
+    }
+
+    // The block of the given size around addr, host bits are cleared.
+    pub fn containing(addr: IpAddr, prefix: u8) -> Result<Cidr, ParseError> {
+        let max = addr.family().bits();
+        if prefix > max {
+            return Err(ParseError::PrefixTooLong { prefix, max });
+        }
+        let bits = addr.to_bits() & mask(addr.family(), prefix);
+        Ok(Cidr {
+            network: IpAddr::from_bits(addr.family(), bits),
+            prefix,
+        })
+    }
+
+    pub fn parse(s: &str) -> Result<Cidr, ParseError> {
+        let (addr, prefix) = s.split_once('/').ok_or(ParseError::MissingPrefix)?;
+        let valid = !prefix.is_empty() && prefix.bytes().all(|b| b.is_ascii_digit());
+        let prefix = match prefix.parse::<u8>() {
+            Ok(prefix) if valid => prefix,
+            _ => return Err(ParseError::InvalidPrefix(prefix.to_string())),
+        };
+        Cidr::new(IpAddr::parse(addr)?, prefix)
+    }
+
+    pub fn network(&self) -> IpAddr {
+        self.network
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    pub fn family(&self) -> IpFamily {
+        self.network.family()
+    }
+
+    // the last address, the broadcast address for IPv4
+    pub fn last(&self) -> IpAddr {
+        let host = !mask(self.family(), self.prefix) & max_bits(self.family());
+        IpAddr::from_bits(self.family(), self.network.to_bits() | host)
+    }
+
+    // None for ::/0, which has 2^128 addresses
+    pub fn address_count(&self) -> Option<u128> {
+        1u128.checked_shl((self.family().bits() - self.prefix) as u32)
+    }
+
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        addr.family() == self.family()
+            && addr.to_bits() & mask(self.family(), self.prefix) == self.network.to_bits()
+    }
+
+    pub fn contains_cidr(&self, other: &Cidr) -> bool {
+        other.prefix >= self.prefix && self.contains(&other.network)
+    }
+
+    // All the blocks of length new_prefix inside this one, in order.
+    pub fn split(&self, new_prefix: u8) -> Result<Subnets, ParseError> {
+        let max = self.family().bits();
+        if new_prefix > max {
+            return Err(ParseError::PrefixTooLong {
+                prefix: new_prefix,
+                max,
+            });
+        }
+        if new_prefix < self.prefix {
+            return Err(ParseError::InvalidPrefix(format!(
+                "/{} is shorter than /{}",
+                new_prefix, self.prefix
+            )));
+        }
+        Ok(Subnets {
+            family: self.family(),
+            prefix: new_prefix,
+            next: Some(self.network.to_bits()),
+            last: self.last().to_bits(),
+        })
+    }
+
+    // the two halves, None for a single address
+    pub fn halves(&self) -> Option<(Cidr, Cidr)> {
+        let mut subnets = self.split(self.prefix.checked_add(1)?).ok()?;
+        Some((subnets.next()?, subnets.next()?))
+    }
+}
+
+fn mask(family: IpFamily, prefix: u8) -> u128 {
+    let bits = family.bits() as u32;
+    let host_bits = bits - prefix as u32;
+    let all = max_bits(family);
+    all.checked_shl(host_bits).unwrap_or(0) & all
+}
+
+impl FromStr for Cidr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Cidr::parse(s)
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+// IPv4 first, then by address, a bigger block before the blocks inside it
+impl Ord for Cidr {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.network
+            .cmp(&other.network)
+            .then(self.prefix.cmp(&other.prefix))
+    }
+}
+
+impl PartialOrd for Cidr {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+pub struct Subnets {
+    family: IpFamily,
+    prefix: u8,
+    next: Option<u128>,
+    last: u128,
+}
+
+impl Iterator for Subnets {
+    type Item = Cidr;
+
+    fn next(&mut self) -> Option<Cidr> {
+        let start = self.next?;
+        let cidr = Cidr {
+            network: IpAddr::from_bits(self.family, start),
+            prefix: self.prefix,
+        };
+        let end = cidr.last().to_bits();
+        self.next = if end >= self.last {
+            None
+        } else {
+            Some(end + 1)
+        };
+        Some(cidr)
+    }
+}
+
+// The fewest CIDR blocks covering exactly the addresses from start to end, both included.
+pub fn range_to_cidrs(start: IpAddr, end: IpAddr) -> Vec<Cidr> {
+    let family = start.family();
+    if end.family() != family || end < start {
+        return Vec::new();
+    }
+    let bits = family.bits();
+    let (mut start, end) = (start.to_bits(), end.to_bits());
+    let mut cidrs = Vec::new();
+    loop {
+        // largest block aligned at start that does not go past end
+        let mut size_bits = (start.trailing_zeros() as u8).min(bits);
+        while size_bits > 0 {
+            let last = start + (max_bits(family) >> (bits - size_bits) as u32);
+            if last <= end {
+                break;
+            }
+            size_bits -= 1;
+        }
+        let cidr = Cidr {
+            network: IpAddr::from_bits(family, start),
+            prefix: bits - size_bits,
+        };
+        cidrs.push(cidr);
+        let last = cidr.last().to_bits();
+        if last >= end {
+            return cidrs;
+        }
+        start = last + 1;
+    }
+}
+
+// Merges overlapping and adjacent blocks into the fewest blocks covering the same addresses.
+// The result is sorted, IPv4 blocks first.
+pub fn summarize(cidrs: &[Cidr]) -> Vec<Cidr> {
+    let mut ranges: Vec<(IpFamily, u128, u128)> = cidrs
+        .iter()
+        .map(|c| (c.family(), c.network.to_bits(), c.last().to_bits()))
+        .collect();
+    ranges.sort_by_key(|&(family, start, end)| (family == IpFamily::V6, start, end));
+
+    let mut merged: Vec<(IpFamily, u128, u128)> = Vec::new();
+    for (family, start, end) in ranges {
+        match merged.last_mut() {
+            Some((f, _, last)) if *f == family && start <= last.saturating_add(1) => {
+                *last = (*last).max(end);
+            }
+            _ => merged.push((family, start, end)),
+        }
+    }
+
+    merged
+        .into_iter()
+        .flat_map(|(family, start, end)| {
+            range_to_cidrs(
+                IpAddr::from_bits(family, start),
+                IpAddr::from_bits(family, end),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn ip(s: &str) -> IpAddr {
+        IpAddr::parse(s).unwrap()
+    }
+
+    fn cidr(s: &str) -> Cidr {
+        Cidr::parse(s).unwrap()
+    }
+
+    #[test]
+    fn parse_v4() {
+        assert_eq!(ip("127.0.0.1"), IpAddr::V4([127, 0, 0, 1]));
+        assert_eq!(ip("255.255.255.255").to_bits(), u32::MAX as u128);
+        assert_eq!(IpAddr::parse(""), Err(ParseError::Empty));
+        assert_eq!(IpAddr::parse("1.2.3"), Err(ParseError::WrongOctetCount(3)));
+        assert_eq!(
+            IpAddr::parse("1.2.3.256"),
+            Err(ParseError::InvalidOctet(String::from("256")))
+        );
+        assert_eq!(
+            IpAddr::parse("1.2.3.04"),
+            Err(ParseError::InvalidOctet(String::from("04")))
+        );
+        assert_eq!(
+            IpAddr::parse("1.2.+3.4"),
+            Err(ParseError::InvalidOctet(String::from("+3")))
+        );
+    }
+
+    #[test]
+    fn parse_v6() {
+        assert_eq!(ip("::1"), IpAddr::V6_LOOPBACK);
+        assert_eq!(ip("::"), IpAddr::V6([0; 8]));
+        assert_eq!(
+            ip("2001:DB8::8:800:200C:417A"),
+            IpAddr::V6([0x2001, 0xdb8, 0, 0, 0x8, 0x800, 0x200c, 0x417a])
+        );
+        assert_eq!(ip("1::"), IpAddr::V6([1, 0, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(ip("1:2:3:4:5:6:7::"), IpAddr::V6([1, 2, 3, 4, 5, 6, 7, 0]));
+        assert_eq!(
+            ip("::ffff:192.0.2.128"),
+            IpAddr::V6([0, 0, 0, 0, 0, 0xffff, 0xc000, 0x0280])
+        );
+        assert_eq!(
+            IpAddr::parse("1::2::3"),
+            Err(ParseError::MultipleCompressions)
+        );
+        assert_eq!(
+            IpAddr::parse("1:2:3:4:5:6:7"),
+            Err(ParseError::WrongGroupCount(7))
+        );
+        assert_eq!(
+            IpAddr::parse("1:2:3:4::5:6:7:8"),
+            Err(ParseError::WrongGroupCount(8))
+        );
+        assert_eq!(
+            IpAddr::parse("12345::"),
+            Err(ParseError::InvalidGroup(String::from("12345")))
+        );
+        assert_eq!(
+            IpAddr::parse("1:::2"),
+            Err(ParseError::InvalidGroup(String::from("")))
+        );
+    }
+
+    #[test]
+    fn canonical_v6() {
+        let canonical = |s: &str| ip(s).to_string();
+        assert_eq!(
+            canonical("2001:0DB8:0000:0000:0000:0000:0002:0001"),
+            "2001:db8::2:1"
+        );
+        assert_eq!(canonical("2001:db8:0:1:1:1:1:1"), "2001:db8:0:1:1:1:1:1");
+        assert_eq!(canonical("2001:0:0:1:0:0:0:1"), "2001:0:0:1::1");
+        assert_eq!(canonical("2001:db8:0:0:1:0:0:1"), "2001:db8::1:0:0:1");
+        assert_eq!(canonical("0:0:0:0:0:0:0:0"), "::");
+        assert_eq!(canonical("::ffff:c000:0280"), "::ffff:192.0.2.128");
+    }
+
+    #[test]
+    fn classification() {
+        assert_eq!(ip("127.1.2.3").class(), AddressClass::Loopback);
+        assert_eq!(ip("::1").class(), AddressClass::Loopback);
+        assert_eq!(ip("172.31.0.1").class(), AddressClass::Private);
+        assert_eq!(ip("172.32.0.1").class(), AddressClass::Global);
+        assert_eq!(ip("fd12::1").class(), AddressClass::Private);
+        assert_eq!(ip("224.0.0.251").class(), AddressClass::Multicast);
+        assert_eq!(ip("ff02::fb").class(), AddressClass::Multicast);
+        assert_eq!(ip("169.254.1.1").class(), AddressClass::LinkLocal);
+        assert_eq!(ip("fe80::1").class(), AddressClass::LinkLocal);
+        assert_eq!(ip("0.0.0.0").class(), AddressClass::Unspecified);
+        assert_eq!(ip("8.8.8.8").class(), AddressClass::Global);
+    }
+
+    #[test]
+    fn cidr_blocks() {
+        let net = cidr("192.168.0.0/16");
+        assert!(net.contains(&ip("192.168.255.1")));
+        assert!(!net.contains(&ip("192.169.0.1")));
+        assert!(!net.contains(&ip("::1")));
+        assert!(net.contains_cidr(&cidr("192.168.4.0/24")));
+        assert!(!cidr("192.168.4.0/24").contains_cidr(&net));
+        assert_eq!(net.last(), ip("192.168.255.255"));
+        assert_eq!(net.address_count(), Some(65536));
+        assert_eq!(cidr("::/0").address_count(), None);
+        assert_eq!(cidr("0.0.0.0/0").last(), ip("255.255.255.255"));
+
+        assert_eq!(Cidr::parse("10.0.0.1/8"), Err(ParseError::HostBitsSet));
+        assert_eq!(Cidr::parse("10.0.0.0"), Err(ParseError::MissingPrefix));
+        assert_eq!(
+            Cidr::parse("10.0.0.0/33"),
+            Err(ParseError::PrefixTooLong {
+                prefix: 33,
+                max: 32
+            })
+        );
+        assert_eq!(
+            Cidr::containing(ip("10.1.2.3"), 8).unwrap(),
+            cidr("10.0.0.0/8")
+        );
+        assert_eq!(cidr("2001:db8::/32").to_string(), "2001:db8::/32");
+    }
+
+    #[test]
+    fn split_and_halves() {
+        let subnets: Vec<String> = cidr("10.0.0.0/22")
+            .split(24)
+            .unwrap()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(
+            subnets,
+            vec!["10.0.0.0/24", "10.0.1.0/24", "10.0.2.0/24", "10.0.3.0/24"]
+        );
+        assert_eq!(cidr("255.255.255.0/24").split(26).unwrap().count(), 4);
+        assert_eq!(
+            cidr("::/0").halves(),
+            Some((cidr("::/1"), cidr("8000::/1")))
+        );
+        assert_eq!(cidr("10.0.0.1/32").halves(), None);
+        assert!(cidr("10.0.0.0/24").split(16).is_err());
+    }
+
+    #[test]
+    fn summarization() {
+        let blocks = [
+            cidr("10.0.1.0/24"),
+            cidr("10.0.0.0/24"),
+            cidr("10.0.2.0/23"),
+            cidr("10.0.2.128/25"),
+            cidr("2001:db8::/33"),
+            cidr("2001:db8:8000::/33"),
+            cidr("192.168.0.0/24"),
+        ];
+        let summary: Vec<String> = summarize(&blocks).iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            summary,
+            vec!["10.0.0.0/22", "192.168.0.0/24", "2001:db8::/32"]
+        );
+
+        let range: Vec<String> = range_to_cidrs(ip("10.0.0.1"), ip("10.0.0.6"))
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(
+            range,
+            vec!["10.0.0.1/32", "10.0.0.2/31", "10.0.0.4/31", "10.0.0.6/32"]
+        );
+        assert_eq!(
+            range_to_cidrs(ip("::"), ip("ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff")),
+            vec![cidr("::/0")]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn v6_matches_std(bits in any::<u128>(), zeros in any::<u8>()) {
+            // clear some groups so that "::" shows up often
+            let mut addr = IpAddr::from_bits(IpFamily::V6, bits);
+            if let IpAddr::V6(groups) = &mut addr {
+                for (i, group) in groups.iter_mut().enumerate() {
+                    if zeros & (1 << i) != 0 {
+                        *group = 0;
+                    }
+                }
+            }
+            let std_addr = std::net::Ipv6Addr::from(addr.to_bits());
+            prop_assert_eq!(addr.to_string(), std_addr.to_string());
+            prop_assert_eq!(ip(&std_addr.to_string()), addr);
+        }
+
+        #[test]
+        fn v4_matches_std(bits in any::<u32>()) {
+            let std_addr = std::net::Ipv4Addr::from(bits);
+            let addr = ip(&std_addr.to_string());
+            prop_assert_eq!(addr.to_bits(), bits as u128);
+            prop_assert_eq!(addr.to_string(), std_addr.to_string());
+        }
+
+        #[test]
+        fn summary_covers_the_same_addresses(blocks in proptest::collection::vec((any::<u8>(), 24u8..=32), 1..20)) {
+            let cidrs: Vec<Cidr> = blocks
+                .iter()
+                .map(|&(b, prefix)| Cidr::containing(IpAddr::V4([10, 0, b, b]), prefix).unwrap())
+                .collect();
+            let summary = summarize(&cidrs);
+            // membership can only change at the edges of a block
+            let probes = cidrs.iter().chain(&summary).flat_map(|c| {
+                let (first, last) = (c.network().to_bits(), c.last().to_bits());
+                [first - 1, first, last, last + 1]
+            });
+            for probe in probes {
+                let addr = IpAddr::from_bits(IpFamily::V4, probe);
+                prop_assert_eq!(
+                    cidrs.iter().any(|c| c.contains(&addr)),
+                    summary.iter().any(|c| c.contains(&addr))
+                );
+            }
+            prop_assert_eq!(summarize(&summary), summary);
+        }
+    }
+}
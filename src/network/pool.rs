@@ -0,0 +1,701 @@
+// Hands out addresses from CIDR ranges to hosts the way a DHCP server does, and keeps its
+// state in a JSON file so that a lab network survives a restart.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Cidr, IpAddr, IpFamily};
+use crate::auth::{later, Clock};
+use crate::files::write_atomically;
+
+// how many addresses allocate_checked may find in use before it gives up
+pub const MAX_PROBES: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lease {
+    pub addr: IpAddr,
+    pub host: String,
+    pub expires_at: SystemTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressStatus {
+    OutOfRange,
+    // excluded by hand, the network and broadcast address of an IPv4 range or the
+    // subnet-router anycast address of an IPv6 one
+    Excluded,
+    // found in use by someone the pool does not know about
+    Conflict,
+    Reserved(String),
+    Leased(Lease),
+    Free,
+}
+
+#[derive(Debug)]
+pub enum PoolError {
+    OutOfRange(IpAddr),
+    Excluded(IpAddr),
+    Conflict(IpAddr),
+    Reserved { addr: IpAddr, host: String },
+    Leased { addr: IpAddr, host: String },
+    Exhausted,
+    // every address probed was in use, it is likely not worth probing more
+    TooManyConflicts(usize),
+    NoLease(String),
+    Io(io::Error),
+    Corrupt(serde_json::Error),
+    // a pool file that reads but contradicts itself
+    Inconsistent(String),
+}
+
+impl Display for PoolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::OutOfRange(addr) => write!(f, "{} is outside of the pool", addr),
+            PoolError::Excluded(addr) => write!(f, "{} is excluded", addr),
+            PoolError::Conflict(addr) => write!(f, "{} is already used on the network", addr),
+            PoolError::Reserved { addr, host } => write!(f, "{} is reserved for {}", addr, host),
+            PoolError::Leased { addr, host } => write!(f, "{} is leased to {}", addr, host),
+            PoolError::Exhausted => write!(f, "no free address left"),
+            PoolError::TooManyConflicts(probed) => {
+                write!(f, "gave up after {} addresses were already in use", probed)
+            }
+            PoolError::NoLease(host) => write!(f, "{} has no lease", host),
+            PoolError::Io(e) => write!(f, "could not access the pool file: {}", e),
+            PoolError::Corrupt(e) => write!(f, "pool file is corrupt: {}", e),
+            PoolError::Inconsistent(problem) => write!(f, "pool file is inconsistent: {}", problem),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PoolError::Io(e) => Some(e),
+            PoolError::Corrupt(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PoolError {
+    fn from(e: io::Error) -> Self {
+        PoolError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for PoolError {
+    fn from(e: serde_json::Error) -> Self {
+        PoolError::Corrupt(e)
+    }
+}
+
+// Everything that is saved. Expired leases are kept until the address is handed to someone
+// else, so a returning host gets its old address back when it is still free.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PoolState {
+    ranges: Vec<Cidr>,
+    lease_time: Duration,
+    excluded: Vec<Cidr>,
+    conflicts: BTreeSet<IpAddr>,
+    reservations: BTreeMap<String, IpAddr>,
+    leases: BTreeMap<IpAddr, Lease>,
+}
+
+impl PoolState {
+    fn check(&self) -> Result<(), PoolError> {
+        let inconsistent = |problem: String| Err(PoolError::Inconsistent(problem));
+        let in_range = |addr: &IpAddr| self.ranges.iter().any(|r| r.contains(addr));
+        // like status(), a range's network and broadcast address count as excluded
+        let excluded = |addr: &IpAddr| {
+            self.excluded.iter().any(|e| e.contains(addr))
+                || self
+                    .ranges
+                    .iter()
+                    .find(|r| r.contains(addr))
+                    .is_some_and(|r| !usable(r).contains(&addr.to_bits()))
+        };
+        let mut reserved = BTreeSet::new();
+        for (host, addr) in &self.reservations {
+            if !in_range(addr) {
+                return inconsistent(format!("{} is reserved outside of the pool", addr));
+            }
+            if excluded(addr) {
+                return inconsistent(format!("{} is reserved but excluded", addr));
+            }
+            if self.conflicts.contains(addr) {
+                return inconsistent(format!("{} is reserved but in conflict", addr));
+            }
+            if !reserved.insert(addr) {
+                return inconsistent(format!("{} is reserved for two hosts", addr));
+            }
+            if host.is_empty() {
+                return inconsistent(format!("{} is reserved for a host without a name", addr));
+            }
+        }
+        let mut hosts = BTreeSet::new();
+        for (addr, lease) in &self.leases {
+            if lease.addr != *addr {
+                return inconsistent(format!(
+                    "the lease of {} is filed under {}",
+                    lease.addr, addr
+                ));
+            }
+            if !in_range(addr) {
+                return inconsistent(format!("{} is leased outside of the pool", addr));
+            }
+            if excluded(addr) {
+                return inconsistent(format!("{} is leased but excluded", addr));
+            }
+            if self.conflicts.contains(addr) {
+                return inconsistent(format!("{} is leased but in conflict", addr));
+            }
+            if let Some((host, _)) = self
+                .reservations
+                .iter()
+                .find(|(host, reserved)| *reserved == addr && **host != lease.host)
+            {
+                return inconsistent(format!(
+                    "{} is reserved for {} but leased to {}",
+                    addr, host, lease.host
+                ));
+            }
+            if !hosts.insert(&lease.host) {
+                return inconsistent(format!("{} holds two leases", lease.host));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct AddressPool<C: Clock> {
+    clock: C,
+    state: PoolState,
+}
+
+impl<C: Clock> AddressPool<C> {
+    pub fn new(clock: C, ranges: Vec<Cidr>, lease_time: Duration) -> AddressPool<C> {
+        AddressPool {
+            clock,
+            state: PoolState {
+                ranges,
+                lease_time,
+                excluded: Vec::new(),
+                conflicts: BTreeSet::new(),
+                reservations: BTreeMap::new(),
+                leases: BTreeMap::new(),
+            },
+        }
+    }
+
+    pub fn ranges(&self) -> &[Cidr] {
+        &self.state.ranges
+    }
+
+    pub fn lease_time(&self) -> Duration {
+        self.state.lease_time
+    }
+
+    // Fails when a reservation or an active lease falls inside the block. Expired leases in
+    // it are dropped, the address cannot go back to their host anyway.
+    pub fn exclude(&mut self, block: Cidr) -> Result<(), PoolError> {
+        if let Some((host, addr)) = self
+            .state
+            .reservations
+            .iter()
+            .find(|(_, addr)| block.contains(addr))
+        {
+            return Err(PoolError::Reserved {
+                addr: *addr,
+                host: host.clone(),
+            });
+        }
+        if let Some(lease) = self.active_leases().find(|l| block.contains(&l.addr)) {
+            return Err(PoolError::Leased {
+                addr: lease.addr,
+                host: lease.host.clone(),
+            });
+        }
+        self.state.leases.retain(|addr, _| !block.contains(addr));
+        self.state.excluded.push(block);
+        Ok(())
+    }
+
+    // Always gives addr to host, replacing an earlier reservation of the same host. An expired
+    // lease of another host on addr is dropped.
+    pub fn reserve(&mut self, host: &str, addr: IpAddr) -> Result<(), PoolError> {
+        match self.status(&addr) {
+            AddressStatus::OutOfRange => return Err(PoolError::OutOfRange(addr)),
+            AddressStatus::Excluded => return Err(PoolError::Excluded(addr)),
+            AddressStatus::Conflict => return Err(PoolError::Conflict(addr)),
+            AddressStatus::Reserved(other) if other != host => {
+                return Err(PoolError::Reserved { addr, host: other })
+            }
+            AddressStatus::Leased(lease) if lease.host != host => {
+                return Err(PoolError::Leased {
+                    addr,
+                    host: lease.host,
+                })
+            }
+            _ => {}
+        }
+        if matches!(self.state.leases.get(&addr), Some(lease) if lease.host != host) {
+            self.state.leases.remove(&addr);
+        }
+        self.state.reservations.insert(host.to_string(), addr);
+        Ok(())
+    }
+
+    pub fn unreserve(&mut self, host: &str) -> Option<IpAddr> {
+        self.state.reservations.remove(host)
+    }
+
+    pub fn status(&self, addr: &IpAddr) -> AddressStatus {
+        let Some(range) = self.state.ranges.iter().find(|r| r.contains(addr)) else {
+            return AddressStatus::OutOfRange;
+        };
+        if !usable(range).contains(&addr.to_bits())
+            || self.state.excluded.iter().any(|e| e.contains(addr))
+        {
+            return AddressStatus::Excluded;
+        }
+        if self.state.conflicts.contains(addr) {
+            return AddressStatus::Conflict;
+        }
+        if let Some((host, _)) = self.state.reservations.iter().find(|(_, a)| *a == addr) {
+            return AddressStatus::Reserved(host.clone());
+        }
+        match self.state.leases.get(addr) {
+            Some(lease) if self.is_active(lease) => AddressStatus::Leased(lease.clone()),
+            _ => AddressStatus::Free,
+        }
+    }
+
+    pub fn allocate(&mut self, host: &str) -> Result<Lease, PoolError> {
+        self.allocate_checked(host, |_| false)
+    }
+
+    // Like allocate, but asks in_use (a ping or an ARP probe, say) before handing out a new
+    // address. Addresses that answer are remembered as conflicts and skipped from then on,
+    // after MAX_PROBES of them in one call it gives up with TooManyConflicts. A reserved
+    // address is not probed, the host may already be using it.
+    pub fn allocate_checked(
+        &mut self,
+        host: &str,
+        mut in_use: impl FnMut(&IpAddr) -> bool,
+    ) -> Result<Lease, PoolError> {
+        if let Some(&addr) = self.state.reservations.get(host) {
+            return Ok(self.grant(host, addr));
+        }
+        if let Some(previous) = self.state.leases.values().find(|l| l.host == host) {
+            let addr = previous.addr;
+            if self.status(&addr) == AddressStatus::Free || self.is_active(previous) {
+                return Ok(self.grant(host, addr));
+            }
+        }
+
+        let mut probed = 0;
+        for range in self.state.ranges.clone() {
+            let usable = usable(&range);
+            let mut bits = *usable.start();
+            while bits <= *usable.end() {
+                let mut addr = IpAddr::from_bits(range.family(), bits);
+                // jump over excluded blocks instead of walking them, they can be huge in IPv6
+                if let Some(block) = self.state.excluded.iter().find(|e| e.contains(&addr)) {
+                    addr = block.last();
+                } else if self.status(&addr) == AddressStatus::Free {
+                    if !in_use(&addr) {
+                        return Ok(self.grant(host, addr));
+                    }
+                    // a free address can still have an expired lease on file
+                    self.state.leases.remove(&addr);
+                    self.state.conflicts.insert(addr);
+                    probed += 1;
+                    if probed == MAX_PROBES {
+                        return Err(PoolError::TooManyConflicts(probed));
+                    }
+                }
+                match addr.to_bits().checked_add(1) {
+                    Some(next) => bits = next,
+                    None => break,
+                }
+            }
+        }
+        Err(PoolError::Exhausted)
+    }
+
+    // Extends an active lease by a full lease time.
+    pub fn renew(&mut self, host: &str) -> Result<Lease, PoolError> {
+        match self.lease_of(host) {
+            Some(lease) => {
+                let addr = lease.addr;
+                Ok(self.grant(host, addr))
+            }
+            None => Err(PoolError::NoLease(host.to_string())),
+        }
+    }
+
+    pub fn release(&mut self, host: &str) -> Option<Lease> {
+        let addr = self.lease_of(host)?.addr;
+        self.state.leases.remove(&addr)
+    }
+
+    // only an active lease counts
+    pub fn lease_of(&self, host: &str) -> Option<&Lease> {
+        self.active_leases().find(|l| l.host == host)
+    }
+
+    pub fn leases(&self) -> impl Iterator<Item = &Lease> {
+        self.active_leases()
+    }
+
+    // Drops expired leases for good and returns them.
+    pub fn expire(&mut self) -> Vec<Lease> {
+        let now = self.clock.now();
+        let (expired, active) = std::mem::take(&mut self.state.leases)
+            .into_iter()
+            .partition(|(_, lease)| lease.expires_at <= now);
+        self.state.leases = active;
+        expired.into_values().collect()
+    }
+
+    pub fn conflicts(&self) -> impl Iterator<Item = &IpAddr> {
+        self.state.conflicts.iter()
+    }
+
+    // for when the unknown device is gone
+    pub fn clear_conflict(&mut self, addr: &IpAddr) -> bool {
+        self.state.conflicts.remove(addr)
+    }
+
+    // Writes to a temporary file next to path first, so a crash cannot leave half a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PoolError> {
+        write_atomically(path.as_ref(), &serde_json::to_vec_pretty(&self.state)?)?;
+        Ok(())
+    }
+
+    // Refuses a file with leases or reservations outside of the usable addresses of its
+    // ranges, in an excluded block or on a conflict, an address reserved twice, a lease on
+    // another host's reservation or a host with two leases.
+    pub fn load(path: impl AsRef<Path>, clock: C) -> Result<AddressPool<C>, PoolError> {
+        let state: PoolState = serde_json::from_slice(&fs::read(path)?)?;
+        state.check()?;
+        Ok(AddressPool { clock, state })
+    }
+
+    fn is_active(&self, lease: &Lease) -> bool {
+        lease.expires_at > self.clock.now()
+    }
+
+    fn active_leases(&self) -> impl Iterator<Item = &Lease> {
+        let now = self.clock.now();
+        self.state
+            .leases
+            .values()
+            .filter(move |lease| lease.expires_at > now)
+    }
+
+    fn grant(&mut self, host: &str, addr: IpAddr) -> Lease {
+        self.state.leases.retain(|_, lease| lease.host != host);
+        let lease = Lease {
+            addr,
+            host: host.to_string(),
+            expires_at: later(self.clock.now(), self.state.lease_time),
+        };
+        self.state.leases.insert(addr, lease.clone());
+        lease
+    }
+}
+
+// The addresses of a range that can be handed out, as numbers. IPv4 ranges lose their
+// network and broadcast address, except for /31 and /32 which have none. IPv6 ranges lose
+// their first address, the subnet-router anycast address, except for /127 and /128.
+fn usable(range: &Cidr) -> std::ops::RangeInclusive<u128> {
+    let (first, last) = (range.network().to_bits(), range.last().to_bits());
+    match range.family() {
+        IpFamily::V4 if range.prefix() <= 30 => first + 1..=last - 1,
+        IpFamily::V6 if range.prefix() <= 126 => first + 1..=last,
+        _ => first..=last,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::FakeClock;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn pool(range: &str) -> (FakeClock, AddressPool<FakeClock>) {
+        let clock = FakeClock::default();
+        let pool = AddressPool::new(clock.clone(), vec![range.parse().unwrap()], HOUR);
+        (clock, pool)
+    }
+
+    #[test]
+    fn allocates_in_order_and_skips_network_and_broadcast() {
+        let (_, mut pool) = pool("10.0.0.0/30");
+        assert_eq!(pool.allocate("a").unwrap().addr, ip("10.0.0.1"));
+        assert_eq!(pool.allocate("b").unwrap().addr, ip("10.0.0.2"));
+        assert!(matches!(pool.allocate("c"), Err(PoolError::Exhausted)));
+        assert_eq!(pool.status(&ip("10.0.0.3")), AddressStatus::Excluded);
+        assert_eq!(pool.status(&ip("10.0.1.1")), AddressStatus::OutOfRange);
+        // asking again keeps the same address
+        assert_eq!(pool.allocate("a").unwrap().addr, ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn leases_expire_and_are_reused() {
+        let (clock, mut pool) = pool("10.0.0.0/30");
+        pool.allocate("a").unwrap();
+        pool.allocate("b").unwrap();
+        clock.advance(HOUR / 2);
+        pool.renew("a").unwrap();
+        clock.advance(HOUR / 2);
+
+        assert!(pool.lease_of("b").is_none());
+        assert!(matches!(pool.renew("b"), Err(PoolError::NoLease(_))));
+        assert_eq!(pool.allocate("c").unwrap().addr, ip("10.0.0.2"));
+        assert_eq!(pool.leases().count(), 2);
+        assert_eq!(pool.release("a").unwrap().addr, ip("10.0.0.1"));
+        assert_eq!(pool.status(&ip("10.0.0.1")), AddressStatus::Free);
+    }
+
+    #[test]
+    fn returning_host_gets_its_old_address() {
+        let (clock, mut pool) = pool("10.0.0.0/29");
+        pool.allocate("a").unwrap();
+        pool.allocate("b").unwrap();
+        clock.advance(HOUR * 2);
+        assert_eq!(pool.allocate("b").unwrap().addr, ip("10.0.0.2"));
+        assert_eq!(pool.expire().len(), 1);
+    }
+
+    #[test]
+    fn reservations_and_exclusions() {
+        let (_, mut pool) = pool("10.0.0.0/29");
+        pool.exclude("10.0.0.0/31".parse().unwrap()).unwrap();
+        pool.reserve("printer", ip("10.0.0.2")).unwrap();
+        assert_eq!(pool.allocate("a").unwrap().addr, ip("10.0.0.3"));
+        assert_eq!(pool.allocate("printer").unwrap().addr, ip("10.0.0.2"));
+
+        assert!(matches!(
+            pool.reserve("b", ip("10.0.0.2")),
+            Err(PoolError::Reserved { .. })
+        ));
+        assert!(matches!(
+            pool.reserve("b", ip("10.0.0.3")),
+            Err(PoolError::Leased { .. })
+        ));
+        assert!(matches!(
+            pool.reserve("b", ip("10.0.0.1")),
+            Err(PoolError::Excluded(_))
+        ));
+        assert!(matches!(
+            pool.exclude("10.0.0.2/32".parse().unwrap()),
+            Err(PoolError::Reserved { .. })
+        ));
+        assert!(matches!(
+            pool.reserve("b", ip("192.168.0.1")),
+            Err(PoolError::OutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn addresses_in_use_are_skipped() {
+        let (_, mut pool) = pool("10.0.0.0/29");
+        let squatter = ip("10.0.0.1");
+        let lease = pool
+            .allocate_checked("a", |addr| *addr == squatter)
+            .unwrap();
+        assert_eq!(lease.addr, ip("10.0.0.2"));
+        assert_eq!(pool.status(&squatter), AddressStatus::Conflict);
+        assert_eq!(pool.allocate("b").unwrap().addr, ip("10.0.0.3"));
+
+        assert!(pool.clear_conflict(&squatter));
+        assert_eq!(pool.allocate("c").unwrap().addr, squatter);
+    }
+
+    #[test]
+    fn probing_gives_up() {
+        let (_, mut pool) = pool("10.0.0.0/16");
+        let mut probes = 0;
+        let error = pool
+            .allocate_checked("a", |_| {
+                probes += 1;
+                true
+            })
+            .unwrap_err();
+        assert!(matches!(error, PoolError::TooManyConflicts(MAX_PROBES)));
+        assert_eq!(probes, MAX_PROBES);
+        assert_eq!(pool.conflicts().count(), MAX_PROBES);
+        // the next call starts after the conflicts it found
+        let lease = pool.allocate_checked("a", |_| false).unwrap();
+        assert_eq!(
+            lease.addr.to_bits(),
+            ip("10.0.0.1").to_bits() + MAX_PROBES as u128
+        );
+    }
+
+    #[test]
+    fn excluded_blocks_are_skipped_not_walked() {
+        let (_, mut pool) = pool("2001:db8::/64");
+        pool.exclude("2001:db8::/65".parse().unwrap()).unwrap();
+        assert_eq!(pool.allocate("a").unwrap().addr, ip("2001:db8:0:0:8000::"));
+    }
+
+    #[test]
+    fn pools_that_were_changed_still_load() {
+        let (clock, mut pool) = pool("10.0.0.0/29");
+        pool.allocate("a").unwrap();
+        pool.allocate("b").unwrap();
+        pool.allocate("c").unwrap();
+        clock.advance(HOUR);
+        // the leases have run out but are still on file
+        pool.exclude("10.0.0.1/32".parse().unwrap()).unwrap();
+        pool.reserve("printer", ip("10.0.0.2")).unwrap();
+        let lease = pool
+            .allocate_checked("d", |addr| *addr == ip("10.0.0.3"))
+            .unwrap();
+        assert_eq!(pool.status(&ip("10.0.0.3")), AddressStatus::Conflict);
+        assert_eq!(pool.state.leases.values().collect::<Vec<_>>(), [&lease]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pool.json");
+        pool.save(&path).unwrap();
+        assert!(AddressPool::load(&path, clock).is_ok());
+    }
+
+    #[test]
+    fn huge_lease_times_do_not_overflow() {
+        let clock = FakeClock::default();
+        let range = "10.0.0.0/29".parse().unwrap();
+        let mut pool = AddressPool::new(clock.clone(), vec![range], Duration::MAX);
+        let lease = pool.allocate("a").unwrap();
+        assert!(lease.expires_at > clock.now() + Duration::from_secs(1 << 40));
+        assert_eq!(pool.renew("a").unwrap().addr, lease.addr);
+    }
+
+    #[test]
+    fn state_survives_save_and_load() {
+        let (clock, mut pool) = pool("10.0.0.0/29");
+        pool.exclude("10.0.0.6/32".parse().unwrap()).unwrap();
+        pool.reserve("printer", ip("10.0.0.5")).unwrap();
+        pool.allocate("a").unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pool.json");
+        pool.save(&path).unwrap();
+        let mut loaded = AddressPool::load(&path, clock.clone()).unwrap();
+        assert_eq!(loaded.lease_of("a"), pool.lease_of("a"));
+        assert_eq!(
+            loaded.status(&ip("10.0.0.5")),
+            AddressStatus::Reserved(String::from("printer"))
+        );
+        assert_eq!(loaded.status(&ip("10.0.0.6")), AddressStatus::Excluded);
+        assert_eq!(loaded.allocate("b").unwrap().addr, ip("10.0.0.2"));
+
+        fs::write(&path, "{").unwrap();
+        assert!(matches!(
+            AddressPool::load(&path, clock.clone()),
+            Err(PoolError::Corrupt(_))
+        ));
+        assert!(matches!(
+            AddressPool::load(dir.path().join("missing.json"), clock.clone()),
+            Err(PoolError::Io(_))
+        ));
+        let names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["pool.json"]);
+    }
+
+    #[test]
+    fn contradicting_files_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pool.json");
+        let lease = |addr: &str, host: &str| {
+            format!(
+                r#""{}": {{"addr": "{}", "host": "{}",
+                    "expires_at": {{"secs_since_epoch": 0, "nanos_since_epoch": 0}}}}"#,
+                addr, addr, host
+            )
+        };
+        let file = |excluded: &str, reservations: &str, leases: &str| {
+            format!(
+                r#"{{"ranges": ["10.0.0.0/29"], "lease_time": {{"secs": 3600, "nanos": 0}},
+                    "excluded": [{}], "conflicts": [], "reservations": {{{}}}, "leases": {{{}}}}}"#,
+                excluded, reservations, leases
+            )
+        };
+        let conflict = (r#""conflicts": []"#, r#""conflicts": ["10.0.0.3"]"#);
+        let load = |json: String| {
+            fs::write(&path, json).unwrap();
+            AddressPool::load(&path, FakeClock::default())
+        };
+
+        let good = file(
+            r#""10.0.0.6/32""#,
+            r#""printer": "10.0.0.5""#,
+            &lease("10.0.0.1", "a"),
+        );
+        assert_eq!(load(good).unwrap().leases().count(), 0);
+        for json in [
+            file("", r#""printer": "10.0.1.5""#, ""),
+            file("", r#""printer": "10.0.0.5", "scanner": "10.0.0.5""#, ""),
+            file("", r#""": "10.0.0.5""#, ""),
+            file(r#""10.0.0.4/30""#, r#""printer": "10.0.0.5""#, ""),
+            file("", "", &lease("10.0.1.1", "a")),
+            file(
+                "",
+                "",
+                &lease("10.0.0.1", "a").replacen("10.0.0.1", "10.0.0.2", 1),
+            ),
+            file(
+                "",
+                "",
+                &format!("{}, {}", lease("10.0.0.1", "a"), lease("10.0.0.2", "a")),
+            ),
+            file(r#""10.0.0.0/31""#, "", &lease("10.0.0.1", "a")),
+            file("", r#""printer": "10.0.0.1""#, &lease("10.0.0.1", "a")),
+            // network and broadcast address
+            file("", r#""printer": "10.0.0.0""#, ""),
+            file("", r#""printer": "10.0.0.7""#, ""),
+            file("", "", &lease("10.0.0.7", "a")),
+            file("", r#""printer": "10.0.0.3""#, "").replace(conflict.0, conflict.1),
+            file("", "", &lease("10.0.0.3", "a")).replace(conflict.0, conflict.1),
+        ] {
+            assert!(
+                matches!(load(json.clone()), Err(PoolError::Inconsistent(_))),
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn ipv6_ranges() {
+        let (_, mut pool) = pool("2001:db8::/126");
+        assert_eq!(pool.status(&ip("2001:db8::")), AddressStatus::Excluded);
+        assert_eq!(pool.allocate("a").unwrap().addr, ip("2001:db8::1"));
+        assert_eq!(pool.leases().count(), 1);
+    }
+
+    // point-to-point links have no anycast address
+    #[test]
+    fn ipv6_point_to_point() {
+        let (_, mut pool) = pool("2001:db8::/127");
+        assert_eq!(pool.allocate("a").unwrap().addr, ip("2001:db8::"));
+        assert_eq!(pool.allocate("b").unwrap().addr, ip("2001:db8::1"));
+    }
+}
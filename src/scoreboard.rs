@@ -0,0 +1,504 @@
+// https://doc.rust-lang.org/book/ch08-03-hash-maps.html#overwriting-a-value
+// The Blue and Yellow scores from the hash maps chapter kept as a scoreboard: every change
+// is an event with a reason and a time, so the board can be ranked over all time or over
+// a recent window, and it can be saved to a file and loaded back.
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Clock;
+use crate::files::write_atomically;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoreEvent {
+    pub team: String,
+    pub points: i64,
+    pub reason: String,
+    pub at: SystemTime,
+}
+
+// Teams with the same score share a rank and the next rank is skipped, 1, 1, 3.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Standing {
+    pub rank: usize,
+    pub team: String,
+    pub score: i64,
+}
+
+#[derive(Debug)]
+pub enum ScoreError {
+    EmptyName,
+    DuplicateTeam(String),
+    UnknownTeam(String),
+    Overflow(String),
+    Io(io::Error),
+    Corrupt(serde_json::Error),
+    // a snapshot that reads but does not add up
+    Inconsistent(String),
+}
+
+impl Display for ScoreError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ScoreError::EmptyName => write!(f, "a team needs a name"),
+            ScoreError::DuplicateTeam(team) => write!(f, "there already is a team {}", team),
+            ScoreError::UnknownTeam(team) => write!(f, "there is no team {}", team),
+            ScoreError::Overflow(team) => write!(f, "the score of {} is out of range", team),
+            ScoreError::Io(e) => write!(f, "could not access the snapshot: {}", e),
+            ScoreError::Corrupt(e) => write!(f, "snapshot is corrupt: {}", e),
+            ScoreError::Inconsistent(problem) => write!(f, "snapshot is inconsistent: {}", problem),
+        }
+    }
+}
+
+impl std::error::Error for ScoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScoreError::Io(e) => Some(e),
+            ScoreError::Corrupt(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScoreError {
+    fn from(e: io::Error) -> Self {
+        ScoreError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ScoreError {
+    fn from(e: serde_json::Error) -> Self {
+        ScoreError::Corrupt(e)
+    }
+}
+
+// Totals are kept apart from the events so old events can be dropped without changing them,
+// the points of dropped events are carried so every total is still its carried points plus
+// its events.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct BoardState {
+    totals: BTreeMap<String, i64>,
+    #[serde(default)]
+    carried: BTreeMap<String, i64>,
+    events: Vec<ScoreEvent>,
+}
+
+impl BoardState {
+    // What the carried points and events of each team add up to, wide enough that any
+    // number of i64 points fits. Points for a team the board does not have are an error.
+    fn sums(&self) -> Result<BTreeMap<&str, i128>, ScoreError> {
+        let mut sums: BTreeMap<&str, i128> =
+            self.totals.keys().map(|team| (team.as_str(), 0)).collect();
+        let carried = self.carried.iter().map(|(team, points)| (team, *points));
+        let scored = self.events.iter().map(|event| (&event.team, event.points));
+        for (team, points) in carried.chain(scored) {
+            let sum = sums.get_mut(team.as_str()).ok_or_else(|| {
+                ScoreError::Inconsistent(format!("there are points for unknown team {}", team))
+            })?;
+            *sum += i128::from(points);
+        }
+        Ok(sums)
+    }
+}
+
+pub struct Scoreboard<C: Clock> {
+    clock: C,
+    state: BoardState,
+}
+
+impl<C: Clock> Scoreboard<C> {
+    pub fn new(clock: C) -> Scoreboard<C> {
+        Scoreboard {
+            clock,
+            state: BoardState::default(),
+        }
+    }
+
+    pub fn add_team(&mut self, team: &str) -> Result<(), ScoreError> {
+        if team.trim().is_empty() {
+            return Err(ScoreError::EmptyName);
+        }
+        if self.state.totals.contains_key(team) {
+            return Err(ScoreError::DuplicateTeam(team.to_string()));
+        }
+        self.state.totals.insert(team.to_string(), 0);
+        Ok(())
+    }
+
+    // the team and its events, returns its last score
+    pub fn remove_team(&mut self, team: &str) -> Result<i64, ScoreError> {
+        let total = self
+            .state
+            .totals
+            .remove(team)
+            .ok_or_else(|| ScoreError::UnknownTeam(team.to_string()))?;
+        self.state.carried.remove(team);
+        self.state.events.retain(|event| event.team != team);
+        Ok(total)
+    }
+
+    // in name order
+    pub fn teams(&self) -> impl Iterator<Item = &str> {
+        self.state.totals.keys().map(String::as_str)
+    }
+
+    pub fn total(&self, team: &str) -> Option<i64> {
+        self.state.totals.get(team).copied()
+    }
+
+    // Points can be negative for penalties.
+    pub fn score(
+        &mut self,
+        team: &str,
+        points: i64,
+        reason: &str,
+    ) -> Result<&ScoreEvent, ScoreError> {
+        let total = self
+            .state
+            .totals
+            .get_mut(team)
+            .ok_or_else(|| ScoreError::UnknownTeam(team.to_string()))?;
+        *total = total
+            .checked_add(points)
+            .ok_or_else(|| ScoreError::Overflow(team.to_string()))?;
+        self.state.events.push(ScoreEvent {
+            team: team.to_string(),
+            points,
+            reason: reason.to_string(),
+            at: self.clock.now(),
+        });
+        Ok(&self.state.events[self.state.events.len() - 1])
+    }
+
+    // Overwrites the score like insert does on the map, recorded as the difference.
+    pub fn set_score(
+        &mut self,
+        team: &str,
+        score: i64,
+        reason: &str,
+    ) -> Result<&ScoreEvent, ScoreError> {
+        let total = self
+            .total(team)
+            .ok_or_else(|| ScoreError::UnknownTeam(team.to_string()))?;
+        let points = score
+            .checked_sub(total)
+            .ok_or_else(|| ScoreError::Overflow(team.to_string()))?;
+        self.score(team, points, reason)
+    }
+
+    // oldest first
+    pub fn events(&self) -> &[ScoreEvent] {
+        &self.state.events
+    }
+
+    pub fn history<'a>(&'a self, team: &'a str) -> impl Iterator<Item = &'a ScoreEvent> {
+        self.state
+            .events
+            .iter()
+            .filter(move |event| event.team == team)
+    }
+
+    pub fn leaderboard(&self) -> Vec<Standing> {
+        rank(self.state.totals.clone())
+    }
+
+    // Only the points scored within the last stretch of time, teams without any have 0.
+    // The points of a window can add up past i64 even when the totals do not.
+    pub fn leaderboard_for(&self, last: Duration) -> Result<Vec<Standing>, ScoreError> {
+        let since = self.since(last);
+        let mut scores: BTreeMap<String, i64> =
+            self.teams().map(|team| (team.to_string(), 0)).collect();
+        for event in self.state.events.iter().filter(|event| event.at >= since) {
+            if let Some(score) = scores.get_mut(&event.team) {
+                *score = score
+                    .checked_add(event.points)
+                    .ok_or_else(|| ScoreError::Overflow(event.team.clone()))?;
+            }
+        }
+        Ok(rank(scores))
+    }
+
+    // Drops the events from the oldest up to the first one within the given age, their
+    // points are carried so totals stay as they are. Returns how many went.
+    pub fn forget_older_than(&mut self, age: Duration) -> usize {
+        let since = self.since(age);
+        let old = self
+            .state
+            .events
+            .iter()
+            .take_while(|event| event.at < since)
+            .count();
+        for event in self.state.events.drain(..old) {
+            // each step lands on a total the team once had, so it stays in range
+            let carried = self.state.carried.entry(event.team).or_insert(0);
+            *carried = carried.saturating_add(event.points);
+        }
+        old
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ScoreError> {
+        write_atomically(path.as_ref(), &serde_json::to_vec_pretty(&self.state)?)?;
+        Ok(())
+    }
+
+    // Refuses a snapshot with a team without a name, totals that do not match the carried
+    // points and events, or points for teams it does not have.
+    pub fn load(path: impl AsRef<Path>, clock: C) -> Result<Scoreboard<C>, ScoreError> {
+        let state: BoardState = serde_json::from_slice(&fs::read(path)?)?;
+        if state.totals.keys().any(|team| team.trim().is_empty()) {
+            return Err(ScoreError::Inconsistent("a team has no name".to_string()));
+        }
+        for (team, sum) in state.sums()? {
+            let total = state.totals[team];
+            if sum != i128::from(total) {
+                return Err(ScoreError::Inconsistent(format!(
+                    "the total of {} is {} but its points add up to {}",
+                    team, total, sum
+                )));
+            }
+        }
+        Ok(Scoreboard { clock, state })
+    }
+
+    fn since(&self, last: Duration) -> SystemTime {
+        let now = self.clock.now();
+        now.checked_sub(last).unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+}
+
+// highest first, names in order within a tie
+fn rank(scores: BTreeMap<String, i64>) -> Vec<Standing> {
+    let mut scores: Vec<(String, i64)> = scores.into_iter().collect();
+    scores.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let mut standings: Vec<Standing> = Vec::with_capacity(scores.len());
+    for (index, (team, score)) in scores.into_iter().enumerate() {
+        let rank = match standings.last() {
+            Some(previous) if previous.score == score => previous.rank,
+            _ => index + 1,
+        };
+        standings.push(Standing { rank, team, score });
+    }
+    standings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::FakeClock;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn board(clock: &FakeClock) -> Scoreboard<FakeClock> {
+        let mut board = Scoreboard::new(clock.clone());
+        for team in ["Blue", "Yellow", "Red"] {
+            board.add_team(team).unwrap();
+        }
+        board
+    }
+
+    fn ranks(standings: &[Standing]) -> Vec<(usize, &str, i64)> {
+        standings
+            .iter()
+            .map(|s| (s.rank, s.team.as_str(), s.score))
+            .collect()
+    }
+
+    #[test]
+    fn scores_and_overwrites() {
+        let clock = FakeClock::default();
+        let mut board = board(&clock);
+        board.score("Blue", 10, "goal").unwrap();
+        board.score("Yellow", 50, "goal").unwrap();
+        let event = board.set_score("Blue", 25, "recount").unwrap();
+        assert_eq!(event.points, 15);
+        assert_eq!(event.reason, "recount");
+        assert_eq!(board.total("Blue"), Some(25));
+        board.score("Yellow", -5, "foul").unwrap();
+        assert_eq!(board.total("Yellow"), Some(45));
+        assert_eq!(
+            board.history("Blue").map(|e| e.points).collect::<Vec<_>>(),
+            [10, 15]
+        );
+
+        assert!(matches!(
+            board.score("Green", 1, "goal"),
+            Err(ScoreError::UnknownTeam(_))
+        ));
+        assert!(matches!(
+            board.add_team("Blue"),
+            Err(ScoreError::DuplicateTeam(_))
+        ));
+        assert!(matches!(board.add_team(" "), Err(ScoreError::EmptyName)));
+        board.score("Red", i64::MAX, "cheat").unwrap();
+        assert!(matches!(
+            board.score("Red", 1, "more"),
+            Err(ScoreError::Overflow(_))
+        ));
+        assert_eq!(board.total("Red"), Some(i64::MAX));
+    }
+
+    #[test]
+    fn ties_share_a_rank() {
+        let clock = FakeClock::default();
+        let mut board = board(&clock);
+        board.add_team("Green").unwrap();
+        board.score("Yellow", 30, "goal").unwrap();
+        board.score("Red", 30, "goal").unwrap();
+        board.score("Green", 10, "goal").unwrap();
+        assert_eq!(
+            ranks(&board.leaderboard()),
+            [
+                (1, "Red", 30),
+                (1, "Yellow", 30),
+                (3, "Green", 10),
+                (4, "Blue", 0)
+            ]
+        );
+
+        assert_eq!(board.remove_team("Red").unwrap(), 30);
+        assert_eq!(
+            board.teams().collect::<Vec<_>>(),
+            ["Blue", "Green", "Yellow"]
+        );
+        assert!(board.history("Red").next().is_none());
+    }
+
+    #[test]
+    fn rolling_windows() {
+        let clock = FakeClock::default();
+        clock.advance(Duration::from_secs(24 * 3600));
+        let mut board = board(&clock);
+        board.score("Blue", 100, "early lead").unwrap();
+        clock.advance(90 * MINUTE);
+        board.score("Yellow", 20, "goal").unwrap();
+        clock.advance(30 * MINUTE);
+        board.score("Yellow", 20, "goal").unwrap();
+        board.score("Blue", 5, "goal").unwrap();
+
+        assert_eq!(
+            ranks(&board.leaderboard_for(60 * MINUTE).unwrap()),
+            [(1, "Yellow", 40), (2, "Blue", 5), (3, "Red", 0)]
+        );
+        assert_eq!(
+            ranks(&board.leaderboard_for(10 * MINUTE).unwrap()),
+            [(1, "Yellow", 20), (2, "Blue", 5), (3, "Red", 0)]
+        );
+        assert_eq!(ranks(&board.leaderboard())[0], (1, "Blue", 105));
+
+        assert_eq!(board.forget_older_than(60 * MINUTE), 1);
+        assert_eq!(board.events().len(), 3);
+        assert_eq!(board.total("Blue"), Some(105));
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scores.json");
+        let clock = FakeClock::default();
+        let mut board = board(&clock);
+        board.score("Blue", 10, "goal").unwrap();
+        clock.advance(MINUTE);
+        board.score("Yellow", 50, "goal").unwrap();
+        board.save(&path).unwrap();
+
+        let restored = Scoreboard::load(&path, clock.clone()).unwrap();
+        assert_eq!(restored.leaderboard(), board.leaderboard());
+        assert_eq!(restored.events(), board.events());
+        assert_eq!(
+            restored.leaderboard_for(MINUTE).unwrap(),
+            board.leaderboard_for(MINUTE).unwrap()
+        );
+
+        assert!(matches!(
+            Scoreboard::load(dir.path().join("missing.json"), clock.clone()),
+            Err(ScoreError::Io(_))
+        ));
+        fs::write(&path, "{\"totals\": 3}").unwrap();
+        assert!(matches!(
+            Scoreboard::load(&path, clock),
+            Err(ScoreError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn forgotten_points_are_carried() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scores.json");
+        let clock = FakeClock::default();
+        let mut board = board(&clock);
+        board.score("Blue", 100, "early lead").unwrap();
+        board.score("Red", -10, "foul").unwrap();
+        clock.advance(90 * MINUTE);
+        board.score("Blue", 10, "goal").unwrap();
+        board.score("Red", i64::MAX, "comeback").unwrap();
+        assert_eq!(
+            ranks(&board.leaderboard_for(60 * MINUTE).unwrap()),
+            [(1, "Red", i64::MAX), (2, "Blue", 10), (3, "Yellow", 0)]
+        );
+        // the total fits, the points of the window alone do not
+        board.score("Red", 10, "goal").unwrap();
+        assert_eq!(board.total("Red"), Some(i64::MAX));
+        assert!(matches!(
+            board.leaderboard_for(60 * MINUTE),
+            Err(ScoreError::Overflow(team)) if team == "Red"
+        ));
+
+        assert_eq!(board.forget_older_than(60 * MINUTE), 2);
+        assert_eq!(board.total("Blue"), Some(110));
+        board.save(&path).unwrap();
+        let restored = Scoreboard::load(&path, clock.clone()).unwrap();
+        assert_eq!(restored.leaderboard(), board.leaderboard());
+        assert_eq!(restored.events(), board.events());
+        let names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["scores.json"]);
+    }
+
+    #[test]
+    fn snapshots_that_do_not_add_up_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scores.json");
+        let clock = FakeClock::default();
+        let mut board = board(&clock);
+        board.score("Blue", 10, "goal").unwrap();
+        board.save(&path).unwrap();
+        let saved: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+
+        // written before points were carried
+        let mut old = saved.clone();
+        old.as_object_mut().unwrap().remove("carried");
+        fs::write(&path, old.to_string()).unwrap();
+        let restored = Scoreboard::load(&path, clock.clone()).unwrap();
+        assert_eq!(restored.total("Blue"), Some(10));
+
+        let tampering: [fn(&mut serde_json::Value); 5] = [
+            |snapshot| snapshot["totals"]["Blue"] = 11.into(),
+            |snapshot| snapshot["events"][0]["team"] = "Green".into(),
+            |snapshot| snapshot["carried"]["Green"] = 0.into(),
+            |snapshot| snapshot["totals"][" "] = 0.into(),
+            // adds up to more than any total can hold
+            |snapshot| snapshot["carried"]["Blue"] = i64::MAX.into(),
+        ];
+        for tamper in tampering {
+            let mut snapshot = saved.clone();
+            tamper(&mut snapshot);
+            fs::write(&path, snapshot.to_string()).unwrap();
+            assert!(
+                matches!(
+                    Scoreboard::load(&path, clock.clone()),
+                    Err(ScoreError::Inconsistent(_))
+                ),
+                "{}",
+                snapshot
+            );
+        }
+    }
+}
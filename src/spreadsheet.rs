@@ -0,0 +1,626 @@
+// https://doc.rust-lang.org/book/ch08-01-vectors.html#using-an-enum-to-store-multiple-types
+// The SpreadsheetCell enum from the vectors example as the core of a spreadsheet: a grid of
+// cells addressed like A1, formulas that read other cells, and recalculation of only the
+// cells that depend on a change.
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+pub mod csv;
+pub mod formula;
+
+use formula::{Cells, Expr, FormulaError};
+
+// 16384 columns (A to XFD) and 1048576 rows, like the big spreadsheet programs.
+pub const MAX_COLUMNS: u32 = 16_384;
+pub const MAX_ROWS: u32 = 1_048_576;
+
+// A cell position, both counted from 0. Ordered row by row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CellRef {
+    pub row: u32,
+    pub column: u32,
+}
+
+impl CellRef {
+    pub fn new(column: u32, row: u32) -> CellRef {
+        CellRef { row, column }
+    }
+
+    // column letters, 0 is A, 25 is Z and 26 is AA
+    pub fn column_name(column: u32) -> String {
+        let mut name = Vec::new();
+        // wider, so the last column has a name too
+        let mut n = u64::from(column) + 1;
+        while n > 0 {
+            let digit = (n - 1) % 26;
+            name.push(b'A' + digit as u8);
+            n = (n - 1) / 26;
+        }
+        name.reverse();
+        String::from_utf8(name).unwrap_or_default()
+    }
+
+    // All cells of the rectangle between two corners, in any order, row by row.
+    pub fn range(a: CellRef, b: CellRef) -> impl Iterator<Item = CellRef> {
+        let (top, bottom) = (a.row.min(b.row), a.row.max(b.row));
+        let (left, right) = (a.column.min(b.column), a.column.max(b.column));
+        (top..=bottom)
+            .flat_map(move |row| (left..=right).map(move |column| CellRef { row, column }))
+    }
+}
+
+impl Display for CellRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            CellRef::column_name(self.column),
+            u64::from(self.row) + 1
+        )
+    }
+}
+
+impl FromStr for CellRef {
+    type Err = SheetError;
+
+    // letters then a row number from 1, case does not matter
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SheetError::InvalidReference(s.to_string());
+        let split = s
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .ok_or_else(invalid)?;
+        let (letters, digits) = s.split_at(split);
+        if letters.is_empty()
+            || !digits.bytes().all(|b| b.is_ascii_digit())
+            || digits.starts_with('0')
+        {
+            return Err(invalid());
+        }
+        let mut column: u32 = 0;
+        for letter in letters.bytes() {
+            let value = (letter.to_ascii_uppercase() - b'A') as u32 + 1;
+            column = column
+                .checked_mul(26)
+                .and_then(|c| c.checked_add(value))
+                .filter(|&c| c <= MAX_COLUMNS)
+                .ok_or_else(invalid)?;
+        }
+        let row: u32 = digits.parse().map_err(|_| invalid())?;
+        if row > MAX_ROWS {
+            return Err(invalid());
+        }
+        Ok(CellRef::new(column - 1, row - 1))
+    }
+}
+
+// What can be typed into a cell besides a formula.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpreadsheetCell {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CellError {
+    DivZero,
+    // a reference to a cell off the sheet
+    Ref,
+    // text where a number is needed, or a range outside of a function
+    Value,
+    // an unknown function
+    Name,
+    // a result too large for a float
+    Num,
+    // the cell is part of a cycle, or reads one
+    Cycle,
+}
+
+impl Display for CellError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let code = match self {
+            CellError::DivZero => "#DIV/0!",
+            CellError::Ref => "#REF!",
+            CellError::Value => "#VALUE!",
+            CellError::Name => "#NAME?",
+            CellError::Num => "#NUM!",
+            CellError::Cycle => "#CYCLE!",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+// What a cell shows.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Value {
+    #[default]
+    Empty,
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Error(CellError),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Empty => Ok(()),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Float(n) => write!(f, "{}", n),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Error(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<SpreadsheetCell> for Value {
+    fn from(cell: SpreadsheetCell) -> Self {
+        match cell {
+            SpreadsheetCell::Int(n) => Value::Int(n),
+            SpreadsheetCell::Float(n) => Value::Float(n),
+            SpreadsheetCell::Text(s) => Value::Text(s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SheetError {
+    InvalidReference(String),
+    OutOfBounds(CellRef),
+    Formula { at: CellRef, error: FormulaError },
+}
+
+impl Display for SheetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SheetError::InvalidReference(s) => write!(f, "{:?} is not a cell reference", s),
+            SheetError::OutOfBounds(at) => write!(f, "{} is off the sheet", at),
+            SheetError::Formula { at, error } => write!(f, "formula in {}: {}", at, error),
+        }
+    }
+}
+
+impl std::error::Error for SheetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SheetError::Formula { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Contents {
+    Literal(SpreadsheetCell),
+    // the text after '=' as typed, and what it parsed to
+    Formula(String, Expr),
+}
+
+// A range on the sheet as its top left and bottom right corners.
+type Area = (CellRef, CellRef);
+
+fn covers((top_left, bottom_right): &Area, cell: CellRef) -> bool {
+    (top_left.row..=bottom_right.row).contains(&cell.row)
+        && (top_left.column..=bottom_right.column).contains(&cell.column)
+}
+
+// Values are kept up to date on every change. Each formula cell knows which cells it reads
+// and each cell knows which formulas read it, so a change only recalculates the cells that
+// depend on it, in an order where every cell comes after the cells it reads.
+// Ranges are kept as their corners, so =SUM(A1:XFD1048576) costs no more than =A1; finding
+// the readers of a cell goes through every range instead.
+#[derive(Debug, Clone)]
+pub struct Sheet {
+    columns: u32,
+    rows: u32,
+    contents: BTreeMap<CellRef, Contents>,
+    values: BTreeMap<CellRef, Value>,
+    // single cell references, both ways
+    reads: BTreeMap<CellRef, BTreeSet<CellRef>>,
+    read_by: BTreeMap<CellRef, BTreeSet<CellRef>>,
+    // ranges of more than one cell, by the formula cell that reads them
+    range_reads: BTreeMap<CellRef, Vec<Area>>,
+}
+
+impl Sheet {
+    // sizes are capped at MAX_COLUMNS and MAX_ROWS
+    pub fn new(columns: u32, rows: u32) -> Sheet {
+        Sheet {
+            columns: columns.min(MAX_COLUMNS),
+            rows: rows.min(MAX_ROWS),
+            contents: BTreeMap::new(),
+            values: BTreeMap::new(),
+            reads: BTreeMap::new(),
+            read_by: BTreeMap::new(),
+            range_reads: BTreeMap::new(),
+        }
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    pub fn contains(&self, at: CellRef) -> bool {
+        at.column < self.columns && at.row < self.rows
+    }
+
+    // Takes what a user would type: "=..." is a formula, numbers become numbers, anything
+    // else is text and an empty string clears the cell. Returns the cells that were
+    // recalculated, the changed one first.
+    pub fn set(&mut self, at: CellRef, input: &str) -> Result<Vec<CellRef>, SheetError> {
+        if let Some(text) = input.strip_prefix('=') {
+            let expr = formula::parse(text).map_err(|error| SheetError::Formula { at, error })?;
+            return self.replace(at, Some(Contents::Formula(text.to_string(), expr)));
+        }
+        let trimmed = input.trim();
+        let literal = if trimmed.is_empty() {
+            None
+        } else if let Ok(n) = trimmed.parse::<i64>() {
+            Some(SpreadsheetCell::Int(n))
+        } else if let Some(n) = trimmed.parse::<f64>().ok().filter(|n| n.is_finite()) {
+            Some(SpreadsheetCell::Float(n))
+        } else {
+            Some(SpreadsheetCell::Text(input.to_string()))
+        };
+        self.replace(at, literal.map(Contents::Literal))
+    }
+
+    pub fn set_cell(
+        &mut self,
+        at: CellRef,
+        cell: SpreadsheetCell,
+    ) -> Result<Vec<CellRef>, SheetError> {
+        self.replace(at, Some(Contents::Literal(cell)))
+    }
+
+    pub fn clear(&mut self, at: CellRef) -> Result<Vec<CellRef>, SheetError> {
+        self.replace(at, None)
+    }
+
+    pub fn value(&self, at: CellRef) -> Value {
+        self.values.get(&at).cloned().unwrap_or_default()
+    }
+
+    // the formula as typed, with its '='
+    pub fn formula(&self, at: CellRef) -> Option<String> {
+        match self.contents.get(&at)? {
+            Contents::Formula(text, _) => Some(format!("={}", text)),
+            Contents::Literal(_) => None,
+        }
+    }
+
+    // what would be typed to get the cell back, "" for an empty cell
+    pub fn input(&self, at: CellRef) -> String {
+        match self.contents.get(&at) {
+            None => String::new(),
+            Some(Contents::Formula(text, _)) => format!("={}", text),
+            Some(Contents::Literal(cell)) => Value::from(cell.clone()).to_string(),
+        }
+    }
+
+    // cells that have contents, row by row
+    pub fn filled(&self) -> impl Iterator<Item = CellRef> + '_ {
+        self.contents.keys().copied()
+    }
+
+    // the formula cells that read at, directly
+    pub fn dependents(&self, at: CellRef) -> Vec<CellRef> {
+        let mut readers = self.read_by.get(&at).cloned().unwrap_or_default();
+        for (reader, areas) in &self.range_reads {
+            if areas.iter().any(|area| covers(area, at)) {
+                readers.insert(*reader);
+            }
+        }
+        readers.into_iter().collect()
+    }
+
+    fn replace(
+        &mut self,
+        at: CellRef,
+        contents: Option<Contents>,
+    ) -> Result<Vec<CellRef>, SheetError> {
+        if !self.contains(at) {
+            return Err(SheetError::OutOfBounds(at));
+        }
+
+        for read in self.reads.remove(&at).unwrap_or_default() {
+            if let Some(readers) = self.read_by.get_mut(&read) {
+                readers.remove(&at);
+                if readers.is_empty() {
+                    self.read_by.remove(&read);
+                }
+            }
+        }
+        self.range_reads.remove(&at);
+        if let Some(Contents::Formula(_, expr)) = &contents {
+            // cells off the sheet are left out, they evaluate to #REF! anyway
+            let (cells, areas): (Vec<Area>, Vec<Area>) = expr
+                .references()
+                .into_iter()
+                .filter_map(|(from, to)| self.clamp(from, to))
+                .partition(|(from, to)| from == to);
+            let reads: BTreeSet<CellRef> = cells.into_iter().map(|(cell, _)| cell).collect();
+            for read in &reads {
+                self.read_by.entry(*read).or_default().insert(at);
+            }
+            self.reads.insert(at, reads);
+            if !areas.is_empty() {
+                self.range_reads.insert(at, areas);
+            }
+        }
+        match contents {
+            Some(contents) => self.contents.insert(at, contents),
+            None => self.contents.remove(&at),
+        };
+
+        Ok(self.recalculate_from(at))
+    }
+
+    // The part of a range that is on the sheet, as top left and bottom right corners, so a
+    // range like A1:XFD1048576 is never walked past the edge.
+    fn clamp(&self, a: CellRef, b: CellRef) -> Option<Area> {
+        let (top, left) = (a.row.min(b.row), a.column.min(b.column));
+        if top >= self.rows || left >= self.columns {
+            return None;
+        }
+        let bottom = a.row.max(b.row).min(self.rows - 1);
+        let right = a.column.max(b.column).min(self.columns - 1);
+        Some((CellRef::new(left, top), CellRef::new(right, bottom)))
+    }
+
+    // Kahn's algorithm over the changed cell and everything that reads it, directly or not.
+    // Cells that never become ready are in a cycle or read one.
+    fn recalculate_from(&mut self, changed: CellRef) -> Vec<CellRef> {
+        let mut affected = BTreeSet::from([changed]);
+        let mut queue = VecDeque::from([changed]);
+        while let Some(cell) = queue.pop_front() {
+            for reader in self.dependents(cell) {
+                if affected.insert(reader) {
+                    queue.push_back(reader);
+                }
+            }
+        }
+
+        // how many affected cells each one still waits for, counted the way they are let go
+        let mut waiting: BTreeMap<CellRef, usize> =
+            affected.iter().map(|cell| (*cell, 0)).collect();
+        for cell in &affected {
+            for reader in self.dependents(*cell) {
+                if let Some(count) = waiting.get_mut(&reader) {
+                    *count += 1;
+                }
+            }
+        }
+        let mut ready: VecDeque<CellRef> = waiting
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(cell, _)| *cell)
+            .collect();
+
+        let mut order = Vec::with_capacity(affected.len());
+        let mut done = BTreeSet::new();
+        while let Some(cell) = ready.pop_front() {
+            self.evaluate(cell);
+            order.push(cell);
+            done.insert(cell);
+            for reader in self.dependents(cell) {
+                if let Some(count) = waiting.get_mut(&reader) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push_back(reader);
+                    }
+                }
+            }
+        }
+        for cell in affected {
+            if !done.contains(&cell) {
+                self.values.insert(cell, Value::Error(CellError::Cycle));
+                order.push(cell);
+            }
+        }
+        order
+    }
+
+    fn evaluate(&mut self, at: CellRef) {
+        let value = match self.contents.get(&at) {
+            None => Value::Empty,
+            Some(Contents::Literal(cell)) => Value::from(cell.clone()),
+            Some(Contents::Formula(_, expr)) => expr.evaluate(self),
+        };
+        match value {
+            Value::Empty => self.values.remove(&at),
+            value => self.values.insert(at, value),
+        };
+    }
+}
+
+impl Cells for Sheet {
+    fn value(&self, at: CellRef) -> Option<Value> {
+        self.contains(at).then(|| Sheet::value(self, at))
+    }
+
+    // Goes through the filled cells in the rows of the range only, so a range as big as the
+    // sheet costs as much as the cells that are in it.
+    fn filled(&self, from: CellRef, to: CellRef) -> Option<Vec<Value>> {
+        let (top_left, bottom_right) = (
+            CellRef::new(from.column.min(to.column), from.row.min(to.row)),
+            CellRef::new(from.column.max(to.column), from.row.max(to.row)),
+        );
+        if !self.contains(bottom_right) {
+            return None;
+        }
+        let area = (top_left, bottom_right);
+        Some(
+            self.values
+                .range(top_left..=bottom_right)
+                .filter(|(at, _)| covers(&area, **at))
+                .map(|(_, value)| value.clone())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> CellRef {
+        s.parse().unwrap()
+    }
+
+    fn sheet(cells: &[(&str, &str)]) -> Sheet {
+        let mut sheet = Sheet::new(26, 100);
+        for (cell, input) in cells {
+            sheet.set(at(cell), input).unwrap();
+        }
+        sheet
+    }
+
+    #[test]
+    fn a1_references() {
+        assert_eq!(at("A1"), CellRef::new(0, 0));
+        assert_eq!(at("z10"), CellRef::new(25, 9));
+        assert_eq!(at("AA1"), CellRef::new(26, 0));
+        assert_eq!(at("XFD1048576"), CellRef::new(16_383, 1_048_575));
+        for bad in ["", "A", "1", "A0", "A01", "XFE1", "A1048577", "A-1", "É1"] {
+            assert!(bad.parse::<CellRef>().is_err(), "{}", bad);
+        }
+        for column in [0, 25, 26, 701, 702, 16_383] {
+            let cell = CellRef::new(column, 4);
+            assert_eq!(at(&cell.to_string()), cell);
+        }
+        // off any sheet, but still printable
+        assert_eq!(
+            CellRef::new(u32::MAX, u32::MAX).to_string(),
+            "MWLQKWV4294967296"
+        );
+    }
+
+    #[test]
+    fn formulas_read_other_cells() {
+        let sheet = sheet(&[
+            ("A1", "1"),
+            ("A2", "2.5"),
+            ("A3", "blue"),
+            ("A4", "=SUM(A1:A3)"),
+            ("B1", "=A1+A2*2"),
+            ("B2", "=A4/COUNT(A1:A3)"),
+            ("B3", "=A3*2"),
+        ]);
+        assert_eq!(sheet.value(at("A4")), Value::Float(3.5));
+        assert_eq!(sheet.value(at("B1")), Value::Float(6.0));
+        assert_eq!(sheet.value(at("B2")), Value::Float(1.75));
+        assert_eq!(sheet.value(at("B3")), Value::Error(CellError::Value));
+        assert_eq!(sheet.value(at("C1")), Value::Empty);
+        assert_eq!(sheet.formula(at("B1")).as_deref(), Some("=A1+A2*2"));
+        assert_eq!(sheet.input(at("A2")), "2.5");
+    }
+
+    #[test]
+    fn changes_only_recalculate_dependents() {
+        let mut sheet = sheet(&[
+            ("A1", "1"),
+            ("A2", "=A1*10"),
+            ("A3", "=A2+A1"),
+            ("B1", "=5"),
+        ]);
+        let order = sheet.set(at("A1"), "2").unwrap();
+        assert_eq!(order, vec![at("A1"), at("A2"), at("A3")]);
+        assert_eq!(sheet.value(at("A3")), Value::Int(22));
+
+        // A3 no longer reads A1 or A2
+        sheet.set(at("A3"), "=B1").unwrap();
+        assert_eq!(sheet.set(at("A1"), "3").unwrap(), vec![at("A1"), at("A2")]);
+        assert_eq!(sheet.dependents(at("B1")), vec![at("A3")]);
+    }
+
+    #[test]
+    fn errors_and_their_codes() {
+        let sheet = sheet(&[
+            ("A1", "0"),
+            ("A2", "=1/A1"),
+            ("A3", "=A2+1"),
+            ("A4", "=Z101"),
+            ("A5", "=SUM(A1:A2)"),
+            ("A6", "=FOO(1)"),
+        ]);
+        assert_eq!(sheet.value(at("A2")).to_string(), "#DIV/0!");
+        assert_eq!(sheet.value(at("A3")).to_string(), "#DIV/0!");
+        assert_eq!(sheet.value(at("A4")).to_string(), "#REF!");
+        assert_eq!(sheet.value(at("A5")).to_string(), "#DIV/0!");
+        assert_eq!(sheet.value(at("A6")).to_string(), "#NAME?");
+    }
+
+    #[test]
+    fn cycles_are_detected_and_broken() {
+        let mut sheet = sheet(&[("A1", "=B1+1"), ("C1", "=A1*2")]);
+        sheet.set(at("B1"), "=A1").unwrap();
+        for cell in ["A1", "B1", "C1"] {
+            assert_eq!(
+                sheet.value(at(cell)),
+                Value::Error(CellError::Cycle),
+                "{}",
+                cell
+            );
+        }
+        assert_eq!(sheet.set(at("D1"), "=D1").unwrap(), vec![at("D1")]);
+        assert_eq!(sheet.value(at("D1")), Value::Error(CellError::Cycle));
+
+        sheet.set(at("B1"), "4").unwrap();
+        assert_eq!(sheet.value(at("A1")), Value::Int(5));
+        assert_eq!(sheet.value(at("C1")), Value::Int(10));
+    }
+
+    #[test]
+    fn bad_input() {
+        let mut sheet = Sheet::new(2, 2);
+        assert_eq!(
+            sheet.set(at("C1"), "1"),
+            Err(SheetError::OutOfBounds(at("C1")))
+        );
+        assert!(matches!(
+            sheet.set(at("A1"), "=1+"),
+            Err(SheetError::Formula { .. })
+        ));
+        sheet.set(at("A1"), "5").unwrap();
+        sheet.set(at("B1"), "=A1").unwrap();
+        sheet.clear(at("A1")).unwrap();
+        assert_eq!(sheet.value(at("B1")), Value::Empty);
+        assert_eq!(sheet.filled().collect::<Vec<_>>(), vec![at("B1")]);
+
+        // the part of column A on the sheet is kept as one range, a range off it not at all
+        sheet.set(at("B2"), "=COUNT(A1:A1048576)").unwrap();
+        sheet.set(at("B1"), "=SUM(XFD1:XFD1048576)").unwrap();
+        assert_eq!(sheet.dependents(at("A2")), vec![at("B2")]);
+        assert_eq!(sheet.range_reads[&at("B2")], [(at("A1"), at("A2"))]);
+        assert!(!sheet.range_reads.contains_key(&at("B1")));
+        assert_eq!(sheet.value(at("B2")), Value::Error(CellError::Ref));
+    }
+
+    #[test]
+    fn whole_sheet_ranges_are_kept_as_ranges() {
+        let mut sheet = Sheet::new(MAX_COLUMNS, MAX_ROWS);
+        sheet.set(at("A1"), "=SUM(B1:XFD1048576)").unwrap();
+        sheet.set(at("B2"), "=SUM(C1:C1048576) + B1").unwrap();
+        assert_eq!(sheet.range_reads.values().map(Vec::len).sum::<usize>(), 2);
+        assert_eq!(sheet.reads[&at("B2")].len(), 1);
+
+        sheet.set(at("C5"), "4").unwrap();
+        assert_eq!(sheet.value(at("B2")), Value::Int(4));
+        assert_eq!(sheet.value(at("A1")), Value::Int(8));
+        assert_eq!(sheet.dependents(at("C5")), vec![at("A1"), at("B2")]);
+
+        // a range that takes in the formula's own cell is a cycle
+        let changed = sheet.set(at("B1"), "=SUM(A1:B1)").unwrap();
+        assert_eq!(changed.len(), 3);
+        assert_eq!(sheet.value(at("B1")), Value::Error(CellError::Cycle));
+        assert_eq!(sheet.value(at("A1")), Value::Error(CellError::Cycle));
+        sheet.clear(at("B1")).unwrap();
+        assert_eq!(sheet.value(at("A1")), Value::Int(8));
+    }
+}
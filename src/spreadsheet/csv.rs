@@ -0,0 +1,622 @@
+// CSV files in and out of spreadsheet cells. Quoting, quotes inside fields and fields that
+// span lines follow RFC 4180 and are handled by the csv crate, rows are read one at a time
+// so a file never has to fit in memory.
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+
+use super::{CellRef, Sheet, SheetError, SpreadsheetCell, Value};
+
+// Text that would read back as something else is written with a leading apostrophe, the
+// way spreadsheets let "007" be typed in as text. Auto takes it off again.
+pub const TEXT_MARK: char = '\'';
+
+// What a column holds. Auto tries a whole number, then a decimal one, then falls back to
+// text, and a field starting with TEXT_MARK is always the text after it; the others insist
+// and report fields that do not fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Inference {
+    #[default]
+    Auto,
+    Int,
+    Float,
+    // for columns like zip codes where "01234" has to stay as it is
+    Text,
+}
+
+impl Display for Inference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Inference::Auto => "any value",
+            Inference::Int => "a whole number",
+            Inference::Float => "a number",
+            Inference::Text => "text",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Inference {
+    // None for an empty field, that is an empty cell whatever the column holds
+    pub fn infer(self, field: &str) -> Option<Result<SpreadsheetCell, Inference>> {
+        if field.is_empty() {
+            return None;
+        }
+        let number = field.trim();
+        let int = || number.parse::<i64>().ok().map(SpreadsheetCell::Int);
+        let float = || {
+            number
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(SpreadsheetCell::Float)
+        };
+        let cell = match self {
+            Inference::Auto => match field.strip_prefix(TEXT_MARK) {
+                Some(text) => SpreadsheetCell::Text(text.to_string()),
+                None => int()
+                    .or_else(float)
+                    .unwrap_or_else(|| SpreadsheetCell::Text(field.to_string())),
+            },
+            Inference::Int => match int() {
+                Some(cell) => cell,
+                None => return Some(Err(self)),
+            },
+            Inference::Float => match float() {
+                Some(cell) => cell,
+                None => return Some(Err(self)),
+            },
+            Inference::Text => SpreadsheetCell::Text(field.to_string()),
+        };
+        Some(Ok(cell))
+    }
+}
+
+#[derive(Debug)]
+pub enum CsvError {
+    Io(io::Error),
+    Csv(::csv::Error),
+    // line counts from 1 and includes the header, column counts from 0
+    Type {
+        line: u64,
+        column: usize,
+        value: String,
+        expected: Inference,
+    },
+    Sheet(SheetError),
+}
+
+impl Display for CsvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::Io(e) => write!(f, "{}", e),
+            CsvError::Csv(e) => write!(f, "{}", e),
+            CsvError::Type {
+                line,
+                column,
+                value,
+                expected,
+            } => write!(
+                f,
+                "line {}, column {}: expected {}, found {:?}",
+                line,
+                CellRef::column_name(*column as u32),
+                expected,
+                value
+            ),
+            CsvError::Sheet(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CsvError::Io(e) => Some(e),
+            CsvError::Csv(e) => Some(e),
+            CsvError::Sheet(e) => Some(e),
+            CsvError::Type { .. } => None,
+        }
+    }
+}
+
+impl From<::csv::Error> for CsvError {
+    fn from(e: ::csv::Error) -> Self {
+        if !e.is_io_error() {
+            return CsvError::Csv(e);
+        }
+        match e.into_kind() {
+            ::csv::ErrorKind::Io(e) => CsvError::Io(e),
+            kind => CsvError::Io(io::Error::other(format!("{:?}", kind))),
+        }
+    }
+}
+
+impl From<io::Error> for CsvError {
+    fn from(e: io::Error) -> Self {
+        CsvError::Io(e)
+    }
+}
+
+impl From<SheetError> for CsvError {
+    fn from(e: SheetError) -> Self {
+        CsvError::Sheet(e)
+    }
+}
+
+// Columns can be picked by position or, when the file has a header row, by name.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    delimiter: u8,
+    has_headers: bool,
+    default: Inference,
+    by_index: BTreeMap<usize, Inference>,
+    by_name: BTreeMap<String, Inference>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            has_headers: false,
+            default: Inference::Auto,
+            by_index: BTreeMap::new(),
+            by_name: BTreeMap::new(),
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn new() -> CsvOptions {
+        CsvOptions::default()
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> CsvOptions {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn has_headers(mut self, has_headers: bool) -> CsvOptions {
+        self.has_headers = has_headers;
+        self
+    }
+
+    // for every column that is not set on its own
+    pub fn infer(mut self, inference: Inference) -> CsvOptions {
+        self.default = inference;
+        self
+    }
+
+    pub fn column(mut self, index: usize, inference: Inference) -> CsvOptions {
+        self.by_index.insert(index, inference);
+        self
+    }
+
+    // a name wins over an index for the same column
+    pub fn column_named(mut self, name: &str, inference: Inference) -> CsvOptions {
+        self.by_name.insert(name.to_string(), inference);
+        self
+    }
+}
+
+pub type Row = Vec<Option<SpreadsheetCell>>;
+
+pub struct CsvReader<R> {
+    reader: ::csv::Reader<R>,
+    record: ::csv::StringRecord,
+    headers: Option<Vec<String>>,
+    default: Inference,
+    columns: BTreeMap<usize, Inference>,
+}
+
+impl<R: Read> CsvReader<R> {
+    // reads the header row straight away when there is one
+    pub fn new(reader: R, options: CsvOptions) -> Result<CsvReader<R>, CsvError> {
+        let mut reader = ::csv::ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .has_headers(options.has_headers)
+            .flexible(true)
+            .from_reader(reader);
+        let mut columns = options.by_index;
+        let headers = if options.has_headers {
+            let headers: Vec<String> = reader.headers()?.iter().map(String::from).collect();
+            for (index, name) in headers.iter().enumerate() {
+                if let Some(inference) = options.by_name.get(name) {
+                    columns.insert(index, *inference);
+                }
+            }
+            Some(headers)
+        } else {
+            None
+        };
+        Ok(CsvReader {
+            reader,
+            record: ::csv::StringRecord::new(),
+            headers,
+            default: options.default,
+            columns,
+        })
+    }
+
+    pub fn headers(&self) -> Option<&[String]> {
+        self.headers.as_deref()
+    }
+
+    // Rows can have different lengths, missing fields are simply not there.
+    pub fn read_row(&mut self) -> Result<Option<Row>, CsvError> {
+        if !self.reader.read_record(&mut self.record)? {
+            return Ok(None);
+        }
+        let line = self.record.position().map_or(0, |p| p.line());
+        self.record
+            .iter()
+            .enumerate()
+            .map(|(column, field)| {
+                let inference = self.columns.get(&column).copied().unwrap_or(self.default);
+                inference
+                    .infer(field)
+                    .transpose()
+                    .map_err(|expected| CsvError::Type {
+                        line,
+                        column,
+                        value: field.to_string(),
+                        expected,
+                    })
+            })
+            .collect::<Result<Row, CsvError>>()
+            .map(Some)
+    }
+}
+
+impl<R: Read> Iterator for CsvReader<R> {
+    type Item = Result<Row, CsvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_row().transpose()
+    }
+}
+
+pub struct CsvWriter<W: Write> {
+    writer: ::csv::Writer<W>,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(writer: W, delimiter: u8) -> CsvWriter<W> {
+        CsvWriter {
+            writer: ::csv::WriterBuilder::new()
+                .delimiter(delimiter)
+                .flexible(true)
+                .from_writer(writer),
+        }
+    }
+
+    // Fields with the delimiter, quotes or line breaks get quoted. Text that is empty, looks
+    // like a number or starts with TEXT_MARK gets the mark, so Auto reads back every cell
+    // as it was written. So does text starting with a byte order mark, which a reader would
+    // drop at the start of a file.
+    pub fn write_row(&mut self, row: &[Option<SpreadsheetCell>]) -> Result<(), CsvError> {
+        let fields = row.iter().map(|cell| match cell {
+            None => String::new(),
+            Some(SpreadsheetCell::Int(n)) => n.to_string(),
+            // Debug keeps the ".0" so 3.0 is read back as a float
+            Some(SpreadsheetCell::Float(n)) => format!("{:?}", n),
+            Some(SpreadsheetCell::Text(s)) => match Inference::Auto.infer(s) {
+                Some(Ok(SpreadsheetCell::Text(read)))
+                    if read == *s && !s.starts_with('\u{feff}') =>
+                {
+                    s.clone()
+                }
+                _ => format!("{}{}", TEXT_MARK, s),
+            },
+        });
+        self.writer.write_record(fields)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), CsvError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> Result<W, CsvError> {
+        self.writer
+            .into_inner()
+            .map_err(|e| CsvError::from(e.into_error()))
+    }
+}
+
+// Fills the sheet from its top left corner, a header row goes in as text. Returns the
+// number of rows read. Cells already filled in before a bad row stay.
+pub fn import(sheet: &mut Sheet, reader: impl Read, options: CsvOptions) -> Result<u32, CsvError> {
+    let mut reader = CsvReader::new(reader, options)?;
+    let mut row = 0;
+    if let Some(headers) = reader.headers().map(<[String]>::to_vec) {
+        let headers: Row = headers
+            .into_iter()
+            .map(|name| Some(SpreadsheetCell::Text(name)))
+            .collect();
+        set_row(sheet, row, headers)?;
+        row += 1;
+    }
+    while let Some(cells) = reader.read_row()? {
+        set_row(sheet, row, cells)?;
+        row += 1;
+    }
+    Ok(row)
+}
+
+fn set_row(sheet: &mut Sheet, row: u32, cells: Row) -> Result<(), SheetError> {
+    for (column, cell) in cells.into_iter().enumerate() {
+        if let Some(cell) = cell {
+            sheet.set_cell(CellRef::new(column as u32, row), cell)?;
+        }
+    }
+    Ok(())
+}
+
+// Writes what the cells show, from A1 to the last filled row and column. Formulas are
+// written as their values and errors as their codes.
+pub fn export(sheet: &Sheet, writer: impl Write, delimiter: u8) -> Result<(), CsvError> {
+    let mut writer = CsvWriter::new(writer, delimiter);
+    let (columns, rows) = sheet.filled().fold((0, 0), |(columns, rows), at| {
+        (columns.max(at.column + 1), rows.max(at.row + 1))
+    });
+    for row in 0..rows {
+        let cells: Row = (0..columns)
+            .map(|column| match sheet.value(CellRef::new(column, row)) {
+                Value::Empty => None,
+                Value::Int(n) => Some(SpreadsheetCell::Int(n)),
+                Value::Float(n) => Some(SpreadsheetCell::Float(n)),
+                Value::Text(s) => Some(SpreadsheetCell::Text(s)),
+                Value::Error(e) => Some(SpreadsheetCell::Text(e.to_string())),
+            })
+            .collect();
+        writer.write_row(&cells)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use SpreadsheetCell::{Float, Int, Text};
+
+    fn read_all(text: &str, options: CsvOptions) -> Result<Vec<Row>, CsvError> {
+        CsvReader::new(text.as_bytes(), options)?.collect()
+    }
+
+    fn text(s: &str) -> Option<SpreadsheetCell> {
+        Some(Text(s.to_string()))
+    }
+
+    #[test]
+    fn infers_each_field() {
+        let rows = read_all("1, 2.5 ,blue,,-7\n\"3\",1e3,NaN\n", CsvOptions::new()).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    Some(Int(1)),
+                    Some(Float(2.5)),
+                    text("blue"),
+                    None,
+                    Some(Int(-7))
+                ],
+                vec![Some(Int(3)), Some(Float(1000.0)), text("NaN")],
+            ]
+        );
+    }
+
+    #[test]
+    fn quoting_and_line_breaks() {
+        let input = "\"a, b\",\"say \"\"hi\"\"\"\r\n\"two\nlines\",x\r\n";
+        let rows = read_all(input, CsvOptions::new()).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![text("a, b"), text("say \"hi\"")],
+                vec![text("two\nlines"), text("x")],
+            ]
+        );
+
+        let mut writer = CsvWriter::new(Vec::new(), b',');
+        for row in &rows {
+            writer.write_row(row).unwrap();
+        }
+        let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(written, "\"a, b\",\"say \"\"hi\"\"\"\n\"two\nlines\",x\n");
+    }
+
+    #[test]
+    fn text_that_looks_like_something_else_is_marked() {
+        let row = vec![
+            text("007"),
+            text(" 2.5"),
+            text(""),
+            text("'tis"),
+            text("NaN"),
+            Some(Int(7)),
+        ];
+        let mut writer = CsvWriter::new(Vec::new(), b',');
+        writer.write_row(&row).unwrap();
+        let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(written, "'007,' 2.5,',''tis,NaN,7\n");
+        assert_eq!(read_all(&written, CsvOptions::new()).unwrap(), vec![row]);
+
+        let bom = vec![text("\u{feff}x")];
+        let mut writer = CsvWriter::new(Vec::new(), b',');
+        writer.write_row(&bom).unwrap();
+        let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(read_all(&written, CsvOptions::new()).unwrap(), vec![bom]);
+
+        // a column read as text keeps the mark, as the file has it
+        let zip = CsvOptions::new().infer(Inference::Text);
+        assert_eq!(read_all("'007\n", zip).unwrap(), vec![vec![text("'007")]]);
+    }
+
+    #[test]
+    fn per_column_inference() {
+        let input = "zip;count;price\n01234;2;3\n99501;x;4.5\n";
+        let options = CsvOptions::new()
+            .delimiter(b';')
+            .has_headers(true)
+            .column_named("zip", Inference::Text)
+            .column(2, Inference::Float);
+        let mut reader = CsvReader::new(input.as_bytes(), options).unwrap();
+        assert_eq!(reader.headers().unwrap(), ["zip", "count", "price"]);
+        assert_eq!(
+            reader.next().unwrap().unwrap(),
+            vec![text("01234"), Some(Int(2)), Some(Float(3.0))]
+        );
+        assert_eq!(
+            reader.next().unwrap().unwrap(),
+            vec![text("99501"), text("x"), Some(Float(4.5))]
+        );
+
+        let strict = CsvOptions::new()
+            .delimiter(b';')
+            .has_headers(true)
+            .column(1, Inference::Int);
+        let error = read_all(input, strict).unwrap_err();
+        assert!(matches!(
+            error,
+            CsvError::Type {
+                line: 3,
+                column: 1,
+                expected: Inference::Int,
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "line 3, column B: expected a whole number, found \"x\""
+        );
+    }
+
+    // a file that never ends, only the rows asked for are read
+    struct Endless(usize);
+
+    impl Read for Endless {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let line = format!("{},row {}\n", self.0, self.0);
+            let n = line.len().min(buf.len());
+            buf[..n].copy_from_slice(&line.as_bytes()[..n]);
+            if n == line.len() {
+                self.0 += 1;
+            }
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn write_failures_are_io_errors() {
+        struct Full;
+
+        impl Write for Full {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut sheet = Sheet::new(1, 1);
+        sheet.set_cell(CellRef::new(0, 0), Int(1)).unwrap();
+        let error = export(&sheet, Full, b',').unwrap_err();
+        assert!(matches!(error, CsvError::Io(_)), "{:?}", error);
+
+        // more than the csv writer buffers, so the write fails before the flush
+        let mut sheet = Sheet::new(1, 10_000);
+        for row in 0..10_000 {
+            sheet
+                .set_cell(CellRef::new(0, row), Int(row.into()))
+                .unwrap();
+        }
+        let error = export(&sheet, Full, b',').unwrap_err();
+        assert!(matches!(error, CsvError::Io(_)), "{:?}", error);
+    }
+
+    #[test]
+    fn read_failures_are_io_errors() {
+        struct Broken;
+
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk gone"))
+            }
+        }
+
+        let mut sheet = Sheet::new(1, 1);
+        for options in [CsvOptions::new(), CsvOptions::new().has_headers(true)] {
+            let error = import(&mut sheet, Broken, options).unwrap_err();
+            assert!(matches!(error, CsvError::Io(_)), "{:?}", error);
+            assert_eq!(error.to_string(), "disk gone");
+        }
+    }
+
+    #[test]
+    fn streams_rows() {
+        let reader = CsvReader::new(Endless(0), CsvOptions::new()).unwrap();
+        let rows: Vec<Row> = reader.skip(1000).take(2).collect::<Result<_, _>>().unwrap();
+        assert_eq!(rows[0], vec![Some(Int(1000)), text("row 1000")]);
+        assert_eq!(rows[1], vec![Some(Int(1001)), text("row 1001")]);
+    }
+
+    #[test]
+    fn sheets_in_and_out() {
+        let mut sheet = Sheet::new(5, 5);
+        let input = "item\tcost\nlamp\t12.5\ndesk\t80\n";
+        let options = CsvOptions::new().delimiter(b'\t').has_headers(true);
+        assert_eq!(import(&mut sheet, input.as_bytes(), options).unwrap(), 3);
+        sheet.set("B4".parse().unwrap(), "=SUM(B2:B3)").unwrap();
+        sheet.set("C1".parse().unwrap(), "=1/0").unwrap();
+
+        let mut out = Vec::new();
+        export(&sheet, &mut out, b',').unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "item,cost,#DIV/0!\nlamp,12.5,\ndesk,80,\n,92.5,\n"
+        );
+
+        let mut small = Sheet::new(1, 5);
+        assert!(matches!(
+            import(
+                &mut small,
+                input.as_bytes(),
+                CsvOptions::new().delimiter(b'\t')
+            ),
+            Err(CsvError::Sheet(SheetError::OutOfBounds(_)))
+        ));
+    }
+
+    fn cell() -> impl Strategy<Value = Option<SpreadsheetCell>> {
+        prop_oneof![
+            Just(None),
+            any::<i64>().prop_map(|n| Some(Int(n))),
+            any::<f64>()
+                .prop_filter("finite", |n| n.is_finite())
+                .prop_map(|n| Some(Float(n))),
+            any::<String>().prop_map(|s| Some(Text(s))),
+            "'?[ 0-9.e+-]{0,6}".prop_map(|s| Some(Text(s))),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips(rows in prop::collection::vec(prop::collection::vec(cell(), 1..5), 0..10)) {
+            let mut writer = CsvWriter::new(Vec::new(), b'|');
+            for row in &rows {
+                writer.write_row(row).unwrap();
+            }
+            let written = writer.into_inner().unwrap();
+            let read: Vec<Row> = CsvReader::new(&written[..], CsvOptions::new().delimiter(b'|'))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            prop_assert_eq!(read, rows);
+        }
+    }
+}
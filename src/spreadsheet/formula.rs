@@ -0,0 +1,615 @@
+// Formulas like =A1+B2*2 or =SUM(A1:A10)/COUNT(A1:A10): a tokenizer, a recursive descent
+// parser and an evaluator that reads other cells through the Cells trait.
+use std::fmt::{self, Display, Formatter};
+
+use super::{CellError, CellRef, Value};
+
+// Where a formula reads other cells from.
+pub trait Cells {
+    // the value of a cell, None when it is off the sheet
+    fn value(&self, at: CellRef) -> Option<Value>;
+
+    // The values of a range that are not empty, row by row, None when some of it is off the
+    // sheet. This one visits every cell, a sheet can skip to the filled ones.
+    fn filled(&self, from: CellRef, to: CellRef) -> Option<Vec<Value>> {
+        let mut values = Vec::new();
+        for at in CellRef::range(from, to) {
+            match self.value(at)? {
+                Value::Empty => {}
+                value => values.push(value),
+            }
+        }
+        Some(values)
+    }
+}
+
+impl<F: Fn(CellRef) -> Option<Value>> Cells for F {
+    fn value(&self, at: CellRef) -> Option<Value> {
+        self(at)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(Value),
+    Text(String),
+    Ref(CellRef),
+    // only allowed as a function argument
+    Range(CellRef, CellRef),
+    Negate(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormulaError {
+    UnexpectedChar { ch: char, position: usize },
+    UnexpectedToken { found: String, position: usize },
+    UnexpectedEnd,
+    InvalidReference(String),
+    // nested past MAX_DEPTH
+    TooDeep { max: usize },
+}
+
+impl Display for FormulaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FormulaError::UnexpectedChar { ch, position } => {
+                write!(f, "unexpected {:?} at {}", ch, position)
+            }
+            FormulaError::UnexpectedToken { found, position } => {
+                write!(f, "unexpected {} at {}", found, position)
+            }
+            FormulaError::UnexpectedEnd => write!(f, "formula ends too early"),
+            FormulaError::InvalidReference(s) => write!(f, "{:?} is not a cell reference", s),
+            FormulaError::TooDeep { max } => write!(f, "formula nests more than {} deep", max),
+        }
+    }
+}
+
+impl std::error::Error for FormulaError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(Value),
+    Text(String),
+    Name(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Colon,
+    Comma,
+    Open,
+    Close,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Text(s) => write!(f, "{:?}", s),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Plus => write!(f, "'+'"),
+            Token::Minus => write!(f, "'-'"),
+            Token::Star => write!(f, "'*'"),
+            Token::Slash => write!(f, "'/'"),
+            Token::Colon => write!(f, "':'"),
+            Token::Comma => write!(f, "','"),
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+        }
+    }
+}
+
+// tokens with the byte offset they start at
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, FormulaError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(position, ch)) = chars.peek() {
+        let single = match ch {
+            '+' => Some(Token::Plus),
+            '-' => Some(Token::Minus),
+            '*' => Some(Token::Star),
+            '/' => Some(Token::Slash),
+            ':' => Some(Token::Colon),
+            ',' => Some(Token::Comma),
+            '(' => Some(Token::Open),
+            ')' => Some(Token::Close),
+            _ => None,
+        };
+        if let Some(token) = single {
+            chars.next();
+            tokens.push((token, position));
+        } else if ch.is_whitespace() {
+            chars.next();
+        } else if ch.is_ascii_digit() || ch == '.' {
+            let mut number = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || c == '.') {
+                    break;
+                }
+                number.push(c);
+                chars.next();
+            }
+            let value = match number.parse::<i64>() {
+                Ok(n) => Value::Int(n),
+                Err(_) => match number.parse::<f64>() {
+                    Ok(n) => Value::Float(n),
+                    Err(_) => {
+                        return Err(FormulaError::UnexpectedToken {
+                            found: number,
+                            position,
+                        })
+                    }
+                },
+            };
+            tokens.push((Token::Number(value), position));
+        } else if ch.is_ascii_alphabetic() {
+            let mut name = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !c.is_ascii_alphanumeric() {
+                    break;
+                }
+                name.push(c.to_ascii_uppercase());
+                chars.next();
+            }
+            tokens.push((Token::Name(name), position));
+        } else if ch == '"' {
+            // "" inside a string is a quote, as in spreadsheets
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) if chars.peek().map(|&(_, c)| c) == Some('"') => {
+                        chars.next();
+                        text.push('"');
+                    }
+                    Some((_, '"')) => break,
+                    Some((_, c)) => text.push(c),
+                    None => return Err(FormulaError::UnexpectedEnd),
+                }
+            }
+            tokens.push((Token::Text(text), position));
+        } else {
+            return Err(FormulaError::UnexpectedChar { ch, position });
+        }
+    }
+    Ok(tokens)
+}
+
+// Parsing, evaluating and dropping an Expr all recurse, so a formula like ((((...1)))) or
+// 1+1+...+1 could overflow the stack. Brackets, signs and operators each count a level.
+pub const MAX_DEPTH: usize = 256;
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    fn advance(&mut self) -> Result<(Token, usize), FormulaError> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or(FormulaError::UnexpectedEnd)?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), FormulaError> {
+        match self.advance()? {
+            (token, _) if token == expected => Ok(()),
+            (token, position) => Err(unexpected(token, position)),
+        }
+    }
+
+    // a level deeper, undone by leave
+    fn enter(&mut self) -> Result<(), FormulaError> {
+        if self.depth == MAX_DEPTH {
+            return Err(FormulaError::TooDeep { max: MAX_DEPTH });
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn leave(&mut self, levels: usize) {
+        self.depth -= levels;
+    }
+
+    // every operator puts the expression so far one level further down
+    fn expression(&mut self) -> Result<Expr, FormulaError> {
+        let mut left = self.term()?;
+        let mut levels = 0;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => Op::Add,
+                Some(Token::Minus) => Op::Subtract,
+                _ => break,
+            };
+            self.next += 1;
+            self.enter()?;
+            levels += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.term()?));
+        }
+        self.leave(levels);
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Expr, FormulaError> {
+        let mut left = self.unary()?;
+        let mut levels = 0;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Star) => Op::Multiply,
+                Some(Token::Slash) => Op::Divide,
+                _ => break,
+            };
+            self.next += 1;
+            self.enter()?;
+            levels += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+        self.leave(levels);
+        Ok(left)
+    }
+
+    // brackets and calls come back through here, so this counts them too
+    fn unary(&mut self) -> Result<Expr, FormulaError> {
+        self.enter()?;
+        let expr = match self.peek() {
+            Some(Token::Minus) => {
+                self.next += 1;
+                self.unary().map(|inner| Expr::Negate(Box::new(inner)))
+            }
+            Some(Token::Plus) => {
+                self.next += 1;
+                self.unary()
+            }
+            _ => self.primary(),
+        }?;
+        self.leave(1);
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, FormulaError> {
+        match self.advance()? {
+            (Token::Number(n), _) => Ok(Expr::Number(n)),
+            (Token::Text(s), _) => Ok(Expr::Text(s)),
+            (Token::Open, _) => {
+                let inner = self.expression()?;
+                self.expect(Token::Close)?;
+                Ok(inner)
+            }
+            (Token::Name(name), _) if self.peek() == Some(&Token::Open) => {
+                self.next += 1;
+                let mut args = Vec::new();
+                if self.peek() != Some(&Token::Close) {
+                    loop {
+                        args.push(self.argument()?);
+                        if self.peek() != Some(&Token::Comma) {
+                            break;
+                        }
+                        self.next += 1;
+                    }
+                }
+                self.expect(Token::Close)?;
+                Ok(Expr::Call(name, args))
+            }
+            (Token::Name(name), _) => Ok(Expr::Ref(reference(&name)?)),
+            (token, position) => Err(unexpected(token, position)),
+        }
+    }
+
+    // an expression or a range like A1:B3
+    fn argument(&mut self) -> Result<Expr, FormulaError> {
+        let start = self.expression()?;
+        if self.peek() != Some(&Token::Colon) {
+            return Ok(start);
+        }
+        self.next += 1;
+        let Expr::Ref(from) = start else {
+            return Err(FormulaError::InvalidReference(String::from(":")));
+        };
+        match self.advance()? {
+            (Token::Name(name), _) => Ok(Expr::Range(from, reference(&name)?)),
+            (token, position) => Err(unexpected(token, position)),
+        }
+    }
+}
+
+fn unexpected(token: Token, position: usize) -> FormulaError {
+    FormulaError::UnexpectedToken {
+        found: token.to_string(),
+        position,
+    }
+}
+
+fn reference(name: &str) -> Result<CellRef, FormulaError> {
+    name.parse()
+        .map_err(|_| FormulaError::InvalidReference(name.to_string()))
+}
+
+// The text after the '='.
+pub fn parse(text: &str) -> Result<Expr, FormulaError> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        next: 0,
+        depth: 0,
+    };
+    let expr = parser.expression()?;
+    match parser.tokens.get(parser.next) {
+        None => Ok(expr),
+        Some((token, position)) => Err(unexpected(token.clone(), *position)),
+    }
+}
+
+impl Expr {
+    // Every cell or range the formula reads, a single cell as a range of one.
+    pub fn references(&self) -> Vec<(CellRef, CellRef)> {
+        let mut found = Vec::new();
+        self.collect_references(&mut found);
+        found
+    }
+
+    fn collect_references(&self, found: &mut Vec<(CellRef, CellRef)>) {
+        match self {
+            Expr::Number(_) | Expr::Text(_) => {}
+            Expr::Ref(at) => found.push((*at, *at)),
+            Expr::Range(from, to) => found.push((*from, *to)),
+            Expr::Negate(inner) => inner.collect_references(found),
+            Expr::Binary(_, left, right) => {
+                left.collect_references(found);
+                right.collect_references(found);
+            }
+            Expr::Call(_, args) => {
+                for arg in args {
+                    arg.collect_references(found);
+                }
+            }
+        }
+    }
+
+    pub fn evaluate(&self, cells: &dyn Cells) -> Value {
+        match self.evaluate_inner(cells) {
+            Ok(value) => value,
+            Err(e) => Value::Error(e),
+        }
+    }
+
+    fn evaluate_inner(&self, cells: &dyn Cells) -> Result<Value, CellError> {
+        match self {
+            Expr::Number(n) => Ok(n.clone()),
+            Expr::Text(s) => Ok(Value::Text(s.clone())),
+            Expr::Ref(at) => match cells.value(*at).ok_or(CellError::Ref)? {
+                Value::Error(e) => Err(e),
+                value => Ok(value),
+            },
+            Expr::Range(..) => Err(CellError::Value),
+            Expr::Negate(inner) => {
+                let value = number(inner.evaluate_inner(cells)?)?;
+                arithmetic(Op::Subtract, Value::Int(0), value)
+            }
+            Expr::Binary(op, left, right) => {
+                let left = number(left.evaluate_inner(cells)?)?;
+                let right = number(right.evaluate_inner(cells)?)?;
+                arithmetic(*op, left, right)
+            }
+            Expr::Call(name, args) => {
+                let mut numbers = Vec::new();
+                for arg in args {
+                    collect_numbers(arg, cells, &mut numbers)?;
+                }
+                call(name, numbers)
+            }
+        }
+    }
+}
+
+// Ranges skip text and empty cells like spreadsheets do, a single argument has to be a number.
+fn collect_numbers(
+    arg: &Expr,
+    cells: &dyn Cells,
+    numbers: &mut Vec<Value>,
+) -> Result<(), CellError> {
+    let Expr::Range(from, to) = arg else {
+        numbers.push(number(arg.evaluate_inner(cells)?)?);
+        return Ok(());
+    };
+    for value in cells.filled(*from, *to).ok_or(CellError::Ref)? {
+        match value {
+            Value::Error(e) => return Err(e),
+            value @ (Value::Int(_) | Value::Float(_)) => numbers.push(value),
+            Value::Text(_) | Value::Empty => {}
+        }
+    }
+    Ok(())
+}
+
+fn call(name: &str, numbers: Vec<Value>) -> Result<Value, CellError> {
+    match name {
+        "SUM" => numbers
+            .into_iter()
+            .try_fold(Value::Int(0), |sum, n| arithmetic(Op::Add, sum, n)),
+        "COUNT" => Ok(Value::Int(numbers.len() as i64)),
+        "AVERAGE" => {
+            let count = numbers.len() as i64;
+            if count == 0 {
+                return Err(CellError::DivZero);
+            }
+            let sum = call("SUM", numbers)?;
+            arithmetic(Op::Divide, sum, Value::Int(count))
+        }
+        "MIN" | "MAX" => {
+            let pick_first = |a: f64, b: f64| if name == "MIN" { a <= b } else { a >= b };
+            Ok(numbers
+                .into_iter()
+                .reduce(|a, b| {
+                    if pick_first(as_f64(&a), as_f64(&b)) {
+                        a
+                    } else {
+                        b
+                    }
+                })
+                .unwrap_or(Value::Int(0)))
+        }
+        _ => Err(CellError::Name),
+    }
+}
+
+// Empty cells count as 0, text cannot be used as a number.
+fn number(value: Value) -> Result<Value, CellError> {
+    match value {
+        Value::Empty => Ok(Value::Int(0)),
+        Value::Int(_) | Value::Float(_) => Ok(value),
+        Value::Text(_) => Err(CellError::Value),
+        Value::Error(e) => Err(e),
+    }
+}
+
+fn as_f64(value: &Value) -> f64 {
+    match value {
+        Value::Int(n) => *n as f64,
+        Value::Float(n) => *n,
+        _ => 0.0,
+    }
+}
+
+// Integers stay integers as long as the result is exact and fits, otherwise it is a float.
+fn arithmetic(op: Op, left: Value, right: Value) -> Result<Value, CellError> {
+    if let (Value::Int(a), Value::Int(b)) = (&left, &right) {
+        let (a, b) = (*a, *b);
+        let exact = match op {
+            Op::Add => a.checked_add(b),
+            Op::Subtract => a.checked_sub(b),
+            Op::Multiply => a.checked_mul(b),
+            Op::Divide if b == 0 => return Err(CellError::DivZero),
+            Op::Divide => a.checked_rem(b).filter(|r| *r == 0).and(a.checked_div(b)),
+        };
+        if let Some(n) = exact {
+            return Ok(Value::Int(n));
+        }
+    }
+    let (a, b) = (as_f64(&left), as_f64(&right));
+    let result = match op {
+        Op::Add => a + b,
+        Op::Subtract => a - b,
+        Op::Multiply => a * b,
+        Op::Divide if b == 0.0 => return Err(CellError::DivZero),
+        Op::Divide => a / b,
+    };
+    if result.is_finite() {
+        Ok(Value::Float(result))
+    } else {
+        Err(CellError::Num)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str) -> Value {
+        parse(text)
+            .unwrap()
+            .evaluate(&|_: CellRef| Some(Value::Empty))
+    }
+
+    #[test]
+    fn precedence_and_types() {
+        assert_eq!(eval("1 + 2 * 3"), Value::Int(7));
+        assert_eq!(eval("(1 + 2) * 3"), Value::Int(9));
+        assert_eq!(eval("-2 - -3"), Value::Int(1));
+        assert_eq!(eval("7 / 2"), Value::Float(3.5));
+        assert_eq!(eval("8 / 2"), Value::Int(4));
+        assert_eq!(eval("0.5 + 1"), Value::Float(1.5));
+        assert_eq!(
+            eval("9223372036854775807 + 1"),
+            Value::Float(9223372036854775808.0)
+        );
+        assert_eq!(
+            eval("\"say \"\"hi\"\"\""),
+            Value::Text(String::from("say \"hi\""))
+        );
+        assert_eq!(eval("1 / 0"), Value::Error(CellError::DivZero));
+        assert_eq!(eval("\"a\" + 1"), Value::Error(CellError::Value));
+        assert_eq!(eval("NOPE(1)"), Value::Error(CellError::Name));
+        assert_eq!(eval("AVERAGE()"), Value::Error(CellError::DivZero));
+        assert_eq!(eval("MAX(1, 5.5, 3)"), Value::Float(5.5));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(parse("1 +"), Err(FormulaError::UnexpectedEnd));
+        assert_eq!(
+            parse("1 + )"),
+            Err(FormulaError::UnexpectedToken {
+                found: String::from("')'"),
+                position: 4
+            })
+        );
+        assert_eq!(
+            parse("1 # 2"),
+            Err(FormulaError::UnexpectedChar {
+                ch: '#',
+                position: 2
+            })
+        );
+        assert_eq!(
+            parse("A1B"),
+            Err(FormulaError::InvalidReference(String::from("A1B")))
+        );
+        assert_eq!(
+            parse("SUM(1:A2)"),
+            Err(FormulaError::InvalidReference(String::from(":")))
+        );
+        assert!(parse("(1").is_err());
+        assert!(parse("1 2").is_err());
+    }
+
+    #[test]
+    fn nesting_is_capped() {
+        let too_deep = Err(FormulaError::TooDeep { max: MAX_DEPTH });
+        let brackets = |n: usize| format!("{}1{}", "(".repeat(n), ")".repeat(n));
+        assert_eq!(eval(&brackets(100)), Value::Int(1));
+        assert_eq!(parse(&brackets(MAX_DEPTH)), too_deep);
+        assert_eq!(parse(&format!("{}1", "-".repeat(MAX_DEPTH))), too_deep);
+        assert_eq!(parse(&format!("1{}", "+1".repeat(MAX_DEPTH))), too_deep);
+        assert_eq!(parse(&format!("1{}", "*1".repeat(MAX_DEPTH))), too_deep);
+        assert_eq!(
+            parse(&format!(
+                "{}1{}",
+                "SUM(".repeat(MAX_DEPTH),
+                ")".repeat(MAX_DEPTH)
+            )),
+            too_deep
+        );
+        // siblings do not add up, only nesting does
+        let wide = vec![brackets(100); 100].join("+");
+        assert_eq!(eval(&wide), Value::Int(100));
+    }
+
+    #[test]
+    fn references() {
+        let expr = parse("sum(a1:b2, C3) * d4").unwrap();
+        let a1: CellRef = "A1".parse().unwrap();
+        let b2: CellRef = "B2".parse().unwrap();
+        let c3: CellRef = "C3".parse().unwrap();
+        let d4: CellRef = "D4".parse().unwrap();
+        assert_eq!(expr.references(), vec![(a1, b2), (c3, c3), (d4, d4)]);
+    }
+}
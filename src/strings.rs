@@ -0,0 +1,237 @@
+// https://doc.rust-lang.org/book/ch08-02-strings.html#indexing-into-strings
+// What indexing_into_strings warns about, done safely: text is cut between grapheme
+// clusters, the characters a reader sees, so "स्ते" or an emoji family stays whole. Slicing
+// at a bad index is an error instead of a panic, and widths are counted in terminal columns.
+use std::borrow::Cow;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceError {
+    // end is past the last grapheme or byte
+    OutOfRange { end: usize, len: usize },
+    Reversed { start: usize, end: usize },
+    // a byte index inside a grapheme, maybe inside a character
+    NotBoundary(usize),
+}
+
+impl Display for SliceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SliceError::OutOfRange { end, len } => {
+                write!(f, "range ends at {} but the length is {}", end, len)
+            }
+            SliceError::Reversed { start, end } => {
+                write!(f, "range starts at {} after its end {}", start, end)
+            }
+            SliceError::NotBoundary(index) => {
+                write!(f, "byte {} is inside a grapheme", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SliceError {}
+
+pub fn grapheme_count(s: &str) -> usize {
+    s.graphemes(true).count()
+}
+
+// Graphemes from start up to but not including end, like &s[start..end] if each grapheme
+// were one byte.
+pub fn slice(s: &str, range: Range<usize>) -> Result<&str, SliceError> {
+    let Range { start, end } = range;
+    if start > end {
+        return Err(SliceError::Reversed { start, end });
+    }
+    // byte offsets of every grapheme start and of the end of the text
+    let mut offsets = s
+        .grapheme_indices(true)
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(s.len()));
+    let from = offsets.nth(start);
+    let to = if end == start {
+        from
+    } else {
+        offsets.nth(end - start - 1)
+    };
+    match (from, to) {
+        (Some(from), Some(to)) => Ok(&s[from..to]),
+        _ => Err(SliceError::OutOfRange {
+            end,
+            len: grapheme_count(s),
+        }),
+    }
+}
+
+// &s[range] with bytes, but only between graphemes and without a panic.
+pub fn slice_bytes(s: &str, range: Range<usize>) -> Result<&str, SliceError> {
+    let Range { start, end } = range;
+    if start > end {
+        return Err(SliceError::Reversed { start, end });
+    }
+    if end > s.len() {
+        return Err(SliceError::OutOfRange { end, len: s.len() });
+    }
+    for index in [start, end] {
+        if !is_grapheme_boundary(s, index) {
+            return Err(SliceError::NotBoundary(index));
+        }
+    }
+    Ok(&s[start..end])
+}
+
+pub fn is_grapheme_boundary(s: &str, index: usize) -> bool {
+    s.is_char_boundary(index)
+        && GraphemeCursor::new(index, s.len(), true)
+            .is_boundary(s, 0)
+            .unwrap_or(false)
+}
+
+// at most max graphemes
+pub fn truncate(s: &str, max: usize) -> &str {
+    match s.grapheme_indices(true).nth(max) {
+        Some((offset, _)) => &s[..offset],
+        None => s,
+    }
+}
+
+// Columns the text takes in a terminal: 2 for CJK and most emoji, 0 for combining marks.
+pub fn width(s: &str) -> usize {
+    s.graphemes(true).map(UnicodeWidthStr::width).sum()
+}
+
+// the longest start of the text that fits in the columns, a wide grapheme that would only
+// half fit is left out
+pub fn truncate_width(s: &str, columns: usize) -> &str {
+    let mut used = 0;
+    for (offset, grapheme) in s.grapheme_indices(true) {
+        used += grapheme.width();
+        if used > columns {
+            return &s[..offset];
+        }
+    }
+    s
+}
+
+// Fits the text in the columns, ending with the marker when something had to go.
+pub fn ellipsize<'a>(s: &'a str, columns: usize, marker: &str) -> Cow<'a, str> {
+    if width(s) <= columns {
+        return Cow::Borrowed(s);
+    }
+    let kept = truncate_width(s, columns.saturating_sub(width(marker)));
+    Cow::Owned(format!("{}{}", kept, marker))
+}
+
+// "नमस्ते" becomes "स्तेमन", not the marks on the wrong letters that reversing chars gives.
+pub fn reverse(s: &str) -> String {
+    s.graphemes(true).rev().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // the samples from the collections examples
+    const KOREAN: &str = "안녕하세요";
+    const RUSSIAN: &str = "Здравствуйте";
+    const HINDI: &str = "नमस्ते";
+
+    #[test]
+    fn counts_graphemes_not_chars() {
+        assert_eq!(grapheme_count(KOREAN), 5);
+        assert_eq!(grapheme_count(RUSSIAN), 12);
+        assert_eq!(HINDI.chars().count(), 6);
+        assert_eq!(grapheme_count(HINDI), 3);
+        assert_eq!(grapheme_count("👨\u{200d}👩\u{200d}👧"), 1);
+    }
+
+    #[test]
+    fn slices_by_grapheme() {
+        assert_eq!(slice(KOREAN, 1..3), Ok("녕하"));
+        assert_eq!(slice(RUSSIAN, 0..2), Ok("Зд"));
+        assert_eq!(slice(HINDI, 2..3), Ok("स्ते"));
+        assert_eq!(slice(HINDI, 3..3), Ok(""));
+        assert_eq!(
+            slice(HINDI, 1..4),
+            Err(SliceError::OutOfRange { end: 4, len: 3 })
+        );
+        let (start, end) = (3, 1);
+        assert_eq!(
+            slice(KOREAN, start..end),
+            Err(SliceError::Reversed { start, end })
+        );
+    }
+
+    #[test]
+    fn slices_bytes_without_panicking() {
+        // &hello[0..4] from the book
+        assert_eq!(slice_bytes(RUSSIAN, 0..4), Ok("Зд"));
+        assert_eq!(slice_bytes(RUSSIAN, 0..3), Err(SliceError::NotBoundary(3)));
+        assert_eq!(slice_bytes(KOREAN, 3..6), Ok("녕"));
+        assert_eq!(
+            slice_bytes(KOREAN, 0..16),
+            Err(SliceError::OutOfRange { end: 16, len: 15 })
+        );
+        // स is 3 bytes and the virama after it is a char boundary but not a grapheme one
+        assert!(HINDI.is_char_boundary(9));
+        assert_eq!(slice_bytes(HINDI, 6..9), Err(SliceError::NotBoundary(9)));
+        assert_eq!(slice_bytes(HINDI, 6..HINDI.len()), Ok("स्ते"));
+    }
+
+    #[test]
+    fn truncates() {
+        assert_eq!(truncate(HINDI, 2), "नम");
+        assert_eq!(truncate(KOREAN, 10), KOREAN);
+        assert_eq!(truncate(RUSSIAN, 0), "");
+        assert_eq!(truncate_width(KOREAN, 5), "안녕");
+        assert_eq!(truncate_width(RUSSIAN, 5), "Здрав");
+        assert_eq!(ellipsize(RUSSIAN, 6, "…"), "Здрав…");
+        assert_eq!(ellipsize(KOREAN, 7, "..."), "안녕...");
+        assert!(matches!(ellipsize(KOREAN, 10, "…"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn display_width() {
+        assert_eq!(width(KOREAN), 10);
+        assert_eq!(width(RUSSIAN), 12);
+        assert_eq!(width("日本"), 4);
+        assert_eq!(width("e\u{301}"), 1);
+        assert_eq!(width("👨\u{200d}👩\u{200d}👧"), 2);
+        assert_eq!(width("🇯🇵"), 2);
+    }
+
+    #[test]
+    fn reverses_graphemes() {
+        assert_eq!(reverse(KOREAN), "요세하녕안");
+        assert_eq!(reverse(RUSSIAN), "етйувтсвардЗ");
+        assert_eq!(reverse(HINDI), "स्तेमन");
+        assert_eq!(
+            reverse("ae\u{301}👨\u{200d}👩\u{200d}👧"),
+            "👨\u{200d}👩\u{200d}👧e\u{301}a"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(s in "\\PC{0,12}", start in 0usize..40, end in 0usize..40) {
+            if let Ok(part) = slice_bytes(&s, start..end) {
+                prop_assert_eq!(part.len(), end - start);
+            }
+            let count = grapheme_count(&s);
+            match slice(&s, start..end) {
+                Ok(part) => {
+                    prop_assert_eq!(grapheme_count(part), end - start);
+                    let rest = slice(&s, end..count).unwrap();
+                    prop_assert_eq!(format!("{}{}{}", slice(&s, 0..start).unwrap(), part, rest), s.clone());
+                }
+                Err(_) => prop_assert!(start > end || end > count),
+            }
+            prop_assert!(width(truncate_width(&s, start)) <= start);
+        }
+    }
+}
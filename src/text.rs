@@ -0,0 +1,494 @@
+// https://doc.rust-lang.org/book/ch08-03-hash-maps.html#updating-a-value-based-on-the-old-value
+// The word counter from the hash maps chapter grown into text analysis: words split by the
+// Unicode rules instead of whitespace, case folding and stop words, n-grams, TF-IDF across
+// documents and readability scores. Input is read a line at a time so files of any size work.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, BufRead};
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+pub const ENGLISH_STOP_WORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "am", "an", "and", "any", "are", "as", "at", "be",
+    "because", "been", "but", "by", "can", "could", "did", "do", "does", "for", "from", "had",
+    "has", "have", "he", "her", "him", "his", "how", "i", "if", "in", "into", "is", "it", "its",
+    "just", "me", "more", "my", "no", "not", "of", "on", "or", "our", "out", "she", "so", "some",
+    "than", "that", "the", "their", "them", "then", "there", "these", "they", "this", "to", "up",
+    "us", "was", "we", "were", "what", "when", "which", "who", "will", "with", "would", "you",
+    "your",
+];
+
+// Decides what counts as a word. Stop words are matched without regard to case even when
+// case folding is off.
+#[derive(Debug, Clone)]
+pub struct Tokenizer {
+    fold_case: bool,
+    stop_words: HashSet<String>,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Tokenizer {
+            fold_case: true,
+            stop_words: HashSet::new(),
+        }
+    }
+}
+
+impl Tokenizer {
+    pub fn new() -> Tokenizer {
+        Tokenizer::default()
+    }
+
+    pub fn fold_case(mut self, fold_case: bool) -> Tokenizer {
+        self.fold_case = fold_case;
+        self
+    }
+
+    pub fn stop_words<S: AsRef<str>>(mut self, words: impl IntoIterator<Item = S>) -> Tokenizer {
+        self.stop_words
+            .extend(words.into_iter().map(|word| fold(word.as_ref())));
+        self
+    }
+
+    // one or more words on each line, anything after a '#' is a comment
+    pub fn stop_words_from(self, reader: impl BufRead) -> io::Result<Tokenizer> {
+        let mut words = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.split('#').next().unwrap_or_default();
+            words.extend(line.split_whitespace().map(String::from));
+        }
+        Ok(self.stop_words(words))
+    }
+
+    pub fn is_stop_word(&self, word: &str) -> bool {
+        self.stop_words.contains(&fold(word))
+    }
+
+    // the form a word is counted under
+    pub fn normalize(&self, word: &str) -> String {
+        if self.fold_case {
+            fold(word)
+        } else {
+            word.nfc().collect()
+        }
+    }
+
+    // Words as UAX #29 finds them, so "can't" is one word and punctuation is dropped, with
+    // stop words left out.
+    pub fn tokens<'a>(&'a self, text: &'a str) -> impl Iterator<Item = String> + 'a {
+        text.unicode_words()
+            .filter(|word| !self.is_stop_word(word))
+            .map(|word| self.normalize(word))
+    }
+}
+
+// Compatibility forms first so "ﬁ" matches "fi", then lower case.
+fn fold(word: &str) -> String {
+    word.nfkc().flat_map(char::to_lowercase).collect()
+}
+
+// Counts for the Flesch scores. Every word counts here, stop words too.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Readability {
+    words: usize,
+    syllables: usize,
+    sentences: usize,
+    // words were seen since the last '.', '!' or '?'
+    in_sentence: bool,
+}
+
+impl Readability {
+    pub fn words(&self) -> usize {
+        self.words
+    }
+
+    pub fn syllables(&self) -> usize {
+        self.syllables
+    }
+
+    // text that does not end with a full stop still makes a sentence
+    pub fn sentences(&self) -> usize {
+        self.sentences + self.in_sentence as usize
+    }
+
+    // 100 is very easy to read, 0 and below is very hard
+    pub fn reading_ease(&self) -> Option<f64> {
+        let (per_sentence, per_word) = self.ratios()?;
+        Some(206.835 - 1.015 * per_sentence - 84.6 * per_word)
+    }
+
+    // Flesch-Kincaid, about the school year a reader needs
+    pub fn grade_level(&self) -> Option<f64> {
+        let (per_sentence, per_word) = self.ratios()?;
+        Some(0.39 * per_sentence + 11.8 * per_word - 15.59)
+    }
+
+    // words per sentence and syllables per word
+    fn ratios(&self) -> Option<(f64, f64)> {
+        if self.words == 0 {
+            return None;
+        }
+        let words = self.words as f64;
+        Some((
+            words / self.sentences() as f64,
+            self.syllables as f64 / words,
+        ))
+    }
+
+    fn add_word(&mut self, word: &str) {
+        self.words += 1;
+        self.syllables += syllables(word);
+        self.in_sentence = true;
+    }
+
+    // returns whether a sentence ended, "?!" or "..." ends only one
+    fn end_sentence(&mut self) -> bool {
+        let ended = self.in_sentence;
+        if ended {
+            self.sentences += 1;
+            self.in_sentence = false;
+        }
+        ended
+    }
+}
+
+// Groups of vowels less a silent final 'e', at least one. Only meant for English.
+pub fn syllables(word: &str) -> usize {
+    let word = word.to_lowercase();
+    let is_vowel = |c: char| "aeiouy".contains(c);
+    let mut count = 0;
+    let mut previous = None;
+    for c in word.chars() {
+        if is_vowel(c) && !previous.is_some_and(is_vowel) {
+            count += 1;
+        }
+        previous = Some(c);
+    }
+    if count > 1 && word.ends_with('e') && !word.ends_with("le") && !word.ends_with("ee") {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+// Everything counted in one text. N-grams do not run across the end of a sentence.
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    counts: HashMap<String, usize>,
+    total: usize,
+    ngram_size: usize,
+    ngrams: HashMap<Vec<String>, usize>,
+    window: VecDeque<String>,
+    readability: Readability,
+}
+
+impl Analysis {
+    // sizes below 2 count no n-grams
+    pub fn new(ngram_size: usize) -> Analysis {
+        Analysis {
+            counts: HashMap::new(),
+            total: 0,
+            ngram_size,
+            ngrams: HashMap::new(),
+            window: VecDeque::new(),
+            readability: Readability::default(),
+        }
+    }
+
+    pub fn from_text(text: &str, tokenizer: &Tokenizer, ngram_size: usize) -> Analysis {
+        let mut analysis = Analysis::new(ngram_size);
+        analysis.feed(text, tokenizer);
+        analysis
+    }
+
+    // Only one line is in memory at a time.
+    pub fn from_reader(
+        mut reader: impl BufRead,
+        tokenizer: &Tokenizer,
+        ngram_size: usize,
+    ) -> io::Result<Analysis> {
+        let mut analysis = Analysis::new(ngram_size);
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            analysis.feed(&line, tokenizer);
+            line.clear();
+        }
+        Ok(analysis)
+    }
+
+    // Can be called with any pieces of a text as long as they do not split a word.
+    pub fn feed(&mut self, text: &str, tokenizer: &Tokenizer) {
+        for segment in text.split_word_bounds() {
+            if segment.chars().any(char::is_alphanumeric) {
+                self.readability.add_word(segment);
+                if !tokenizer.is_stop_word(segment) {
+                    self.add(tokenizer.normalize(segment));
+                }
+            } else if segment.contains(['.', '!', '?']) && self.readability.end_sentence() {
+                self.window.clear();
+            }
+        }
+    }
+
+    fn add(&mut self, word: String) {
+        self.total += 1;
+        if self.ngram_size >= 2 {
+            self.window.push_back(word.clone());
+            if self.window.len() > self.ngram_size {
+                self.window.pop_front();
+            }
+            if self.window.len() == self.ngram_size {
+                let ngram = self.window.iter().cloned().collect();
+                *self.ngrams.entry(ngram).or_insert(0) += 1;
+            }
+        }
+        *self.counts.entry(word).or_insert(0) += 1;
+    }
+
+    // counted words, stop words are not
+    pub fn total_words(&self) -> usize {
+        self.total
+    }
+
+    pub fn distinct_words(&self) -> usize {
+        self.counts.len()
+    }
+
+    // word in the form the tokenizer counts it
+    pub fn count(&self, word: &str) -> usize {
+        self.counts.get(word).copied().unwrap_or(0)
+    }
+
+    // most frequent first, words with the same count in alphabetical order
+    pub fn top(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut counts: Vec<(&str, usize)> = self
+            .counts
+            .iter()
+            .map(|(word, count)| (word.as_str(), *count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts.truncate(n);
+        counts
+    }
+
+    // n-grams with their words joined by a space, ordered like top
+    pub fn top_ngrams(&self, n: usize) -> Vec<(String, usize)> {
+        let mut counts: Vec<(String, usize)> = self
+            .ngrams
+            .iter()
+            .map(|(words, count)| (words.join(" "), *count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts.truncate(n);
+        counts
+    }
+
+    pub fn readability(&self) -> Readability {
+        self.readability
+    }
+}
+
+// Named documents for TF-IDF.
+#[derive(Debug, Clone, Default)]
+pub struct Corpus {
+    documents: Vec<(String, Analysis)>,
+}
+
+impl Corpus {
+    pub fn new() -> Corpus {
+        Corpus::default()
+    }
+
+    pub fn add(&mut self, name: &str, analysis: Analysis) {
+        self.documents.push((name.to_string(), analysis));
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = (&str, &Analysis)> {
+        self.documents
+            .iter()
+            .map(|(name, analysis)| (name.as_str(), analysis))
+    }
+
+    // how many documents have the word
+    pub fn document_frequency(&self, word: &str) -> usize {
+        self.documents
+            .iter()
+            .filter(|(_, analysis)| analysis.count(word) > 0)
+            .count()
+    }
+
+    // The words that set a document apart: how often a word is in it, times the log of
+    // how rare the word is across the corpus. A word in every document scores 0.
+    pub fn tf_idf(&self, document: usize, n: usize) -> Vec<(&str, f64)> {
+        let Some((_, analysis)) = self.documents.get(document) else {
+            return Vec::new();
+        };
+        let documents = self.documents.len() as f64;
+        let total = analysis.total_words() as f64;
+        let mut scores: Vec<(&str, f64)> = analysis
+            .counts
+            .iter()
+            .map(|(word, count)| {
+                let idf = (documents / self.document_frequency(word) as f64).ln();
+                (word.as_str(), *count as f64 / total * idf)
+            })
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+        scores.truncate(n);
+        scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn english() -> Tokenizer {
+        Tokenizer::new().stop_words(ENGLISH_STOP_WORDS)
+    }
+
+    #[test]
+    fn unicode_tokens() {
+        let tokens: Vec<String> = Tokenizer::new()
+            .tokens("Can't stop: Ünïcode, ПРИВЕТ мир! The ﬁle's 3.5 GB.")
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                "can't",
+                "stop",
+                "ünïcode",
+                "привет",
+                "мир",
+                "the",
+                "file's",
+                "3.5",
+                "gb"
+            ]
+        );
+
+        let kept: Vec<String> = english()
+            .fold_case(false)
+            .tokens("The Cat and THE hat")
+            .collect();
+        assert_eq!(kept, ["Cat", "hat"]);
+    }
+
+    #[test]
+    fn stop_word_files() {
+        let list = "# common words\nthe a\nand # joins\n";
+        let tokenizer = Tokenizer::new().stop_words_from(list.as_bytes()).unwrap();
+        assert!(tokenizer.is_stop_word("The"));
+        assert!(tokenizer.is_stop_word("and"));
+        assert!(!tokenizer.is_stop_word("joins"));
+    }
+
+    #[test]
+    fn counts_the_book_example() {
+        let analysis = Analysis::from_text("hello world wonderful world", &Tokenizer::new(), 2);
+        assert_eq!(analysis.top(2), [("world", 2), ("hello", 1)]);
+        assert_eq!(analysis.count("wonderful"), 1);
+        assert_eq!(analysis.total_words(), 4);
+        assert_eq!(analysis.distinct_words(), 3);
+        assert_eq!(
+            analysis.top_ngrams(5),
+            [
+                ("hello world".to_string(), 1),
+                ("wonderful world".to_string(), 1),
+                ("world wonderful".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn ngrams_stop_at_sentences() {
+        let text = "Red fish. Blue fish! Red fish, blue fish?";
+        let analysis = Analysis::from_text(text, &english(), 2);
+        assert_eq!(
+            analysis.top_ngrams(10),
+            [
+                ("blue fish".to_string(), 2),
+                ("red fish".to_string(), 2),
+                ("fish blue".to_string(), 1),
+            ]
+        );
+        assert_eq!(analysis.readability().sentences(), 3);
+    }
+
+    #[test]
+    fn streams_lines() {
+        let text = "The first line\nand the second line.\nA third";
+        let whole = Analysis::from_text(text, &english(), 3);
+        let streamed = Analysis::from_reader(text.as_bytes(), &english(), 3).unwrap();
+        assert_eq!(streamed.top(10), whole.top(10));
+        assert_eq!(streamed.top_ngrams(10), whole.top_ngrams(10));
+        assert_eq!(streamed.readability(), whole.readability());
+
+        let invalid: &[u8] = b"ok\n\xff\n";
+        assert!(Analysis::from_reader(invalid, &english(), 2).is_err());
+    }
+
+    #[test]
+    fn readability_scores() {
+        for (word, expected) in [
+            ("cat", 1),
+            ("table", 2),
+            ("make", 1),
+            ("beautiful", 3),
+            ("tree", 1),
+        ] {
+            assert_eq!(syllables(word), expected, "{}", word);
+        }
+
+        let easy = Analysis::from_text("The cat sat. The dog ran.", &Tokenizer::new(), 0);
+        let hard = Analysis::from_text(
+            "Institutional considerations necessitate comprehensive organizational evaluation.",
+            &Tokenizer::new(),
+            0,
+        );
+        let easy = easy.readability();
+        assert_eq!(
+            (easy.words(), easy.sentences(), easy.syllables()),
+            (6, 2, 6)
+        );
+        assert!((easy.reading_ease().unwrap() - 119.19).abs() < 1e-9);
+        assert!(hard.readability().reading_ease().unwrap() < 0.0);
+        assert!(hard.readability().grade_level().unwrap() > easy.grade_level().unwrap());
+        assert_eq!(Readability::default().reading_ease(), None);
+    }
+
+    #[test]
+    fn tf_idf_finds_distinct_words() {
+        let tokenizer = english();
+        let mut corpus = Corpus::new();
+        corpus.add(
+            "rust",
+            Analysis::from_text("the borrow checker checks borrow rules", &tokenizer, 0),
+        );
+        corpus.add(
+            "go",
+            Analysis::from_text("the garbage collector checks rules", &tokenizer, 0),
+        );
+        corpus.add(
+            "c",
+            Analysis::from_text("manual memory rules", &tokenizer, 0),
+        );
+
+        assert_eq!(corpus.document_frequency("rules"), 3);
+        let rust = corpus.tf_idf(0, 10);
+        assert_eq!(rust[0].0, "borrow");
+        assert_eq!(rust.last().unwrap(), &("rules", 0.0));
+        let expected = 2.0 / 5.0 * 3f64.ln();
+        assert!((rust[0].1 - expected).abs() < 1e-12);
+        assert!(corpus.tf_idf(3, 10).is_empty());
+    }
+}
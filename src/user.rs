@@ -0,0 +1,456 @@
+// https://doc.rust-lang.org/book/ch05-00-structs.html
+// https://doc.rust-lang.org/book/ch09-03-to-panic-or-not-to-panic.html#creating-custom-types-for-validation
+// A User can only be built from values that went through validation, like the Guess type
+// in the error handling chapter but returning typed errors instead of panicking.
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use unicode_normalization::UnicodeNormalization;
+
+pub mod audit;
+pub mod patch;
+pub mod profile;
+pub mod registry;
+
+pub const EMAIL_MAX_LEN: usize = 254;
+pub const EMAIL_LOCAL_MAX_LEN: usize = 64;
+pub const EMAIL_DOMAIN_LABEL_MAX_LEN: usize = 63;
+pub const USERNAME_MIN_LEN: usize = 3;
+pub const USERNAME_MAX_LEN: usize = 32;
+
+const EMAIL_LOCAL_SPECIALS: &str = "!#$%&'*+/=?^_`{|}~-";
+const USERNAME_SPECIALS: &str = "_-.";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmailError {
+    Empty,
+    TooLong { max: usize, actual: usize },
+    MissingAt,
+    MultipleAt,
+    EmptyLocalPart,
+    LocalPartTooLong { max: usize, actual: usize },
+    InvalidDots,
+    ForbiddenCharacter(char),
+    EmptyDomain,
+    DomainWithoutDot,
+    InvalidDomainLabel(String),
+}
+
+impl Display for EmailError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EmailError::Empty => write!(f, "email is empty"),
+            EmailError::TooLong { max, actual } => {
+                write!(f, "email is {} characters long, max is {}", actual, max)
+            }
+            EmailError::MissingAt => write!(f, "email has no '@'"),
+            EmailError::MultipleAt => write!(f, "email has more than one '@'"),
+            EmailError::EmptyLocalPart => write!(f, "email has nothing before '@'"),
+            EmailError::LocalPartTooLong { max, actual } => write!(
+                f,
+                "email local part is {} characters long, max is {}",
+                actual, max
+            ),
+            EmailError::InvalidDots => {
+                write!(f, "email local part has a leading, trailing or double dot")
+            }
+            EmailError::ForbiddenCharacter(c) => {
+                write!(f, "email contains forbidden character {:?}", c)
+            }
+            EmailError::EmptyDomain => write!(f, "email has nothing after '@'"),
+            EmailError::DomainWithoutDot => write!(f, "email domain has no '.'"),
+            EmailError::InvalidDomainLabel(label) => {
+                write!(f, "email domain label {:?} is not valid", label)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EmailError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsernameError {
+    TooShort { min: usize, actual: usize },
+    TooLong { max: usize, actual: usize },
+    InvalidStart(char),
+    ForbiddenCharacter(char),
+}
+
+impl Display for UsernameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            UsernameError::TooShort { min, actual } => {
+                write!(f, "username is {} characters long, min is {}", actual, min)
+            }
+            UsernameError::TooLong { max, actual } => {
+                write!(f, "username is {} characters long, max is {}", actual, max)
+            }
+            UsernameError::InvalidStart(c) => {
+                write!(f, "username must start with a letter or digit, got {:?}", c)
+            }
+            UsernameError::ForbiddenCharacter(c) => {
+                write!(f, "username contains forbidden character {:?}", c)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UsernameError {}
+
+// Either of the two validations failed when building a User from raw strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    Email(EmailError),
+    Username(UsernameError),
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::Email(e) => write!(f, "invalid email: {}", e),
+            ValidationError::Username(e) => write!(f, "invalid username: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ValidationError::Email(e) => Some(e),
+            ValidationError::Username(e) => Some(e),
+        }
+    }
+}
+
+impl From<EmailError> for ValidationError {
+    fn from(e: EmailError) -> Self {
+        ValidationError::Email(e)
+    }
+}
+
+impl From<UsernameError> for ValidationError {
+    fn from(e: UsernameError) -> Self {
+        ValidationError::Username(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Email(String);
+
+impl Email {
+    // Trims, applies NFC and lowercases the whole address before checking it.
+    pub fn parse(raw: &str) -> Result<Email, EmailError> {
+        let normalized: String = raw.trim().nfc().collect::<String>().to_lowercase();
+        if normalized.is_empty() {
+            return Err(EmailError::Empty);
+        }
+        let len = normalized.chars().count();
+        if len > EMAIL_MAX_LEN {
+            return Err(EmailError::TooLong {
+                max: EMAIL_MAX_LEN,
+                actual: len,
+            });
+        }
+
+        let mut parts = normalized.split('@');
+        let local = parts.next().unwrap_or_default();
+        let domain = match parts.next() {
+            Some(domain) => domain,
+            None => return Err(EmailError::MissingAt),
+        };
+        if parts.next().is_some() {
+            return Err(EmailError::MultipleAt);
+        }
+
+        validate_local_part(local)?;
+        validate_domain(domain)?;
+        Ok(Email(normalized))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn local_part(&self) -> &str {
+        self.0.split('@').next().unwrap_or_default()
+    }
+
+    pub fn domain(&self) -> &str {
+        self.0.split('@').nth(1).unwrap_or_default()
+    }
+}
+
+fn validate_local_part(local: &str) -> Result<(), EmailError> {
+    if local.is_empty() {
+        return Err(EmailError::EmptyLocalPart);
+    }
+    let len = local.chars().count();
+    if len > EMAIL_LOCAL_MAX_LEN {
+        return Err(EmailError::LocalPartTooLong {
+            max: EMAIL_LOCAL_MAX_LEN,
+            actual: len,
+        });
+    }
+    if local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+        return Err(EmailError::InvalidDots);
+    }
+    match local
+        .chars()
+        .find(|&c| !(c.is_alphanumeric() || c == '.' || EMAIL_LOCAL_SPECIALS.contains(c)))
+    {
+        Some(c) => Err(EmailError::ForbiddenCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+fn validate_domain(domain: &str) -> Result<(), EmailError> {
+    if domain.is_empty() {
+        return Err(EmailError::EmptyDomain);
+    }
+    if !domain.contains('.') {
+        return Err(EmailError::DomainWithoutDot);
+    }
+    for label in domain.split('.') {
+        let valid = !label.is_empty()
+            && label.chars().count() <= EMAIL_DOMAIN_LABEL_MAX_LEN
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_alphanumeric() || c == '-');
+        if !valid {
+            return Err(EmailError::InvalidDomainLabel(label.to_string()));
+        }
+    }
+    Ok(())
+}
+
+impl FromStr for Email {
+    type Err = EmailError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Email::parse(s)
+    }
+}
+
+impl Display for Email {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl AsRef<str> for Email {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Username(String);
+
+impl Username {
+    // NFKC folds look-alike compatibility characters (e.g. fullwidth letters) before lowercasing,
+    // so "Ｎａｍｅ" and "name" end up as the same username.
+    pub fn parse(raw: &str) -> Result<Username, UsernameError> {
+        let normalized: String = raw.trim().nfkc().collect::<String>().to_lowercase();
+        let len = normalized.chars().count();
+        if len < USERNAME_MIN_LEN {
+            return Err(UsernameError::TooShort {
+                min: USERNAME_MIN_LEN,
+                actual: len,
+            });
+        }
+        if len > USERNAME_MAX_LEN {
+            return Err(UsernameError::TooLong {
+                max: USERNAME_MAX_LEN,
+                actual: len,
+            });
+        }
+        if let Some(c) = normalized
+            .chars()
+            .find(|&c| !(c.is_alphanumeric() || USERNAME_SPECIALS.contains(c)))
+        {
+            return Err(UsernameError::ForbiddenCharacter(c));
+        }
+        match normalized.chars().next() {
+            Some(c) if !c.is_alphanumeric() => Err(UsernameError::InvalidStart(c)),
+            _ => Ok(Username(normalized)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Username {
+    type Err = UsernameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Username::parse(s)
+    }
+}
+
+impl Display for Username {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl AsRef<str> for Username {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub active: bool,
+    pub username: Username,
+    pub email: Email,
+    pub sign_in_count: u64,
+}
+
+impl User {
+    // same defaults as the build_user example in the structs chapter
+    pub fn new(email: Email, username: Username) -> User {
+        User {
+            email,
+            username,
+            active: true,
+            sign_in_count: 1,
+        }
+    }
+
+    pub fn parse(email: &str, username: &str) -> Result<User, ValidationError> {
+        Ok(User::new(Email::parse(email)?, Username::parse(username)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn email_is_normalized() {
+        let email = Email::parse("  Someone@Example.COM ").unwrap();
+        assert_eq!(email.as_str(), "someone@example.com");
+        assert_eq!(email.local_part(), "someone");
+        assert_eq!(email.domain(), "example.com");
+    }
+
+    #[test]
+    fn email_composed_and_decomposed_forms_are_equal() {
+        let composed = Email::parse("jos\u{e9}@example.com").unwrap();
+        let decomposed = Email::parse("jose\u{301}@example.com").unwrap();
+        assert_eq!(composed, decomposed);
+    }
+
+    #[test]
+    fn malformed_emails_are_rejected() {
+        assert_eq!(Email::parse(""), Err(EmailError::Empty));
+        assert_eq!(Email::parse("name.example.com"), Err(EmailError::MissingAt));
+        assert_eq!(Email::parse("a@b@c.com"), Err(EmailError::MultipleAt));
+        assert_eq!(
+            Email::parse("@example.com"),
+            Err(EmailError::EmptyLocalPart)
+        );
+        assert_eq!(Email::parse("name@"), Err(EmailError::EmptyDomain));
+        assert_eq!(
+            Email::parse("name@localhost"),
+            Err(EmailError::DomainWithoutDot)
+        );
+        assert_eq!(
+            Email::parse(".name@example.com"),
+            Err(EmailError::InvalidDots)
+        );
+        assert_eq!(
+            Email::parse("na..me@example.com"),
+            Err(EmailError::InvalidDots)
+        );
+        assert_eq!(
+            Email::parse("na me@example.com"),
+            Err(EmailError::ForbiddenCharacter(' '))
+        );
+        assert_eq!(
+            Email::parse("name@-example.com"),
+            Err(EmailError::InvalidDomainLabel(String::from("-example")))
+        );
+        assert_eq!(
+            Email::parse("name@example..com"),
+            Err(EmailError::InvalidDomainLabel(String::new()))
+        );
+    }
+
+    #[test]
+    fn overlong_emails_are_rejected() {
+        let local = "a".repeat(EMAIL_LOCAL_MAX_LEN + 1);
+        assert_eq!(
+            Email::parse(&format!("{}@example.com", local)),
+            Err(EmailError::LocalPartTooLong {
+                max: EMAIL_LOCAL_MAX_LEN,
+                actual: EMAIL_LOCAL_MAX_LEN + 1
+            })
+        );
+        let domain = format!("{}.com", vec!["a".repeat(60); 5].join("."));
+        assert!(matches!(
+            Email::parse(&format!("name@{}", domain)),
+            Err(EmailError::TooLong { .. })
+        ));
+    }
+
+    #[test]
+    fn username_is_normalized() {
+        assert_eq!(
+            Username::parse(" SomeUser_123 ").unwrap().as_str(),
+            "someuser_123"
+        );
+        // fullwidth compatibility characters fold to ASCII
+        assert_eq!(Username::parse("Ｎａｍｅ").unwrap().as_str(), "name");
+    }
+
+    #[test]
+    fn invalid_usernames_are_rejected() {
+        assert_eq!(
+            Username::parse("ab"),
+            Err(UsernameError::TooShort { min: 3, actual: 2 })
+        );
+        assert_eq!(
+            Username::parse(&"a".repeat(33)),
+            Err(UsernameError::TooLong {
+                max: 32,
+                actual: 33
+            })
+        );
+        assert_eq!(
+            Username::parse("some user"),
+            Err(UsernameError::ForbiddenCharacter(' '))
+        );
+        assert_eq!(
+            Username::parse("root@host"),
+            Err(UsernameError::ForbiddenCharacter('@'))
+        );
+        assert_eq!(
+            Username::parse("_admin"),
+            Err(UsernameError::InvalidStart('_'))
+        );
+    }
+
+    #[test]
+    fn user_is_built_from_validated_values() {
+        let user = User::parse("name@example.com", "name").unwrap();
+        assert!(user.active);
+        assert_eq!(user.sign_in_count, 1);
+        assert_eq!(user.username.as_str(), "name");
+
+        assert_eq!(
+            User::parse("name", "name"),
+            Err(ValidationError::Email(EmailError::MissingAt))
+        );
+        assert_eq!(
+            User::parse("name@example.com", "x"),
+            Err(ValidationError::Username(UsernameError::TooShort {
+                min: 3,
+                actual: 1
+            }))
+        );
+    }
+}
@@ -0,0 +1,210 @@
+// Keeps every change applied through a UserPatch so it can be looked up per user later.
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::time::SystemTime;
+
+use super::patch::{Field, FieldChange, UserPatch};
+use super::{User, Username};
+use crate::auth::Clock;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub at: SystemTime,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditError {
+    // A history is still kept under the new username, from a user that had it before.
+    // Nothing is applied and the user comes back as it was.
+    UsernameTaken { username: Username, user: User },
+}
+
+impl Display for AuditError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditError::UsernameTaken { username, user } => write!(
+                f,
+                "cannot rename {} to {}, that name already has a history",
+                user.username, username
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AuditError {}
+
+pub struct AuditLog<C: Clock> {
+    clock: C,
+    // keyed by the current username, a rename moves the history to the new key
+    entries: HashMap<Username, Vec<AuditEntry>>,
+}
+
+impl<C: Clock> AuditLog<C> {
+    pub fn new(clock: C) -> AuditLog<C> {
+        AuditLog {
+            clock,
+            entries: HashMap::new(),
+        }
+    }
+
+    // Applies the patch and records the changes, if there were any, after the ones before.
+    // Whether the new username is free is up to the registry, the log only refuses to
+    // merge two histories.
+    pub fn apply(
+        &mut self,
+        user: User,
+        patch: UserPatch,
+    ) -> Result<(User, Vec<FieldChange>), AuditError> {
+        if let Some(new) = &patch.username {
+            if *new != user.username && self.entries.contains_key(new) {
+                return Err(AuditError::UsernameTaken {
+                    username: new.clone(),
+                    user,
+                });
+            }
+        }
+        let old_username = user.username.clone();
+        let (user, changes) = patch.apply(user);
+        if changes.is_empty() {
+            return Ok((user, changes));
+        }
+
+        let mut history = self.entries.remove(&old_username).unwrap_or_default();
+        history.push(AuditEntry {
+            at: self.clock.now(),
+            changes: changes.clone(),
+        });
+        self.entries.insert(user.username.clone(), history);
+        Ok((user, changes))
+    }
+
+    // in the order the patches were applied
+    pub fn history(&self, username: &Username) -> &[AuditEntry] {
+        self.entries
+            .get(username)
+            .map(|history| history.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn changes_to<'a>(
+        &'a self,
+        username: &Username,
+        field: Field,
+    ) -> impl Iterator<Item = (SystemTime, &'a FieldChange)> + 'a {
+        self.history(username).iter().flat_map(move |entry| {
+            entry
+                .changes
+                .iter()
+                .filter(move |change| change.field() == field)
+                .map(move |change| (entry.at, change))
+        })
+    }
+
+    // from the first entry made at or after `from` on
+    pub fn since(&self, username: &Username, from: SystemTime) -> &[AuditEntry] {
+        let history = self.history(username);
+        let start = history
+            .iter()
+            .position(|entry| entry.at >= from)
+            .unwrap_or(history.len());
+        &history[start..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::auth::FakeClock;
+    use crate::user::Email;
+
+    #[test]
+    fn history_is_recorded_per_user() {
+        let clock = FakeClock::default();
+        let mut log = AuditLog::new(clock.clone());
+        let alice = User::parse("alice@example.com", "alice").unwrap();
+        let bob = User::parse("bob@example.com", "bob").unwrap();
+
+        let (alice, _) = log.apply(alice, UserPatch::new().active(false)).unwrap();
+        clock.advance(Duration::from_secs(10));
+        let (alice, _) = log
+            .apply(
+                alice,
+                UserPatch::new().email(Email::parse("alice@example.org").unwrap()),
+            )
+            .unwrap();
+        let (bob, _) = log.apply(bob, UserPatch::new().active(true)).unwrap(); // no change, not recorded
+
+        assert_eq!(log.history(&alice.username).len(), 2);
+        assert!(log.history(&bob.username).is_empty());
+
+        let emails: Vec<_> = log.changes_to(&alice.username, Field::Email).collect();
+        assert_eq!(emails.len(), 1);
+        assert_eq!(emails[0].0, clock.now());
+
+        let recent = log.since(
+            &alice.username,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(5),
+        );
+        assert_eq!(recent.len(), 1);
+    }
+
+    #[test]
+    fn history_follows_renames() {
+        let mut log = AuditLog::new(FakeClock::default());
+        let user = User::parse("name@example.com", "name").unwrap();
+        let (user, _) = log.apply(user, UserPatch::new().sign_in_count(5)).unwrap();
+        let (user, _) = log
+            .apply(
+                user,
+                UserPatch::new().username(Username::parse("new_name").unwrap()),
+            )
+            .unwrap();
+
+        assert!(log.history(&Username::parse("name").unwrap()).is_empty());
+        assert_eq!(log.history(&user.username).len(), 2);
+    }
+
+    #[test]
+    fn renames_never_take_over_another_history() {
+        let mut log = AuditLog::new(FakeClock::default());
+        let alice = User::parse("alice@example.com", "alice").unwrap();
+        let bob = User::parse("bob@example.com", "bob").unwrap();
+        let (alice, _) = log.apply(alice, UserPatch::new().active(false)).unwrap();
+        let (bob, _) = log.apply(bob, UserPatch::new().sign_in_count(3)).unwrap();
+
+        let error = log
+            .apply(
+                bob.clone(),
+                UserPatch::new()
+                    .username(alice.username.clone())
+                    .active(false),
+            )
+            .unwrap_err();
+        assert_eq!(
+            error,
+            AuditError::UsernameTaken {
+                username: alice.username.clone(),
+                user: bob.clone(),
+            }
+        );
+        assert_eq!(log.history(&alice.username).len(), 1);
+        assert_eq!(log.history(&bob.username).len(), 1);
+
+        // a patch that keeps the name it already has is not a rename
+        let same_name = bob.username.clone();
+        let (bob, changes) = log
+            .apply(bob, UserPatch::new().username(same_name).active(false))
+            .unwrap();
+        assert_eq!(
+            changes,
+            [FieldChange::Active {
+                old: true,
+                new: false
+            }]
+        );
+        assert_eq!(log.history(&bob.username).len(), 2);
+    }
+}
@@ -0,0 +1,202 @@
+// https://doc.rust-lang.org/book/ch05-01-defining-structs.html#creating-instances-from-other-instances-with-struct-update-syntax
+// A patch only names the fields that change, everything else is taken from the old user with `..user`.
+use std::fmt::{self, Display, Formatter};
+
+use super::{Email, User, Username};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Active,
+    Username,
+    Email,
+    SignInCount,
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Field::Active => "active",
+            Field::Username => "username",
+            Field::Email => "email",
+            Field::SignInCount => "sign_in_count",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// One field that a patch actually changed, with the value before and after.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldChange {
+    Active { old: bool, new: bool },
+    Username { old: Username, new: Username },
+    Email { old: Email, new: Email },
+    SignInCount { old: u64, new: u64 },
+}
+
+impl FieldChange {
+    pub fn field(&self) -> Field {
+        match self {
+            FieldChange::Active { .. } => Field::Active,
+            FieldChange::Username { .. } => Field::Username,
+            FieldChange::Email { .. } => Field::Email,
+            FieldChange::SignInCount { .. } => Field::SignInCount,
+        }
+    }
+}
+
+impl Display for FieldChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldChange::Active { old, new } => write!(f, "{}: {} -> {}", self.field(), old, new),
+            FieldChange::Username { old, new } => {
+                write!(f, "{}: {} -> {}", self.field(), old, new)
+            }
+            FieldChange::Email { old, new } => write!(f, "{}: {} -> {}", self.field(), old, new),
+            FieldChange::SignInCount { old, new } => {
+                write!(f, "{}: {} -> {}", self.field(), old, new)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserPatch {
+    pub active: Option<bool>,
+    pub username: Option<Username>,
+    pub email: Option<Email>,
+    pub sign_in_count: Option<u64>,
+}
+
+impl UserPatch {
+    pub fn new() -> UserPatch {
+        UserPatch::default()
+    }
+
+    pub fn active(mut self, active: bool) -> UserPatch {
+        self.active = Some(active);
+        self
+    }
+
+    pub fn username(mut self, username: Username) -> UserPatch {
+        self.username = Some(username);
+        self
+    }
+
+    pub fn email(mut self, email: Email) -> UserPatch {
+        self.email = Some(email);
+        self
+    }
+
+    pub fn sign_in_count(mut self, sign_in_count: u64) -> UserPatch {
+        self.sign_in_count = Some(sign_in_count);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == UserPatch::default()
+    }
+
+    // Consumes the old user like `..user` does and returns the new one with the fields that
+    // really changed. Setting a field to the value it already has is not reported as a change.
+    pub fn apply(self, user: User) -> (User, Vec<FieldChange>) {
+        let mut user = user;
+        let mut changes = Vec::new();
+
+        if let Some(new) = self.active.filter(|&new| new != user.active) {
+            changes.push(FieldChange::Active {
+                old: user.active,
+                new,
+            });
+            user = User {
+                active: new,
+                ..user
+            };
+        }
+        if let Some(new) = self.username.filter(|new| *new != user.username) {
+            changes.push(FieldChange::Username {
+                old: user.username.clone(),
+                new: new.clone(),
+            });
+            user = User {
+                username: new,
+                ..user
+            };
+        }
+        if let Some(new) = self.email.filter(|new| *new != user.email) {
+            changes.push(FieldChange::Email {
+                old: user.email.clone(),
+                new: new.clone(),
+            });
+            user = User { email: new, ..user };
+        }
+        if let Some(new) = self.sign_in_count.filter(|&new| new != user.sign_in_count) {
+            changes.push(FieldChange::SignInCount {
+                old: user.sign_in_count,
+                new,
+            });
+            user = User {
+                sign_in_count: new,
+                ..user
+            };
+        }
+
+        (user, changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> User {
+        User::parse("another@example.com", "the_user_name").unwrap()
+    }
+
+    #[test]
+    fn empty_patch_changes_nothing() {
+        let patch = UserPatch::new();
+        assert!(patch.is_empty());
+        let (updated, changes) = patch.apply(user());
+        assert_eq!(updated, user());
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn patch_reports_old_and_new_values() {
+        let email = Email::parse("updated@email.com").unwrap();
+        let (updated, changes) = UserPatch::new()
+            .email(email.clone())
+            .active(false)
+            .apply(user());
+
+        assert_eq!(updated.email, email);
+        assert!(!updated.active);
+        assert_eq!(updated.username, user().username);
+        assert_eq!(
+            changes,
+            vec![
+                FieldChange::Active {
+                    old: true,
+                    new: false
+                },
+                FieldChange::Email {
+                    old: user().email,
+                    new: email
+                },
+            ]
+        );
+        assert_eq!(
+            changes[1].to_string(),
+            "email: another@example.com -> updated@email.com"
+        );
+    }
+
+    #[test]
+    fn unchanged_values_are_not_reported() {
+        let (_, changes) = UserPatch::new()
+            .username(user().username)
+            .sign_in_count(1)
+            .apply(user());
+        assert!(changes.is_empty());
+    }
+}
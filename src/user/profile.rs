@@ -0,0 +1,558 @@
+// https://doc.rust-lang.org/book/ch09-02-recoverable-errors-with-result.html#propagating-errors
+// What read_username_from_file grows into: a JSON profile with a username, a display name and
+// preferences, looked up in the XDG config directories, validated like a User and written
+// back so that a crash never leaves half a file. A file that is missing, one that cannot be
+// read and one that is not a profile are different errors, so only the first falls back.
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Username, UsernameError};
+use crate::files::write_atomically;
+use crate::strings::grapheme_count;
+
+pub const APP_DIR: &str = "rust-book";
+pub const FILE_NAME: &str = "profile.json";
+pub const DISPLAY_NAME_MAX_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preferences {
+    pub theme: Theme,
+    // a language tag like "en" or "pt-BR"
+    pub language: String,
+    pub notifications: bool,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Preferences {
+            theme: Theme::System,
+            language: String::from("en"),
+            notifications: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidProfile {
+    Username(UsernameError),
+    EmptyDisplayName,
+    DisplayNameTooLong { max: usize, actual: usize },
+    ControlCharacter(char),
+    InvalidLanguage(String),
+}
+
+impl Display for InvalidProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidProfile::Username(e) => write!(f, "invalid username: {}", e),
+            InvalidProfile::EmptyDisplayName => write!(f, "display name is empty"),
+            InvalidProfile::DisplayNameTooLong { max, actual } => {
+                write!(
+                    f,
+                    "display name is {} characters long, at most {} allowed",
+                    actual, max
+                )
+            }
+            InvalidProfile::ControlCharacter(c) => {
+                write!(f, "display name contains control character {:?}", c)
+            }
+            InvalidProfile::InvalidLanguage(tag) => write!(f, "{:?} is not a language tag", tag),
+        }
+    }
+}
+
+impl std::error::Error for InvalidProfile {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InvalidProfile::Username(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<UsernameError> for InvalidProfile {
+    fn from(e: UsernameError) -> Self {
+        InvalidProfile::Username(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+    // none of the places a profile can be has one
+    NotFound {
+        searched: Vec<PathBuf>,
+    },
+    // the file is there but could not be read, permissions or a directory in its place
+    Unreadable {
+        path: PathBuf,
+        error: io::Error,
+    },
+    // not JSON, or not the fields of a profile
+    Malformed {
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+    // a profile, but with values that do not pass validation
+    Invalid {
+        path: PathBuf,
+        error: InvalidProfile,
+    },
+    Unwritable {
+        path: PathBuf,
+        error: io::Error,
+    },
+    // neither XDG_CONFIG_HOME nor HOME is set, so there is nowhere to save
+    NoConfigDir,
+}
+
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::NotFound { searched } => {
+                let searched: Vec<String> =
+                    searched.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "no profile found, looked in {}", searched.join(", "))
+            }
+            ProfileError::Unreadable { path, error } => {
+                write!(f, "cannot read {}: {}", path.display(), error)
+            }
+            ProfileError::Malformed { path, error } => {
+                write!(
+                    f,
+                    "{} is not a valid profile file: {}",
+                    path.display(),
+                    error
+                )
+            }
+            ProfileError::Invalid { path, error } => write!(f, "{}: {}", path.display(), error),
+            ProfileError::Unwritable { path, error } => {
+                write!(f, "cannot write {}: {}", path.display(), error)
+            }
+            ProfileError::NoConfigDir => write!(f, "no config directory, HOME is not set"),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProfileError::Unreadable { error, .. } => Some(error),
+            ProfileError::Malformed { error, .. } => Some(error),
+            ProfileError::Invalid { error, .. } => Some(error),
+            ProfileError::Unwritable { error, .. } => Some(error),
+            ProfileError::NotFound { .. } | ProfileError::NoConfigDir => None,
+        }
+    }
+}
+
+// The file as it is on disk, before validation.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileFile {
+    username: String,
+    display_name: String,
+    #[serde(default)]
+    preferences: Preferences,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    username: Username,
+    display_name: String,
+    pub preferences: Preferences,
+}
+
+impl Profile {
+    pub fn new(username: &str, display_name: &str) -> Result<Profile, InvalidProfile> {
+        Profile::validate(ProfileFile {
+            username: username.to_string(),
+            display_name: display_name.to_string(),
+            preferences: Preferences::default(),
+        })
+    }
+
+    pub fn username(&self) -> &Username {
+        &self.username
+    }
+
+    pub fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    pub fn set_display_name(&mut self, display_name: &str) -> Result<(), InvalidProfile> {
+        self.display_name = check_display_name(display_name)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Profile, ProfileError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|error| {
+            if error.kind() == io::ErrorKind::NotFound {
+                ProfileError::NotFound {
+                    searched: vec![path.to_path_buf()],
+                }
+            } else {
+                ProfileError::Unreadable {
+                    path: path.to_path_buf(),
+                    error,
+                }
+            }
+        })?;
+        let file = serde_json::from_slice(&bytes).map_err(|error| ProfileError::Malformed {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Profile::validate(file).map_err(|error| ProfileError::Invalid {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+
+    // Writes a temporary file next to the profile, flushes it to disk and renames it over
+    // the old one, so readers see either the old profile or the new one. Missing
+    // directories are created.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProfileError> {
+        let path = path.as_ref();
+        let unwritable = |error| ProfileError::Unwritable {
+            path: path.to_path_buf(),
+            error,
+        };
+        // the preferences can be changed directly, so they are checked again
+        check_language(&self.preferences.language).map_err(|error| ProfileError::Invalid {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let file = ProfileFile {
+            username: self.username.to_string(),
+            display_name: self.display_name.clone(),
+            preferences: self.preferences.clone(),
+        };
+        let mut json = serde_json::to_vec_pretty(&file)
+            .map_err(io::Error::from)
+            .map_err(unwritable)?;
+        json.push(b'\n');
+
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(unwritable)?;
+        }
+        write_atomically(path, &json).map_err(unwritable)
+    }
+
+    fn validate(file: ProfileFile) -> Result<Profile, InvalidProfile> {
+        check_language(&file.preferences.language)?;
+        Ok(Profile {
+            username: Username::parse(&file.username)?,
+            display_name: check_display_name(&file.display_name)?,
+            preferences: file.preferences,
+        })
+    }
+}
+
+// parts of 1 to 8 letters or digits joined by '-', the shape of BCP 47 tags
+fn check_language(tag: &str) -> Result<(), InvalidProfile> {
+    let valid = tag.split('-').all(|part| {
+        (1..=8).contains(&part.len()) && part.bytes().all(|b| b.is_ascii_alphanumeric())
+    });
+    if !valid {
+        return Err(InvalidProfile::InvalidLanguage(tag.to_string()));
+    }
+    Ok(())
+}
+
+// trimmed, counted the way a reader sees the characters
+fn check_display_name(name: &str) -> Result<String, InvalidProfile> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(InvalidProfile::EmptyDisplayName);
+    }
+    if let Some(c) = name.chars().find(|c| c.is_control()) {
+        return Err(InvalidProfile::ControlCharacter(c));
+    }
+    let len = grapheme_count(name);
+    if len > DISPLAY_NAME_MAX_LEN {
+        return Err(InvalidProfile::DisplayNameTooLong {
+            max: DISPLAY_NAME_MAX_LEN,
+            actual: len,
+        });
+    }
+    Ok(name.to_string())
+}
+
+// Where a profile is looked for: the user's own file, then the system ones in order.
+// Profiles are only ever saved to the user's file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locations {
+    user: Option<PathBuf>,
+    system: Vec<PathBuf>,
+}
+
+impl Locations {
+    pub fn new(user: Option<PathBuf>, system: Vec<PathBuf>) -> Locations {
+        Locations { user, system }
+    }
+
+    // $XDG_CONFIG_HOME, or ~/.config when it is not set, then each of $XDG_CONFIG_DIRS,
+    // or /etc/xdg. Relative paths in these variables are ignored as the spec says.
+    pub fn from_env(vars: impl IntoIterator<Item = (String, String)>) -> Locations {
+        let vars: BTreeMap<String, String> = vars.into_iter().collect();
+        let absolute = |value: &str| Some(PathBuf::from(value)).filter(|p| p.is_absolute());
+
+        let file = |dir: PathBuf| dir.join(APP_DIR).join(FILE_NAME);
+
+        let user = vars
+            .get("XDG_CONFIG_HOME")
+            .and_then(|value| absolute(value))
+            .or_else(|| {
+                vars.get("HOME")
+                    .and_then(|home| absolute(home))
+                    .map(|home| home.join(".config"))
+            })
+            .map(file);
+        let system = vars
+            .get("XDG_CONFIG_DIRS")
+            .filter(|value| !value.is_empty())
+            .map_or("/etc/xdg", String::as_str)
+            .split(':')
+            .filter_map(absolute)
+            .map(file)
+            .collect();
+        Locations { user, system }
+    }
+
+    // None when neither XDG_CONFIG_HOME nor HOME gave a directory
+    pub fn user(&self) -> Option<&Path> {
+        self.user.as_deref()
+    }
+
+    // every file in the order they are tried
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.user.iter().chain(&self.system).map(PathBuf::as_path)
+    }
+
+    // The first file that exists wins. One that exists but is unreadable, malformed or
+    // invalid is an error rather than a reason to try the next.
+    pub fn load(&self) -> Result<(PathBuf, Profile), ProfileError> {
+        for path in self.paths() {
+            match Profile::load(path) {
+                Ok(profile) => return Ok((path.to_path_buf(), profile)),
+                Err(ProfileError::NotFound { .. }) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(ProfileError::NotFound {
+            searched: self.paths().map(Path::to_path_buf).collect(),
+        })
+    }
+
+    // to the user's own file, never to a system one even when there is no user directory
+    pub fn save(&self, profile: &Profile) -> Result<PathBuf, ProfileError> {
+        let path = self.user.as_ref().ok_or(ProfileError::NoConfigDir)?;
+        profile.save(path)?;
+        Ok(path.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn paths(locations: &Locations) -> Vec<PathBuf> {
+        locations.paths().map(Path::to_path_buf).collect()
+    }
+
+    #[test]
+    fn xdg_locations() {
+        let locations = Locations::from_env(env(&[("HOME", "/home/ferris")]));
+        assert_eq!(
+            paths(&locations),
+            [
+                PathBuf::from("/home/ferris/.config/rust-book/profile.json"),
+                PathBuf::from("/etc/xdg/rust-book/profile.json"),
+            ]
+        );
+
+        let locations = Locations::from_env(env(&[
+            ("HOME", "/home/ferris"),
+            ("XDG_CONFIG_HOME", "/tmp/config"),
+            ("XDG_CONFIG_DIRS", "/opt/xdg:relative:/etc/xdg"),
+        ]));
+        assert_eq!(
+            paths(&locations),
+            [
+                PathBuf::from("/tmp/config/rust-book/profile.json"),
+                PathBuf::from("/opt/xdg/rust-book/profile.json"),
+                PathBuf::from("/etc/xdg/rust-book/profile.json"),
+            ]
+        );
+
+        let nowhere = Locations::from_env(env(&[("XDG_CONFIG_HOME", "relative")]));
+        assert_eq!(nowhere.user(), None);
+        assert_eq!(paths(&nowhere).len(), 1);
+    }
+
+    #[test]
+    fn never_saves_to_a_system_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = dir.path().join("xdg");
+        let profile = Profile::new("ferris", "Ferris").unwrap();
+
+        let empty = Locations::from_env(Vec::new());
+        assert_eq!(empty.user(), None);
+        assert!(matches!(
+            empty.save(&profile),
+            Err(ProfileError::NoConfigDir)
+        ));
+
+        let locations = Locations::from_env(env(&[("XDG_CONFIG_DIRS", system.to_str().unwrap())]));
+        assert_eq!(paths(&locations), [system.join("rust-book/profile.json")]);
+        assert!(matches!(
+            locations.save(&profile),
+            Err(ProfileError::NoConfigDir)
+        ));
+        assert!(!system.exists());
+    }
+
+    #[test]
+    fn saves_and_loads_with_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = dir.path().join("user/rust-book/profile.json");
+        let system = dir.path().join("system/rust-book/profile.json");
+        let locations = Locations::new(Some(user.clone()), vec![system.clone()]);
+        assert!(matches!(
+            locations.load(),
+            Err(ProfileError::NotFound { searched }) if searched.len() == 2
+        ));
+
+        let mut defaults = Profile::new("Admin", "Site Admin").unwrap();
+        defaults.preferences.theme = Theme::Dark;
+        defaults.save(&system).unwrap();
+        let (path, loaded) = locations.load().unwrap();
+        assert_eq!((path, &loaded), (system.clone(), &defaults));
+        assert_eq!(loaded.username().as_str(), "admin");
+
+        let mut mine = Profile::new("ferris", "  Ferris the Crab ").unwrap();
+        mine.preferences.language = String::from("pt-BR");
+        assert_eq!(locations.save(&mine).unwrap(), user);
+        let (path, loaded) = locations.load().unwrap();
+        assert_eq!(
+            (path, loaded.display_name()),
+            (user.clone(), "Ferris the Crab")
+        );
+        assert_eq!(loaded, mine);
+
+        // nothing but the profile is left in the directory
+        let files: Vec<_> = fs::read_dir(user.parent().unwrap()).unwrap().collect();
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn each_failure_has_its_own_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+
+        assert!(matches!(
+            Profile::load(&path),
+            Err(ProfileError::NotFound { .. })
+        ));
+
+        fs::create_dir(&path).unwrap();
+        assert!(matches!(
+            Profile::load(&path),
+            Err(ProfileError::Unreadable { .. })
+        ));
+        // an unreadable file stops the search instead of falling back
+        let fallback = dir.path().join("other.json");
+        Profile::new("ferris", "Ferris")
+            .unwrap()
+            .save(&fallback)
+            .unwrap();
+        let locations = Locations::new(Some(path.clone()), vec![fallback]);
+        assert!(matches!(
+            locations.load(),
+            Err(ProfileError::Unreadable { .. })
+        ));
+        fs::remove_dir(&path).unwrap();
+
+        for text in [
+            "{ not json",
+            r#"{"username": "ferris"}"#,
+            r#"{"username": "ferris", "display_name": "F", "age": 3}"#,
+        ] {
+            fs::write(&path, text).unwrap();
+            assert!(
+                matches!(Profile::load(&path), Err(ProfileError::Malformed { .. })),
+                "{}",
+                text
+            );
+        }
+
+        let invalid = [
+            (
+                r#"{"username": "x", "display_name": "F"}"#,
+                "invalid username",
+            ),
+            (
+                r#"{"username": "ferris", "display_name": "  "}"#,
+                "display name is empty",
+            ),
+            (
+                r#"{"username": "ferris", "display_name": "a\tb"}"#,
+                "control character",
+            ),
+            (
+                r#"{"username": "ferris", "display_name": "F", "preferences": {"language": "en_US"}}"#,
+                "not a language tag",
+            ),
+        ];
+        for (text, message) in invalid {
+            fs::write(&path, text).unwrap();
+            let error = Profile::load(&path).unwrap_err();
+            assert!(matches!(error, ProfileError::Invalid { .. }), "{}", text);
+            assert!(error.to_string().contains(message), "{}", error);
+        }
+
+        let mut profile = Profile::new("ferris", "Ferris").unwrap();
+        assert_eq!(
+            profile.set_display_name(&"é".repeat(65)),
+            Err(InvalidProfile::DisplayNameTooLong {
+                max: 64,
+                actual: 65
+            })
+        );
+        profile.preferences.language = String::new();
+        assert!(matches!(
+            profile.save(&path),
+            Err(ProfileError::Invalid {
+                error: InvalidProfile::InvalidLanguage(_),
+                ..
+            })
+        ));
+        profile.preferences.language = String::from("en");
+        fs::write(dir.path().join("blocker"), "").unwrap();
+        assert!(matches!(
+            profile.save(dir.path().join("blocker/profile.json")),
+            Err(ProfileError::Unwritable { .. })
+        ));
+    }
+}
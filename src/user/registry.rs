@@ -0,0 +1,700 @@
+// All known users keyed by username, with bulk import and export as CSV or JSON.
+// Every imported row is validated on its own so one bad row does not hide the others.
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::patch::Field;
+use super::{User, Username, ValidationError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    #[default]
+    Skip,
+    Overwrite,
+    // abort the whole import, nothing is written
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImportOptions {
+    pub duplicates: DuplicatePolicy,
+    // validate and report without touching the registry
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportOptions {
+    // the username is the key and is always exported
+    pub exclude: Vec<Field>,
+}
+
+impl ExportOptions {
+    pub fn all_fields() -> ExportOptions {
+        ExportOptions {
+            exclude: Vec::new(),
+        }
+    }
+
+    // drops personal data, currently the email address
+    pub fn without_sensitive() -> ExportOptions {
+        ExportOptions {
+            exclude: vec![Field::Email],
+        }
+    }
+
+    fn includes(&self, field: Field) -> bool {
+        field == Field::Username || !self.exclude.contains(&field)
+    }
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions::all_fields()
+    }
+}
+
+// What a row looks like on disk. Everything is optional here, so that missing values are
+// reported per row instead of failing the whole file.
+#[derive(Debug, Deserialize)]
+struct UserRecord {
+    username: Option<String>,
+    email: Option<String>,
+    active: Option<bool>,
+    sign_in_count: Option<u64>,
+}
+
+impl UserRecord {
+    fn into_user(self) -> Result<User, RowError> {
+        let username = self.username.ok_or(RowError::MissingField("username"))?;
+        let email = self.email.ok_or(RowError::MissingField("email"))?;
+        let mut user = User::parse(&email, &username)?;
+        if let Some(active) = self.active {
+            user.active = active;
+        }
+        if let Some(sign_in_count) = self.sign_in_count {
+            user.sign_in_count = sign_in_count;
+        }
+        Ok(user)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowError {
+    Malformed(String),
+    MissingField(&'static str),
+    Invalid(ValidationError),
+}
+
+impl Display for RowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RowError::Malformed(e) => write!(f, "malformed row: {}", e),
+            RowError::MissingField(field) => write!(f, "missing field {}", field),
+            RowError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<ValidationError> for RowError {
+    fn from(e: ValidationError) -> Self {
+        RowError::Invalid(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowOutcome {
+    Inserted,
+    Overwritten,
+    Skipped,
+    Rejected(RowError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowReport {
+    // 1-based, not counting the CSV header
+    pub row: usize,
+    pub username: Option<Username>,
+    pub outcome: RowOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub rows: Vec<RowReport>,
+}
+
+impl ImportReport {
+    fn count(&self, matches: impl Fn(&RowOutcome) -> bool) -> usize {
+        self.rows.iter().filter(|row| matches(&row.outcome)).count()
+    }
+
+    pub fn inserted(&self) -> usize {
+        self.count(|outcome| *outcome == RowOutcome::Inserted)
+    }
+
+    pub fn overwritten(&self) -> usize {
+        self.count(|outcome| *outcome == RowOutcome::Overwritten)
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|outcome| *outcome == RowOutcome::Skipped)
+    }
+
+    pub fn rejected(&self) -> impl Iterator<Item = &RowReport> {
+        self.rows
+            .iter()
+            .filter(|row| matches!(row.outcome, RowOutcome::Rejected(_)))
+    }
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    // the reader failed, nothing is imported
+    Io(io::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    // only with DuplicatePolicy::Fail
+    Duplicate { row: usize, username: Username },
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "cannot read the import: {}", e),
+            ImportError::Csv(e) => write!(f, "cannot read CSV: {}", e),
+            ImportError::Json(e) => write!(f, "cannot read JSON: {}", e),
+            ImportError::Duplicate { row, username } => {
+                write!(f, "row {}: user {} already exists", row, username)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Io(e) => Some(e),
+            ImportError::Csv(e) => Some(e),
+            ImportError::Json(e) => Some(e),
+            ImportError::Duplicate { .. } => None,
+        }
+    }
+}
+
+/// Pulls the I/O error out of a CSV error, handing back any other kind.
+fn csv_io(e: csv::Error) -> Result<io::Error, csv::Error> {
+    if !e.is_io_error() {
+        return Err(e);
+    }
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => Ok(e),
+        kind => Ok(io::Error::other(format!("{:?}", kind))),
+    }
+}
+
+impl From<csv::Error> for ImportError {
+    fn from(e: csv::Error) -> Self {
+        match csv_io(e) {
+            Ok(e) => ImportError::Io(e),
+            Err(e) => ImportError::Csv(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ImportError {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            ImportError::Io(e.into())
+        } else {
+            ImportError::Json(e)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+}
+
+impl Display for ExportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "cannot write the export: {}", e),
+            ExportError::Csv(e) => write!(f, "cannot write CSV: {}", e),
+            ExportError::Json(e) => write!(f, "cannot write JSON: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExportError::Io(e) => Some(e),
+            ExportError::Csv(e) => Some(e),
+            ExportError::Json(e) => Some(e),
+        }
+    }
+}
+
+impl From<csv::Error> for ExportError {
+    fn from(e: csv::Error) -> Self {
+        match csv_io(e) {
+            Ok(e) => ExportError::Io(e),
+            Err(e) => ExportError::Csv(e),
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ExportError {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            ExportError::Io(e.into())
+        } else {
+            ExportError::Json(e)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserRegistry {
+    // BTreeMap so exports always come out in the same order
+    users: BTreeMap<Username, User>,
+}
+
+impl UserRegistry {
+    pub fn new() -> UserRegistry {
+        UserRegistry::default()
+    }
+
+    // Returns the user that was replaced, if any.
+    pub fn insert(&mut self, user: User) -> Option<User> {
+        self.users.insert(user.username.clone(), user)
+    }
+
+    pub fn get(&self, username: &Username) -> Option<&User> {
+        self.users.get(username)
+    }
+
+    pub fn remove(&mut self, username: &Username) -> Option<User> {
+        self.users.remove(username)
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &User> {
+        self.users.values()
+    }
+
+    pub fn import(
+        &mut self,
+        reader: impl Read,
+        format: Format,
+        options: ImportOptions,
+    ) -> Result<ImportReport, ImportError> {
+        let records = match format {
+            Format::Csv => read_csv(reader)?,
+            Format::Json => read_json(reader)?,
+        };
+
+        // Rows are staged first so a Fail policy or a dry run leaves the registry untouched.
+        let mut staged = self.users.clone();
+        let mut report = ImportReport {
+            dry_run: options.dry_run,
+            rows: Vec::with_capacity(records.len()),
+        };
+        for (index, (username, record)) in records.into_iter().enumerate() {
+            let row = index + 1;
+            let user = match record.and_then(UserRecord::into_user) {
+                Ok(user) => user,
+                Err(e) => {
+                    report.rows.push(RowReport {
+                        row,
+                        // whatever else is wrong with the row, its user can still be named
+                        username: username.and_then(|u| Username::parse(&u).ok()),
+                        outcome: RowOutcome::Rejected(e),
+                    });
+                    continue;
+                }
+            };
+            let username = user.username.clone();
+            let outcome = if staged.contains_key(&username) {
+                match options.duplicates {
+                    DuplicatePolicy::Skip => RowOutcome::Skipped,
+                    DuplicatePolicy::Overwrite => {
+                        staged.insert(username.clone(), user);
+                        RowOutcome::Overwritten
+                    }
+                    DuplicatePolicy::Fail => {
+                        return Err(ImportError::Duplicate { row, username });
+                    }
+                }
+            } else {
+                staged.insert(username.clone(), user);
+                RowOutcome::Inserted
+            };
+            report.rows.push(RowReport {
+                row,
+                username: Some(username),
+                outcome,
+            });
+        }
+
+        if !options.dry_run {
+            self.users = staged;
+        }
+        Ok(report)
+    }
+
+    pub fn export(
+        &self,
+        writer: impl Write,
+        format: Format,
+        options: &ExportOptions,
+    ) -> Result<(), ExportError> {
+        match format {
+            Format::Csv => self.write_csv(writer, options),
+            Format::Json => self.write_json(writer, options),
+        }
+    }
+
+    fn write_csv(&self, writer: impl Write, options: &ExportOptions) -> Result<(), ExportError> {
+        let fields = exported_fields(options);
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(fields.iter().map(|field| field.to_string()))?;
+        for user in self.iter() {
+            writer.write_record(fields.iter().map(|&field| match field_value(user, field) {
+                Value::String(s) => s,
+                other => other.to_string(),
+            }))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn write_json(&self, writer: impl Write, options: &ExportOptions) -> Result<(), ExportError> {
+        let fields = exported_fields(options);
+        let users: Vec<Value> = self
+            .iter()
+            .map(|user| {
+                let object: Map<String, Value> = fields
+                    .iter()
+                    .map(|&field| (field.to_string(), field_value(user, field)))
+                    .collect();
+                Value::Object(object)
+            })
+            .collect();
+        serde_json::to_writer_pretty(writer, &users)?;
+        Ok(())
+    }
+}
+
+// A row as read, with its raw username so a rejected row can still say whose it was.
+type Row = (Option<String>, Result<UserRecord, RowError>);
+
+fn read_csv(reader: impl Read) -> Result<Vec<Row>, ImportError> {
+    let mut reader = csv::Reader::from_reader(reader);
+    // headers are checked up front, a file without them cannot be read row by row
+    let headers = reader.headers()?.clone();
+    let username = headers.iter().position(|header| header == "username");
+    // A row the reader itself cannot get through (I/O, bad UTF-8, a ragged line) aborts
+    // the import, only rows that fail to deserialize are rejected one by one.
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let raw = username.and_then(|i| record.get(i)).map(str::to_string);
+        let parsed = record
+            .deserialize::<UserRecord>(Some(&headers))
+            .map_err(|e| RowError::Malformed(e.to_string()));
+        rows.push((raw, parsed));
+    }
+    Ok(rows)
+}
+
+fn read_json(reader: impl Read) -> Result<Vec<Row>, ImportError> {
+    let values: Vec<Value> = serde_json::from_reader(reader)?;
+    Ok(values
+        .into_iter()
+        .map(|value| {
+            let raw = value
+                .get("username")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            let parsed = serde_json::from_value::<UserRecord>(value)
+                .map_err(|e| RowError::Malformed(e.to_string()));
+            (raw, parsed)
+        })
+        .collect())
+}
+
+fn exported_fields(options: &ExportOptions) -> Vec<Field> {
+    [
+        Field::Username,
+        Field::Email,
+        Field::Active,
+        Field::SignInCount,
+    ]
+    .into_iter()
+    .filter(|&field| options.includes(field))
+    .collect()
+}
+
+fn field_value(user: &User, field: Field) -> Value {
+    match field {
+        Field::Username => Value::from(user.username.as_str()),
+        Field::Email => Value::from(user.email.as_str()),
+        Field::Active => Value::from(user.active),
+        Field::SignInCount => Value::from(user.sign_in_count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::{EmailError, UsernameError};
+
+    const CSV: &str = "\
+username,email,active,sign_in_count
+alice,alice@example.com,true,3
+bob,not-an-email,true,1
+carol,carol@example.com,maybe,1
+dave,dave@example.com,,
+";
+
+    #[test]
+    fn csv_import_reports_each_row() {
+        let mut registry = UserRegistry::new();
+        let report = registry
+            .import(CSV.as_bytes(), Format::Csv, ImportOptions::default())
+            .unwrap();
+
+        assert_eq!(report.inserted(), 2);
+        let rejected: Vec<_> = report
+            .rejected()
+            .map(|row| (row.row, row.username.as_ref().map(Username::as_str)))
+            .collect();
+        assert_eq!(rejected, vec![(2, Some("bob")), (3, Some("carol"))]);
+        assert_eq!(
+            report.rows[1].outcome,
+            RowOutcome::Rejected(RowError::Invalid(ValidationError::Email(
+                EmailError::MissingAt
+            )))
+        );
+        assert!(matches!(
+            report.rows[2].outcome,
+            RowOutcome::Rejected(RowError::Malformed(_))
+        ));
+
+        let dave = registry.get(&Username::parse("dave").unwrap()).unwrap();
+        assert!(dave.active);
+        assert_eq!(dave.sign_in_count, 1);
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    fn json_import_reports_missing_fields() {
+        let json = r#"[
+            {"username": "alice", "email": "alice@example.com"},
+            {"username": "alice!", "email": "alice@example.com"},
+            {"email": "nobody@example.com"}
+        ]"#;
+        let mut registry = UserRegistry::new();
+        let report = registry
+            .import(json.as_bytes(), Format::Json, ImportOptions::default())
+            .unwrap();
+        assert_eq!(report.inserted(), 1);
+        assert_eq!(
+            report.rows[1].outcome,
+            RowOutcome::Rejected(RowError::Invalid(ValidationError::Username(
+                UsernameError::ForbiddenCharacter('!')
+            )))
+        );
+        assert_eq!(
+            report.rows[2].outcome,
+            RowOutcome::Rejected(RowError::MissingField("username"))
+        );
+        // neither row has a username that could be a user's
+        assert!(report.rejected().all(|row| row.username.is_none()));
+    }
+
+    #[test]
+    fn dry_run_does_not_change_registry() {
+        let mut registry = UserRegistry::new();
+        let options = ImportOptions {
+            dry_run: true,
+            ..ImportOptions::default()
+        };
+        let report = registry
+            .import(CSV.as_bytes(), Format::Csv, options)
+            .unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.inserted(), 2);
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn duplicate_policies() {
+        let mut registry = UserRegistry::new();
+        registry.insert(User::parse("old@example.com", "alice").unwrap());
+        let alice = Username::parse("alice").unwrap();
+
+        let report = registry
+            .import(CSV.as_bytes(), Format::Csv, ImportOptions::default())
+            .unwrap();
+        assert_eq!(report.skipped(), 1);
+        assert_eq!(
+            registry.get(&alice).unwrap().email.as_str(),
+            "old@example.com"
+        );
+
+        let fail = ImportOptions {
+            duplicates: DuplicatePolicy::Fail,
+            ..ImportOptions::default()
+        };
+        let before = registry.clone();
+        assert!(matches!(
+            registry.import(CSV.as_bytes(), Format::Csv, fail),
+            Err(ImportError::Duplicate { row: 1, .. })
+        ));
+        assert_eq!(registry, before);
+
+        let overwrite = ImportOptions {
+            duplicates: DuplicatePolicy::Overwrite,
+            ..ImportOptions::default()
+        };
+        let report = registry
+            .import(CSV.as_bytes(), Format::Csv, overwrite)
+            .unwrap();
+        assert_eq!(report.overwritten(), 2);
+        assert_eq!(
+            registry.get(&alice).unwrap().email.as_str(),
+            "alice@example.com"
+        );
+    }
+
+    #[test]
+    fn export_round_trips_and_drops_sensitive_fields() {
+        let mut registry = UserRegistry::new();
+        registry
+            .import(CSV.as_bytes(), Format::Csv, ImportOptions::default())
+            .unwrap();
+
+        for format in [Format::Csv, Format::Json] {
+            let mut out = Vec::new();
+            registry
+                .export(&mut out, format, &ExportOptions::all_fields())
+                .unwrap();
+            let mut copy = UserRegistry::new();
+            let report = copy
+                .import(out.as_slice(), format, ImportOptions::default())
+                .unwrap();
+            assert_eq!(report.inserted(), 2);
+            assert_eq!(copy, registry);
+        }
+
+        let mut out = Vec::new();
+        registry
+            .export(&mut out, Format::Csv, &ExportOptions::without_sensitive())
+            .unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        assert_eq!(
+            csv,
+            "username,active,sign_in_count\nalice,true,3\ndave,true,1\n"
+        );
+    }
+
+    // a disk that is always full
+    struct Full;
+
+    impl Write for Full {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // a disk that goes away after the first row
+    struct Failing<'a>(&'a [u8]);
+
+    impl Read for Failing<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::Error::other("disk gone"));
+            }
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn read_failures_abort_the_import() {
+        let mut registry = UserRegistry::new();
+        let partial = "username,email\nalice,alice@example.com\n";
+        for format in [Format::Csv, Format::Json] {
+            let input = match format {
+                Format::Csv => partial,
+                Format::Json => r#"[{"username": "alice", "email": "alice@example.com"},"#,
+            };
+            let error = registry
+                .import(Failing(input.as_bytes()), format, ImportOptions::default())
+                .unwrap_err();
+            assert!(matches!(error, ImportError::Io(_)), "{:?}", error);
+            assert_eq!(error.to_string(), "cannot read the import: disk gone");
+            assert!(registry.is_empty());
+        }
+
+        // a ragged line is the file's fault, not the row's
+        let ragged = "username,email\nalice,alice@example.com\nbob\n";
+        let error = registry
+            .import(ragged.as_bytes(), Format::Csv, ImportOptions::default())
+            .unwrap_err();
+        assert!(matches!(error, ImportError::Csv(_)), "{:?}", error);
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn write_failures_are_io_errors() {
+        let mut registry = UserRegistry::new();
+        registry.insert(User::parse("alice@example.com", "alice").unwrap());
+        // enough users to fill the CSV writer's buffer before the final flush
+        let mut crowd = UserRegistry::new();
+        for i in 0..1000 {
+            let name = format!("user{}", i);
+            crowd.insert(User::parse(&format!("{}@example.com", name), &name).unwrap());
+        }
+        for registry in [&registry, &crowd] {
+            for format in [Format::Csv, Format::Json] {
+                let error = registry
+                    .export(Full, format, &ExportOptions::all_fields())
+                    .unwrap_err();
+                assert!(matches!(error, ExportError::Io(_)), "{:?}", error);
+                assert_eq!(error.to_string(), "cannot write the export: disk full");
+            }
+        }
+    }
+}
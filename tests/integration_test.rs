@@ -1,9 +1,29 @@
 mod common;
 
 use rust_book::api;
+use rust_book::user::registry::{ExportOptions, Format, ImportOptions, UserRegistry};
 
 #[test]
 fn it_adds_two() {
     common::setup();
     assert_eq!(4, api::add_two(2));
-}
\ No newline at end of file
+}
+
+#[test]
+fn users_can_be_moved_between_registries() {
+    common::setup();
+    let mut registry = UserRegistry::new();
+    let csv = "username,email\nname,name@example.com\n";
+    registry
+        .import(csv.as_bytes(), Format::Csv, ImportOptions::default())
+        .unwrap();
+
+    let mut json = Vec::new();
+    registry
+        .export(&mut json, Format::Json, &ExportOptions::all_fields())
+        .unwrap();
+    let mut copy = UserRegistry::new();
+    copy.import(json.as_slice(), Format::Json, ImportOptions::default())
+        .unwrap();
+    assert_eq!(copy, registry);
+}